@@ -0,0 +1,174 @@
+//! ZIP Archives
+//!
+//! In-memory ZIP reading and writing for processing asset packs without
+//! extracting them, on top of the `zip` crate:
+//! - Reads stored and deflated entries, including ZIP64 archives and entries
+//!   written with data descriptors (no encryption)
+//! - Writes deflated entries with UTF-8 names, switching to ZIP64 only for
+//!   entries that need it
+//!
+//! Entry names keep the archive's internal paths (`/`-separated).
+
+use std::io::{Cursor, Read, Write};
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use crate::error::{Result, PixelsError};
+
+/// One file in an archive
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZipEntry {
+    /// Path inside the archive, e.g. "characters/hero.png"
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+fn zip_error(context: &str, e: ZipError) -> PixelsError {
+    match e {
+        ZipError::UnsupportedArchive(what) => PixelsError::UnsupportedFormat(format!("{}{}", context, what)),
+        ZipError::Io(e) => PixelsError::Io(e),
+        other => PixelsError::Decode(format!("{}Malformed ZIP archive: {}", context, other)),
+    }
+}
+
+/// Read every file entry in an archive, in central directory order
+///
+/// Directory entries are skipped. Each entry's CRC-32 is checked against its
+/// decompressed data. Buffers grow with the data actually decompressed, never
+/// from the sizes the headers claim.
+pub fn read_zip(bytes: &[u8]) -> Result<Vec<ZipEntry>> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(|e| zip_error("", e))?;
+
+    let mut entries = Vec::new();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).map_err(|e| zip_error("", e))?;
+        if file.is_dir() {
+            continue;
+        }
+        let name = file.name().to_string();
+        if file.encrypted() {
+            return Err(PixelsError::UnsupportedFormat(format!("{}: encrypted ZIP entries are not supported", name)));
+        }
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)
+            .map_err(|e| PixelsError::Decode(format!("{}: {}", name, e)))?;
+        entries.push(ZipEntry { name, data });
+    }
+
+    Ok(entries)
+}
+
+/// Build a deflate-compressed archive from `entries`, in order
+pub fn write_zip(entries: &[ZipEntry]) -> Result<Vec<u8>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+    for entry in entries {
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(entry.data.len() as u64 >= u32::MAX as u64);
+        writer
+            .start_file(entry.name.as_str(), options)
+            .map_err(|e| zip_error(&format!("{}: ", entry.name), e))?;
+        writer.write_all(&entry.data)?;
+    }
+
+    let out = writer.finish().map_err(|e| zip_error("", e))?;
+    Ok(out.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Signature of a central directory file header
+    const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+
+    #[test]
+    fn test_zip_roundtrip() {
+        let entries = vec![
+            ZipEntry { name: "readme.txt".to_string(), data: b"hello hello hello".to_vec() },
+            ZipEntry { name: "sprites/hero.png".to_string(), data: (0..=255u8).cycle().take(5000).collect() },
+            ZipEntry { name: "empty".to_string(), data: Vec::new() },
+        ];
+        let bytes = write_zip(&entries).unwrap();
+        assert_eq!(read_zip(&bytes).unwrap(), entries);
+    }
+
+    #[test]
+    fn test_read_zip_rejects_corruption() {
+        let entries = vec![ZipEntry { name: "a.txt".to_string(), data: vec![7; 100] }];
+        let mut bytes = write_zip(&entries).unwrap();
+        assert!(read_zip(&bytes[..bytes.len() - 4]).is_err());
+
+        // Flip a bit in the stored CRC of the local and central headers
+        bytes[14] ^= 1;
+        let central = bytes.windows(4).position(|w| w == CENTRAL_HEADER_SIG.to_le_bytes()).unwrap();
+        bytes[central + 16] ^= 1;
+        assert!(matches!(read_zip(&bytes), Err(PixelsError::Decode(_))));
+    }
+
+    #[test]
+    fn test_read_zip_handles_zip64() {
+        // large_file forces ZIP64 extra fields even for a small entry
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(true);
+        writer.start_file("sprites/hero.png", options).unwrap();
+        writer.write_all(&[9; 300]).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let entries = read_zip(&bytes).unwrap();
+        assert_eq!(entries, vec![ZipEntry { name: "sprites/hero.png".to_string(), data: vec![9; 300] }]);
+    }
+
+    #[test]
+    fn test_read_zip_handles_data_descriptors() {
+        // A streamed writer leaves the local header's CRC and sizes at zero
+        // and writes them in a descriptor after the data
+        let (name, data) = (b"a.txt", b"hello");
+        let crc = crc32fast::hash(data).to_le_bytes();
+        let size = (data.len() as u32).to_le_bytes();
+        let u16le = |v: u16| v.to_le_bytes();
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        bytes.extend_from_slice(&[u16le(20), u16le(1 << 3), u16le(0), u16le(0), u16le(0x21)].concat());
+        bytes.extend_from_slice(&[0; 12]);
+        bytes.extend_from_slice(&[u16le(name.len() as u16), u16le(0)].concat());
+        bytes.extend_from_slice(name);
+        bytes.extend_from_slice(data);
+        bytes.extend_from_slice(&[0x0807_4b50u32.to_le_bytes(), crc, size, size].concat());
+
+        let central_offset = bytes.len() as u32;
+        bytes.extend_from_slice(&CENTRAL_HEADER_SIG.to_le_bytes());
+        bytes.extend_from_slice(&[u16le(20), u16le(20), u16le(1 << 3), u16le(0), u16le(0), u16le(0x21)].concat());
+        bytes.extend_from_slice(&[crc, size, size].concat());
+        bytes.extend_from_slice(&[u16le(name.len() as u16), u16le(0), u16le(0), u16le(0), u16le(0)].concat());
+        bytes.extend_from_slice(&[0; 8]); // external attributes, local header offset
+        bytes.extend_from_slice(name);
+        let central_len = bytes.len() as u32 - central_offset;
+
+        bytes.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        bytes.extend_from_slice(&[u16le(0), u16le(0), u16le(1), u16le(1)].concat());
+        bytes.extend_from_slice(&[central_len.to_le_bytes(), central_offset.to_le_bytes()].concat());
+        bytes.extend_from_slice(&u16le(0));
+
+        let entries = read_zip(&bytes).unwrap();
+        assert_eq!(entries, vec![ZipEntry { name: "a.txt".to_string(), data: data.to_vec() }]);
+    }
+
+    #[test]
+    fn test_read_zip_ignores_claimed_sizes() {
+        // A header claiming a huge uncompressed size is never used to size a
+        // buffer; the entry is as long as the data that actually inflates
+        let entries = vec![ZipEntry { name: "a.txt".to_string(), data: vec![7; 100] }];
+        let mut bytes = write_zip(&entries).unwrap();
+        let central = bytes.windows(4).position(|w| w == CENTRAL_HEADER_SIG.to_le_bytes()).unwrap();
+        bytes[central + 24..central + 28].copy_from_slice(&(u32::MAX - 1).to_le_bytes());
+        bytes[22..26].copy_from_slice(&(u32::MAX - 1).to_le_bytes());
+
+        assert_eq!(read_zip(&bytes).unwrap(), entries);
+    }
+}
@@ -0,0 +1,239 @@
+//! Color Utilities
+//!
+//! Shared color math for the processor and downscaler:
+//! - sRGB <-> CIE LAB (D65) conversion, as `Rgb`/`Lab` newtypes or plain tuples
+//! - Delta E76 color difference
+//! - Rec. 601 luma
+//!
+//! The tuple functions are what the pipelines call in hot loops; the
+//! newtypes are for callers that want the two spaces kept apart by type.
+
+/// An sRGB color (0-255 per channel)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+/// A CIE LAB color: L in 0-100, a and b roughly -128..128
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lab(pub f32, pub f32, pub f32);
+
+impl From<Rgb> for Lab {
+    fn from(c: Rgb) -> Self {
+        let (l, a, b) = rgb_to_lab(c.0, c.1, c.2);
+        Lab(l, a, b)
+    }
+}
+
+impl From<Lab> for Rgb {
+    fn from(c: Lab) -> Self {
+        let (r, g, b) = lab_to_rgb(c.0, c.1, c.2);
+        Rgb(r, g, b)
+    }
+}
+
+impl Lab {
+    /// Delta E76 distance to `other`
+    pub fn delta_e76(self, other: Lab) -> f32 {
+        delta_e76((self.0, self.1, self.2), (other.0, other.1, other.2))
+    }
+}
+
+// ============================================================================
+// COLOR SPACE CONVERSIONS (sRGB <-> XYZ <-> LAB)
+// Exact match to Python's rgb_to_lab() and lab_to_rgb()
+// ============================================================================
+
+/// Convert sRGB (0-255) to LAB color space
+pub fn rgb_to_lab(r: u8, g: u8, b: u8) -> (f32, f32, f32) {
+    // Step 1: sRGB to Linear RGB (gamma correction)
+    fn srgb_to_linear(c: u8) -> f32 {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    let rl = srgb_to_linear(r);
+    let gl = srgb_to_linear(g);
+    let bl = srgb_to_linear(b);
+
+    // Step 2: Linear RGB to XYZ (standard matrix)
+    let x = rl * 0.4124564 + gl * 0.3575761 + bl * 0.1804375;
+    let y = rl * 0.2126729 + gl * 0.7151522 + bl * 0.0721750;
+    let z = rl * 0.0193339 + gl * 0.1191920 + bl * 0.9503041;
+
+    // Step 3: XYZ to LAB (D65 illuminant)
+    const XN: f32 = 0.95047;
+    const YN: f32 = 1.00000;
+    const ZN: f32 = 1.08883;
+
+    let xr = x / XN;
+    let yr = y / YN;
+    let zr = z / ZN;
+
+    fn f(t: f32) -> f32 {
+        if t > 0.008856 {
+            t.powf(1.0 / 3.0)
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    }
+
+    let fx = f(xr);
+    let fy = f(yr);
+    let fz = f(zr);
+
+    let l = (116.0 * fy - 16.0).max(0.0);
+    let a = 500.0 * (fx - fy);
+    let lab_b = 200.0 * (fy - fz);
+
+    (l, a, lab_b)
+}
+
+/// Convert LAB color space back to sRGB (0-255)
+pub fn lab_to_rgb(l: f32, a: f32, b: f32) -> (u8, u8, u8) {
+    // Step 1: LAB to XYZ
+    const XN: f32 = 0.95047;
+    const YN: f32 = 1.00000;
+    const ZN: f32 = 1.08883;
+
+    let fy = (l + 16.0) / 116.0;
+    let fx = a / 500.0 + fy;
+    let fz = fy - b / 200.0;
+
+    fn f_inv(t: f32) -> f32 {
+        let t3 = t * t * t;
+        if t3 > 0.008856 {
+            t3
+        } else {
+            (t - 16.0 / 116.0) / 7.787
+        }
+    }
+
+    let xr = f_inv(fx);
+    let yr = f_inv(fy);
+    let zr = f_inv(fz);
+
+    let x = xr * XN;
+    let y = yr * YN;
+    let z = zr * ZN;
+
+    // Step 2: XYZ to Linear RGB (inverse matrix)
+    let rl = x *  3.2404542 + y * -1.5371385 + z * -0.4985314;
+    let gl = x * -0.9692660 + y *  1.8760108 + z *  0.0415560;
+    let bl = x *  0.0556434 + y * -0.2040259 + z *  1.0572252;
+
+    // Step 3: Linear RGB to sRGB (inverse gamma)
+    fn linear_to_srgb(c: f32) -> u8 {
+        let c = c.clamp(0.0, 1.0);
+        let v = if c <= 0.0031308 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+        (v * 255.0).round().clamp(0.0, 255.0) as u8
+    }
+
+    (linear_to_srgb(rl), linear_to_srgb(gl), linear_to_srgb(bl))
+}
+
+/// Calculate Delta E76 color difference in LAB space
+pub fn delta_e76(lab1: (f32, f32, f32), lab2: (f32, f32, f32)) -> f32 {
+    let dl = lab1.0 - lab2.0;
+    let da = lab1.1 - lab2.1;
+    let db = lab1.2 - lab2.2;
+    (dl * dl + da * da + db * db).sqrt()
+}
+
+// ============================================================================
+// LUMA
+// ============================================================================
+
+/// Rec. 601 luma (0-255), rounded
+pub fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((299 * r as u32 + 587 * g as u32 + 114 * b as u32 + 500) / 1000) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random colors for property checks
+    fn random_colors(count: usize) -> Vec<Rgb> {
+        let mut state = 0x2545_f491u32;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        (0..count)
+            .map(|_| {
+                let v = next();
+                Rgb(v as u8, (v >> 8) as u8, (v >> 16) as u8)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rgb_lab_roundtrip() {
+        let test_colors = [
+            (255, 0, 0), (0, 255, 0), (0, 0, 255),
+            (255, 255, 255), (0, 0, 0), (128, 128, 128),
+            (17, 6, 2), // Default outline color
+        ];
+
+        for (r, g, b) in test_colors {
+            let lab = rgb_to_lab(r, g, b);
+            let (r2, g2, b2) = lab_to_rgb(lab.0, lab.1, lab.2);
+            assert!((r as i16 - r2 as i16).abs() <= 1, "Red mismatch for ({}, {}, {})", r, g, b);
+            assert!((g as i16 - g2 as i16).abs() <= 1, "Green mismatch for ({}, {}, {})", r, g, b);
+            assert!((b as i16 - b2 as i16).abs() <= 1, "Blue mismatch for ({}, {}, {})", r, g, b);
+        }
+    }
+
+    #[test]
+    fn test_delta_e76_same_color() {
+        let lab = rgb_to_lab(100, 100, 100);
+        assert!(delta_e76(lab, lab) < 0.001);
+    }
+
+    #[test]
+    fn test_random_colors_roundtrip_and_stay_stable() {
+        for c in random_colors(2000) {
+            let lab = Lab::from(c);
+            assert!((0.0..=100.0).contains(&lab.0), "L out of range for {:?}: {:?}", c, lab);
+
+            let back = Rgb::from(lab);
+            for (x, y) in [(c.0, back.0), (c.1, back.1), (c.2, back.2)] {
+                assert!((x as i16 - y as i16).abs() <= 1, "{:?} came back as {:?}", c, back);
+            }
+            // A second pass through LAB lands on the same color
+            assert_eq!(Rgb::from(Lab::from(back)), back);
+        }
+    }
+
+    #[test]
+    fn test_random_delta_e76_is_a_metric() {
+        let colors: Vec<Lab> = random_colors(60).into_iter().map(Lab::from).collect();
+        for (i, &a) in colors.iter().enumerate() {
+            assert!(a.delta_e76(a) < 0.001);
+            for &b in &colors[i + 1..] {
+                let d = a.delta_e76(b);
+                assert!(d >= 0.0);
+                assert!((d - b.delta_e76(a)).abs() < 1e-4);
+                for &c in &colors[..4] {
+                    assert!(d <= a.delta_e76(c) + c.delta_e76(b) + 1e-3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_luma_endpoints() {
+        assert_eq!(luma(0, 0, 0), 0);
+        assert_eq!(luma(255, 255, 255), 255);
+        assert!(luma(0, 255, 0) > luma(255, 0, 0));
+    }
+}
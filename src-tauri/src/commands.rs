@@ -0,0 +1,1592 @@
+//! Tauri Commands
+//!
+//! Thin adapters between the frontend and the library modules: each command
+//! turns its arguments into paths and settings, runs one library function on
+//! a blocking thread and returns its result. Only built with the `app`
+//! feature.
+
+use std::path::PathBuf;
+use tauri::{Emitter, Manager};
+use crate::error::{self, Result};
+use crate::{downscaler, packer, palette, processor, state};
+use packer::{PackerSettings, PackerResult};
+use processor::{
+    ProcessorSettings, ProcessorResult,
+    AlphaSettings, MergeSettings, OutlineSettings,
+    MergeResult, OutlineDetectionResult, BlurKind, SymmetryAxis, Side, TileReport,
+    SaveOptions, SourceInfo, ColorMatch, PipelineConfig, SaveResult, StageTimings, TintMode, Connectivity,
+};
+use downscaler::{DownscalerSettings, DownscaleResult, ManualDownscaleSettings, BgRemovalMode, PreviewDownscaleSettings};
+use crate::db::{Database, Project, ProjectSettings, SessionState};
+use state::{WorkspaceManager, WorkspaceState};
+
+#[tauri::command]
+async fn pack_sprites_command(
+    input_paths: Vec<String>,
+    output_path: String,
+    settings: PackerSettings,
+) -> Result<PackerResult> {
+    let paths: Vec<PathBuf> = input_paths.iter().map(PathBuf::from).collect();
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        packer::pack_sprites(paths, output, settings)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Thumbnail strip of the inputs in the order they would be packed
+/// Returns PNG bytes and the metadata keys left to right
+#[tauri::command]
+async fn pack_preview_command(
+    input_paths: Vec<String>,
+    settings: PackerSettings,
+) -> Result<packer::PackPreview> {
+    let paths: Vec<PathBuf> = input_paths.iter().map(PathBuf::from).collect();
+
+    tokio::task::spawn_blocking(move || {
+        packer::pack_preview(paths, &settings)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Pack the current version of every workspace source into one sheet
+/// `output_path` is relative to the workspace root
+#[tauri::command]
+async fn pack_workspace_command(
+    workspace_path: String,
+    settings: PackerSettings,
+    output_path: String,
+) -> Result<PackerResult> {
+    let ws_path = PathBuf::from(workspace_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        packer::pack_workspace(&ws_path, &output, settings)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Add sprites to an existing sheet without moving what's already on it
+/// The sheet and its metadata JSON are rewritten in place
+#[tauri::command]
+async fn append_to_sheet_command(
+    sheet_path: String,
+    metadata_path: String,
+    input_paths: Vec<String>,
+    settings: PackerSettings,
+) -> Result<PackerResult> {
+    let sheet = PathBuf::from(sheet_path);
+    let metadata = PathBuf::from(metadata_path);
+    let paths: Vec<PathBuf> = input_paths.iter().map(PathBuf::from).collect();
+
+    tokio::task::spawn_blocking(move || {
+        packer::append_to_sheet(&sheet, &metadata, paths, settings)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Check a sprite sheet against its metadata JSON
+/// Returns a list of problems (empty if the atlas is consistent)
+#[tauri::command]
+async fn validate_atlas_command(sheet_path: String, metadata_path: String) -> Result<Vec<String>> {
+    let sheet = PathBuf::from(sheet_path);
+    let metadata = PathBuf::from(metadata_path);
+
+    tokio::task::spawn_blocking(move || {
+        packer::validate_atlas_files(&sheet, &metadata)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+#[tauri::command]
+async fn process_image_command(
+    input_path: String,
+    output_path: String,
+    settings: ProcessorSettings,
+    save_options: Option<SaveOptions>,
+) -> Result<ProcessorResult> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::process_image(input, output, settings, &save_options.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+#[tauri::command]
+async fn downscale_image_command(
+    input_path: String,
+    output_path: String,
+    settings: DownscalerSettings,
+    save_options: Option<SaveOptions>,
+) -> Result<DownscaleResult> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        downscaler::downscale_image_with_options(input, output, settings, &save_options.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Downscale to explicit target dimensions and save, for when grid detection fails
+/// With `preserve_aspect`, the image is fitted inside the box and letterboxed
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn downscale_to_dimensions_command(
+    input_path: String,
+    output_path: String,
+    target_width: u32,
+    target_height: u32,
+    preserve_aspect: bool,
+    auto_trim: bool,
+    save_options: Option<SaveOptions>,
+) -> Result<DownscaleResult> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        let settings = ManualDownscaleSettings {
+            target_width,
+            target_height,
+            auto_trim,
+            preserve_aspect,
+        };
+        downscaler::downscale_image_to_dimensions(input, output, &settings, &save_options.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Predict the (width, height) a downscale would produce, without rendering it
+#[tauri::command]
+async fn predict_output_size_command(
+    input_path: String,
+    settings: DownscalerSettings,
+) -> Result<(u32, u32)> {
+    let input = PathBuf::from(input_path);
+
+    tokio::task::spawn_blocking(move || {
+        downscaler::predict_output_size_for_file(&input, &settings)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Downscale every image in a folder, reporting each file's detected scale
+/// With `resume`, files finished by an interrupted run are skipped
+#[tauri::command]
+async fn downscale_folder_command(
+    input_dir: String,
+    output_dir: String,
+    settings: DownscalerSettings,
+    manifest: Option<bool>,
+    dry_run: Option<bool>,
+    resume: Option<bool>,
+) -> Result<Vec<downscaler::FolderDownscaleEntry>> {
+    let input = PathBuf::from(input_dir);
+    let output = PathBuf::from(output_dir);
+    let options = downscaler::BatchOptions {
+        manifest: manifest.unwrap_or(false),
+        dry_run: dry_run.unwrap_or(false),
+        resume: resume.unwrap_or(false),
+    };
+
+    tokio::task::spawn_blocking(move || {
+        downscaler::downscale_folder(&input, &output, &settings, options)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Suggest a canvas_multiple that fits most sprites in a folder
+#[tauri::command]
+async fn suggest_canvas_multiple_command(folder_path: String) -> Result<u32> {
+    let folder = PathBuf::from(folder_path);
+
+    tokio::task::spawn_blocking(move || {
+        downscaler::suggest_canvas_multiple_for_folder(&folder)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Detect scale factor of an image without modifying it
+#[tauri::command]
+async fn detect_scale_command(
+    input_path: String,
+) -> Result<downscaler::ScaleDetectionResult> {
+    let input = PathBuf::from(input_path);
+
+    tokio::task::spawn_blocking(move || {
+        downscaler::detect_scale(input)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Report the bounding box and centroid of an image's non-transparent content
+#[tauri::command]
+async fn content_bounds_command(
+    input_path: String,
+) -> Result<Option<downscaler::BoundsInfo>> {
+    let input = PathBuf::from(input_path);
+
+    tokio::task::spawn_blocking(move || {
+        let img = processor::load_image(&input)?;
+        Ok(downscaler::content_bounds(&img))
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Suggest a pivot at the sprite's ground contact (feet)
+/// Returns normalized (x, y), or None for a fully transparent image
+#[tauri::command]
+async fn detect_ground_contact_command(input_path: String) -> Result<Option<(f32, f32)>> {
+    let input = PathBuf::from(input_path);
+
+    tokio::task::spawn_blocking(move || {
+        let img = processor::load_image(&input)?;
+        Ok(downscaler::detect_ground_contact(&img))
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Measure how well a downscaled image reproduces its source at a scale
+#[tauri::command]
+async fn downscale_quality_command(
+    original_path: String,
+    downscaled_path: String,
+    scale: u32,
+) -> Result<downscaler::QualityReport> {
+    let original = PathBuf::from(original_path);
+    let downscaled = PathBuf::from(downscaled_path);
+
+    tokio::task::spawn_blocking(move || {
+        downscaler::downscale_quality_for_files(&original, &downscaled, scale)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+// ============================================================================
+// V2 INDIVIDUAL OPERATION COMMANDS
+// ============================================================================
+
+/// Load image and normalize alpha channel
+#[tauri::command]
+async fn normalize_alpha_command(
+    input_path: String,
+    output_path: String,
+    settings: AlphaSettings,
+    save_options: Option<SaveOptions>,
+) -> Result<()> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            processor::normalize_alpha(img, &settings);
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and normalize alpha with per-color thresholds
+/// Pixels matching no rule use `default_settings` (or the standard defaults)
+#[tauri::command]
+async fn normalize_alpha_by_color_command(
+    input_path: String,
+    output_path: String,
+    rules: Vec<(ColorMatch, AlphaSettings)>,
+    default_settings: Option<AlphaSettings>,
+    save_options: Option<SaveOptions>,
+) -> Result<()> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            let default = default_settings.unwrap_or_default();
+            processor::normalize_alpha_by_color(img, &rules, &default);
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and merge similar colors
+/// Optional region (x, y, w, h) limits the merge to a sub-rectangle
+#[tauri::command]
+async fn merge_colors_command(
+    input_path: String,
+    output_path: String,
+    settings: MergeSettings,
+    region: Option<(u32, u32, u32, u32)>,
+    save_options: Option<SaveOptions>,
+) -> Result<MergeResult> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            let result = processor::apply_in_region(img, region, |sub| {
+                processor::merge_colors(sub, &settings)
+            });
+            Ok(result)
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and merge colors until about `target_fraction` of them remain
+#[tauri::command]
+async fn merge_to_reduction_command(
+    input_path: String,
+    output_path: String,
+    target_fraction: f32,
+    save_options: Option<SaveOptions>,
+) -> Result<MergeResult> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            Ok(processor::merge_to_reduction(img, target_fraction))
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and add outline
+/// Optional region (x, y, w, h) limits the outline to a sub-rectangle
+#[tauri::command]
+async fn add_outline_command(
+    input_path: String,
+    output_path: String,
+    settings: OutlineSettings,
+    region: Option<(u32, u32, u32, u32)>,
+    background_mask_path: Option<String>,
+    save_options: Option<SaveOptions>,
+) -> Result<()> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+    let mask = background_mask_path.map(PathBuf::from);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            processor::add_outline_in_region(img, &settings, region, mask.as_deref())
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and outline the boundary of a separate silhouette mask
+/// (white = inside) instead of the image's own transparency
+#[tauri::command]
+async fn add_outline_from_mask_command(
+    input_path: String,
+    mask_path: String,
+    output_path: String,
+    settings: OutlineSettings,
+    save_options: Option<SaveOptions>,
+) -> Result<()> {
+    let input = PathBuf::from(input_path);
+    let mask_path = PathBuf::from(mask_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            let mask = processor::decode_image(&mask_path)?.to_luma8();
+            processor::add_outline_from_mask(img, &mask, &settings)?;
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Detect if image already has an outline
+#[tauri::command]
+async fn detect_outline_command(input_path: String) -> Result<OutlineDetectionResult> {
+    let input = PathBuf::from(input_path);
+
+    tokio::task::spawn_blocking(move || {
+        let img = processor::load_image(&input)?;
+        Ok(processor::detect_outline(&img))
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and thin a thick outline band down to `target_thickness`
+/// Returns the number of pixels changed
+#[tauri::command]
+async fn thin_outline_command(
+    input_path: String,
+    output_path: String,
+    target_thickness: u32,
+    settings: OutlineSettings,
+    save_options: Option<SaveOptions>,
+) -> Result<u32> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            Ok(processor::thin_outline(img, target_thickness, &settings))
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Find the best grid phase for a known scale
+/// Returns (phase_x, phase_y, variance)
+#[tauri::command]
+async fn find_phase_command(input_path: String, scale: u32) -> Result<(u32, u32, f32)> {
+    let input = PathBuf::from(input_path);
+
+    tokio::task::spawn_blocking(move || {
+        downscaler::find_phase_for_file(&input, scale)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Downsample with an exact scale and phase (no detection)
+#[tauri::command]
+async fn downsample_with_fixed_scale_phase_command(
+    input_path: String,
+    output_path: String,
+    scale: u32,
+    phase_x: u32,
+    phase_y: u32,
+    save_options: Option<SaveOptions>,
+) -> Result<()> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        downscaler::downsample_file(&input, &output, scale, phase_x, phase_y, &save_options.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and clear its background region
+/// Returns the number of pixels made transparent. If `mask_output_path` is
+/// given, also writes a mask of the removed pixels (white = background) for
+/// use with `add_outline_command`.
+#[tauri::command]
+async fn remove_background_command(
+    input_path: String,
+    output_path: String,
+    mode: BgRemovalMode,
+    tolerance: u32,
+    connectivity: Option<Connectivity>,
+    mask_output_path: Option<String>,
+    save_options: Option<SaveOptions>,
+) -> Result<usize> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+    let mask_output = mask_output_path.map(PathBuf::from);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            downscaler::remove_background_with_mask(img, &mode, tolerance, &connectivity.unwrap_or_default(), mask_output.as_deref())
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and blur it (alpha-aware)
+#[tauri::command]
+async fn blur_command(
+    input_path: String,
+    output_path: String,
+    radius: f32,
+    kind: BlurKind,
+    save_options: Option<SaveOptions>,
+) -> Result<()> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            processor::blur(img, radius, kind);
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and mirror one half onto the other
+#[tauri::command]
+async fn enforce_symmetry_command(
+    input_path: String,
+    output_path: String,
+    axis: SymmetryAxis,
+    source_side: Side,
+    save_options: Option<SaveOptions>,
+) -> Result<()> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            processor::enforce_symmetry(img, axis, source_side);
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and convert it to grayscale, sepia or a duotone
+#[tauri::command]
+async fn tint_command(
+    input_path: String,
+    output_path: String,
+    mode: TintMode,
+    save_options: Option<SaveOptions>,
+) -> Result<()> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            processor::tint(img, mode);
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and recolor it with an ordered list of mapping rules
+/// Returns how many pixels each rule changed
+#[tauri::command]
+async fn apply_color_mappings_command(
+    input_path: String,
+    output_path: String,
+    mappings: Vec<(ColorMatch, (u8, u8, u8))>,
+    save_options: Option<SaveOptions>,
+) -> Result<Vec<usize>> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            Ok(processor::apply_color_mappings(img, &mappings))
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and clean up compression-style color noise
+/// Returns the number of pixels changed
+#[tauri::command]
+async fn deartifact_command(
+    input_path: String,
+    output_path: String,
+    strength: f32,
+    save_options: Option<SaveOptions>,
+) -> Result<usize> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            Ok(processor::deartifact(img, strength))
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Time downscale, merge, outline and pack on a sample image
+/// Returns milliseconds and megapixels per second for each operation
+#[tauri::command]
+async fn benchmark_command(sample_image_path: String) -> Result<Vec<processor::BenchmarkEntry>> {
+    let sample = PathBuf::from(sample_image_path);
+
+    tokio::task::spawn_blocking(move || processor::benchmark(&sample))
+        .await
+        .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and bleed edge colors into neighboring transparent pixels
+#[tauri::command]
+async fn bleed_edges_command(
+    input_path: String,
+    output_path: String,
+    iterations: u32,
+    save_options: Option<SaveOptions>,
+) -> Result<()> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            processor::bleed_edges(img, iterations);
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and repaint connected regions of similar color with their average
+/// Returns the number of regions
+#[tauri::command]
+async fn flatten_regions_command(
+    input_path: String,
+    output_path: String,
+    delta_e: f32,
+    connectivity: Option<Connectivity>,
+    save_options: Option<SaveOptions>,
+) -> Result<usize> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            Ok(processor::flatten_regions(img, delta_e, &connectivity.unwrap_or_default()))
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and remap its colors to the palette of a reference image
+#[tauri::command]
+async fn remap_to_reference_command(
+    input_path: String,
+    reference_path: String,
+    output_path: String,
+    save_options: Option<SaveOptions>,
+) -> Result<()> {
+    let input = PathBuf::from(input_path);
+    let reference = PathBuf::from(reference_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            let reference_img = processor::load_image(&reference)?;
+            processor::remap_to_reference(img, &reference_img);
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load palette colors from an Aseprite JSON export or .ase/.aseprite file
+#[tauri::command]
+async fn load_aseprite_palette_command(path: String) -> Result<Vec<(u8, u8, u8, u8)>> {
+    let path = PathBuf::from(path);
+
+    tokio::task::spawn_blocking(move || palette::load_aseprite_palette(&path))
+        .await
+        .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Export an image's colors as a .gpl or .hex palette, in the given order
+/// Returns the number of colors written
+#[tauri::command]
+async fn export_palette_command(
+    input_path: String,
+    output_path: String,
+    sort: Option<palette::PaletteSort>,
+) -> Result<usize> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        let img = processor::load_image(&input)?;
+        palette::export_palette(&img, &output, sort.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Active palette watchers, keyed by the watched path
+#[derive(Default)]
+struct PaletteWatchers(std::sync::Mutex<std::collections::HashMap<String, palette::PaletteWatcher>>);
+
+#[derive(Clone, serde::Serialize)]
+struct PaletteChangedEvent {
+    path: String,
+    colors: Vec<(u8, u8, u8, u8)>,
+}
+
+/// Watch a palette file, emitting `palette-changed` with the parsed colors
+/// whenever it changes on disk. Replaces any existing watch on the same path.
+#[tauri::command]
+fn watch_palette_command(
+    app: tauri::AppHandle,
+    watchers: tauri::State<PaletteWatchers>,
+    palette_path: String,
+) -> Result<()> {
+    let event_path = palette_path.clone();
+    let watcher = palette::PaletteWatcher::watch(PathBuf::from(&palette_path), move |colors| {
+        let _ = app.emit("palette-changed", PaletteChangedEvent { path: event_path.clone(), colors });
+    })?;
+    watchers.0.lock().unwrap().insert(palette_path, watcher);
+    Ok(())
+}
+
+/// Stop watching a palette file (no-op if it isn't watched)
+#[tauri::command]
+fn stop_watch_command(watchers: tauri::State<PaletteWatchers>, palette_path: String) -> Result<()> {
+    watchers.0.lock().unwrap().remove(&palette_path);
+    Ok(())
+}
+
+/// Merge colors until the saved PNG fits within `max_bytes` (best effort)
+#[tauri::command]
+async fn quantize_to_size_command(
+    input_path: String,
+    output_path: String,
+    max_bytes: usize,
+    save_options: Option<SaveOptions>,
+) -> Result<processor::SizeBudgetResult> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            let (quantized, report) = processor::quantize_to_size_report(img, max_bytes)?;
+            *img = quantized;
+            Ok(report)
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and snap its colors to the given palette
+#[tauri::command]
+async fn remap_to_palette_command(
+    input_path: String,
+    output_path: String,
+    palette: Vec<(u8, u8, u8, u8)>,
+    save_options: Option<SaveOptions>,
+) -> Result<()> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            processor::remap_to_palette(img, &palette);
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Report how well an image's opposite edges match when tiled
+#[tauri::command]
+async fn check_tileable_command(input_path: String) -> Result<TileReport> {
+    let input = PathBuf::from(input_path);
+
+    tokio::task::spawn_blocking(move || {
+        let img = processor::load_image(&input)?;
+        Ok(processor::check_tileable(&img))
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load image and cross-blend its edges so it tiles seamlessly
+#[tauri::command]
+async fn make_tileable_command(
+    input_path: String,
+    output_path: String,
+    blend: u32,
+    save_options: Option<SaveOptions>,
+) -> Result<TileReport> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::edit_file(&input, &output, &save_options.unwrap_or_default(), |img| {
+            processor::make_tileable(img, blend);
+            Ok(processor::check_tileable(img))
+        })
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Compare each image in `rust_dir` with the same-named image in
+/// `reference_dir`; pairs pass at `min_similarity` (default 0.99) or above
+#[tauri::command]
+async fn compare_folders_command(
+    rust_dir: String,
+    reference_dir: String,
+    min_similarity: Option<f32>,
+) -> Result<processor::FolderComparison> {
+    let rust_dir = PathBuf::from(rust_dir);
+    let reference_dir = PathBuf::from(reference_dir);
+
+    tokio::task::spawn_blocking(move || {
+        processor::compare_folders(
+            &rust_dir,
+            &reference_dir,
+            min_similarity.unwrap_or(processor::DEFAULT_MIN_SIMILARITY),
+        )
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Generate downscale-only preview with manual target dimensions
+/// Returns PNG bytes for live preview without saving
+#[tauri::command]
+async fn downscale_preview_command(
+    input_path: String,
+    target_width: u32,
+    target_height: u32,
+    auto_trim: bool,
+) -> Result<Vec<u8>> {
+    let input = PathBuf::from(input_path);
+
+    tokio::task::spawn_blocking(move || {
+        let settings = ManualDownscaleSettings {
+            target_width,
+            target_height,
+            auto_trim,
+            preserve_aspect: false,
+        };
+        downscaler::manual_preview_png(&input, &settings)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Generate preview PNG bytes without saving to disk
+/// Inputs over `max_megapixels` are refused (default: no limit).
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn generate_preview_command(
+    input_path: String,
+    downscale_settings: Option<PreviewDownscaleSettings>,
+    alpha_settings: Option<AlphaSettings>,
+    deartifact_strength: Option<f32>,
+    merge_settings: Option<MergeSettings>,
+    outline_settings: Option<OutlineSettings>,
+    outline_before_downscale: Option<bool>,
+    outline_before_alpha: Option<bool>,
+    trim_alpha_threshold: Option<u8>,
+    max_megapixels: Option<u32>,
+) -> Result<Vec<u8>> {
+    let input = PathBuf::from(input_path);
+
+    tokio::task::spawn_blocking(move || {
+        let config = PipelineConfig {
+            downscale: downscale_settings,
+            alpha: alpha_settings,
+            deartifact_strength,
+            merge: merge_settings,
+            outline: outline_settings,
+            outline_before_downscale: outline_before_downscale.unwrap_or(false),
+            outline_before_alpha: outline_before_alpha.unwrap_or(false),
+            max_megapixels: max_megapixels.unwrap_or(0),
+            bleed_iterations: None,
+            trim_alpha_threshold,
+        };
+        processor::preview_pipeline(&input, &config)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Run the pipeline over a hand-picked list of files, writing each into
+/// `output_dir` under its own base name. Returns one entry per input.
+/// With `resume`, files an interrupted run into the same folder finished are skipped.
+/// Inputs over `max_megapixels` fail (default: no limit).
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn process_files_command(
+    input_paths: Vec<String>,
+    output_dir: String,
+    downscale_settings: Option<PreviewDownscaleSettings>,
+    alpha_settings: Option<AlphaSettings>,
+    deartifact_strength: Option<f32>,
+    merge_settings: Option<MergeSettings>,
+    outline_settings: Option<OutlineSettings>,
+    outline_before_downscale: Option<bool>,
+    outline_before_alpha: Option<bool>,
+    save_options: Option<SaveOptions>,
+    resume: Option<bool>,
+    max_megapixels: Option<u32>,
+) -> Result<Vec<processor::ProcessFileEntry>> {
+    let inputs: Vec<PathBuf> = input_paths.iter().map(PathBuf::from).collect();
+    let output = PathBuf::from(output_dir);
+
+    tokio::task::spawn_blocking(move || {
+        let config = PipelineConfig {
+            downscale: downscale_settings,
+            alpha: alpha_settings,
+            deartifact_strength,
+            merge: merge_settings,
+            outline: outline_settings,
+            outline_before_downscale: outline_before_downscale.unwrap_or(false),
+            outline_before_alpha: outline_before_alpha.unwrap_or(false),
+            max_megapixels: max_megapixels.unwrap_or(0),
+            bleed_iterations: None,
+            trim_alpha_threshold: None,
+        };
+        processor::process_files(&inputs, &output, &config, &save_options.unwrap_or_default(), resume.unwrap_or(false))
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Run the pipeline over every image inside a ZIP archive and write the
+/// results to a new archive at the same internal paths. Returns one entry
+/// per image. With `resume`, images an interrupted run finished are reused.
+/// Images over `max_megapixels` fail (default: no limit).
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn process_zip_command(
+    zip_path: String,
+    output_zip_path: String,
+    downscale_settings: Option<PreviewDownscaleSettings>,
+    alpha_settings: Option<AlphaSettings>,
+    deartifact_strength: Option<f32>,
+    merge_settings: Option<MergeSettings>,
+    outline_settings: Option<OutlineSettings>,
+    outline_before_downscale: Option<bool>,
+    outline_before_alpha: Option<bool>,
+    save_options: Option<SaveOptions>,
+    resume: Option<bool>,
+    max_megapixels: Option<u32>,
+) -> Result<Vec<processor::ProcessFileEntry>> {
+    let input = PathBuf::from(zip_path);
+    let output = PathBuf::from(output_zip_path);
+
+    tokio::task::spawn_blocking(move || {
+        let config = PipelineConfig {
+            downscale: downscale_settings,
+            alpha: alpha_settings,
+            deartifact_strength,
+            merge: merge_settings,
+            outline: outline_settings,
+            outline_before_downscale: outline_before_downscale.unwrap_or(false),
+            outline_before_alpha: outline_before_alpha.unwrap_or(false),
+            max_megapixels: max_megapixels.unwrap_or(0),
+            bleed_iterations: None,
+            trim_alpha_threshold: None,
+        };
+        processor::process_zip(&input, &output, &config, &save_options.unwrap_or_default(), resume.unwrap_or(false))
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Save pipeline settings as a recipe file that can be replayed later
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn save_recipe_command(
+    recipe_path: String,
+    downscale_settings: Option<PreviewDownscaleSettings>,
+    alpha_settings: Option<AlphaSettings>,
+    deartifact_strength: Option<f32>,
+    merge_settings: Option<MergeSettings>,
+    outline_settings: Option<OutlineSettings>,
+    outline_before_downscale: Option<bool>,
+    outline_before_alpha: Option<bool>,
+    bleed_iterations: Option<u32>,
+    trim_alpha_threshold: Option<u8>,
+) -> Result<()> {
+    let path = PathBuf::from(recipe_path);
+
+    tokio::task::spawn_blocking(move || {
+        let config = PipelineConfig {
+            downscale: downscale_settings,
+            alpha: alpha_settings,
+            deartifact_strength,
+            merge: merge_settings,
+            outline: outline_settings,
+            outline_before_downscale: outline_before_downscale.unwrap_or(false),
+            outline_before_alpha: outline_before_alpha.unwrap_or(false),
+            max_megapixels: 0,
+            bleed_iterations,
+            trim_alpha_threshold,
+        };
+        processor::save_recipe(&path, &config)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Replay a saved recipe on one image
+/// Returns the size of the written image
+#[tauri::command]
+async fn apply_recipe_command(
+    recipe_path: String,
+    input_path: String,
+    output_path: String,
+    save_options: Option<SaveOptions>,
+) -> Result<(u32, u32)> {
+    let recipe = PathBuf::from(recipe_path);
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        processor::apply_recipe(&recipe, &input, &output, &save_options.unwrap_or_default())
+    })
+        .await
+        .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Process and save image to disk (same pipeline as preview but saves to file)
+/// Returns the path written (None if skipped by the overwrite policy) and,
+/// when `collect_timings` is set, per-stage durations in milliseconds.
+/// Writing over the input file requires `save_options.allow_in_place`;
+/// `auto_backup` then only works inside a workspace and fails otherwise.
+/// Inputs over `max_megapixels` are refused (default: no limit).
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn process_and_save_command(
+    input_path: String,
+    output_path: String,
+    downscale_settings: Option<PreviewDownscaleSettings>,
+    alpha_settings: Option<AlphaSettings>,
+    deartifact_strength: Option<f32>,
+    merge_settings: Option<MergeSettings>,
+    outline_settings: Option<OutlineSettings>,
+    save_options: Option<SaveOptions>,
+    outline_before_downscale: Option<bool>,
+    outline_before_alpha: Option<bool>,
+    collect_timings: Option<bool>,
+    auto_backup: Option<bool>,
+    dry_run: Option<bool>,
+    bleed_iterations: Option<u32>,
+    trim_alpha_threshold: Option<u8>,
+    max_megapixels: Option<u32>,
+) -> Result<SaveResult> {
+    let input = PathBuf::from(input_path);
+    let output = PathBuf::from(output_path);
+
+    tokio::task::spawn_blocking(move || {
+        let config = PipelineConfig {
+            downscale: downscale_settings,
+            alpha: alpha_settings,
+            deartifact_strength,
+            merge: merge_settings,
+            outline: outline_settings,
+            outline_before_downscale: outline_before_downscale.unwrap_or(false),
+            outline_before_alpha: outline_before_alpha.unwrap_or(false),
+            max_megapixels: max_megapixels.unwrap_or(0),
+            bleed_iterations,
+            trim_alpha_threshold,
+        };
+        processor::process_and_save(
+            &input,
+            &output,
+            &config,
+            &save_options.unwrap_or_default(),
+            auto_backup.unwrap_or(false),
+            dry_run.unwrap_or(false),
+            StageTimings::new(collect_timings.unwrap_or(false)),
+        )
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+// ============================================================================
+// WORKSPACE STATE COMMANDS
+// ============================================================================
+
+/// Initialize workspace state for a folder
+/// With `project_id`, new sources in the workspace are seeded with that
+/// project's processing defaults
+#[tauri::command]
+async fn init_workspace_command(
+    db: tauri::State<'_, Database>,
+    workspace_path: String,
+    project_id: Option<i64>,
+) -> Result<()> {
+    let path = PathBuf::from(workspace_path);
+    let defaults = project_id.map(|id| db.get_project_defaults(id)).transpose()?;
+
+    tokio::task::spawn_blocking(move || {
+        state::init_workspace(&path, defaults)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Load workspace state (read-only: never writes to disk)
+#[tauri::command]
+async fn load_workspace_command(workspace_path: String) -> Result<WorkspaceState> {
+    let path = PathBuf::from(workspace_path);
+
+    tokio::task::spawn_blocking(move || {
+        let manager = WorkspaceManager::open_readonly(&path)?;
+        Ok(manager.into_state())
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Save workspace state
+#[tauri::command]
+async fn save_workspace_command(
+    workspace_path: String,
+    state: WorkspaceState,
+) -> Result<()> {
+    let path = PathBuf::from(workspace_path);
+
+    tokio::task::spawn_blocking(move || {
+        let manager = WorkspaceManager::from_state(&path, state);
+        manager.save()
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Get source state for a specific image (or create if new)
+#[tauri::command]
+async fn get_source_state_command(
+    workspace_path: String,
+    relative_path: String,
+) -> Result<state::SourceState> {
+    let path = PathBuf::from(workspace_path);
+
+    tokio::task::spawn_blocking(move || {
+        let mut manager = WorkspaceManager::open(&path)?;
+        let source = manager.get_or_create_source(&relative_path)?;
+        Ok(source.clone())
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Add a new version to a source's lineage
+#[tauri::command]
+async fn add_version_command(
+    workspace_path: String,
+    relative_path: String,
+    version: state::ImageVersion,
+) -> Result<()> {
+    let path = PathBuf::from(workspace_path);
+
+    tokio::task::spawn_blocking(move || {
+        let mut manager = WorkspaceManager::open(&path)?;
+        manager.add_version(&relative_path, version)?;
+        manager.save()
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Get the processing history log for a source (empty if not yet tracked)
+#[tauri::command]
+async fn get_history_command(
+    workspace_path: String,
+    relative_path: String,
+) -> Result<Vec<state::HistoryEntry>> {
+    let path = PathBuf::from(workspace_path);
+
+    tokio::task::spawn_blocking(move || {
+        WorkspaceManager::open_readonly(&path)?.history(&relative_path)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Render every version of a source side by side, captioned with id and type
+/// Returns PNG bytes
+#[tauri::command]
+async fn version_contact_sheet_command(
+    workspace_path: String,
+    relative_path: String,
+) -> Result<Vec<u8>> {
+    let path = PathBuf::from(workspace_path);
+
+    tokio::task::spawn_blocking(move || {
+        let manager = WorkspaceManager::open_readonly(&path)?;
+        let sheet = manager.version_contact_sheet(&relative_path)?;
+        processor::encode_png(&sheet)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Find groups of images in a workspace that look the same
+/// `hamming_threshold` defaults to `state::DEFAULT_DUPLICATE_THRESHOLD`
+#[tauri::command]
+async fn find_duplicates_command(
+    workspace_path: String,
+    hamming_threshold: Option<u32>,
+) -> Result<Vec<Vec<String>>> {
+    let path = PathBuf::from(workspace_path);
+
+    tokio::task::spawn_blocking(move || {
+        let manager = WorkspaceManager::open_readonly(&path)?;
+        Ok(manager.find_duplicates(hamming_threshold.unwrap_or(state::DEFAULT_DUPLICATE_THRESHOLD)))
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// List images in a workspace folder using header-only reads (no pixel decode)
+#[tauri::command]
+async fn list_workspace_images_command(
+    workspace_path: String,
+) -> Result<Vec<state::WorkspaceImage>> {
+    let path = PathBuf::from(workspace_path);
+
+    tokio::task::spawn_blocking(move || {
+        state::list_workspace_images(&path)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Generate a thumbnail for a single image on demand
+/// Returns PNG bytes no larger than max_size on either side
+#[tauri::command]
+async fn get_thumbnail_command(input_path: String, max_size: u32) -> Result<Vec<u8>> {
+    let input = PathBuf::from(input_path);
+
+    tokio::task::spawn_blocking(move || {
+        let img = processor::load_image(&input)?;
+        processor::encode_png(&processor::make_thumbnail(&img, max_size))
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Report a source image's original color type and bit depth
+/// Includes warnings when loading discards precision (e.g. 16-bit sources)
+#[tauri::command]
+async fn inspect_image_command(input_path: String) -> Result<SourceInfo> {
+    let input = PathBuf::from(input_path);
+
+    tokio::task::spawn_blocking(move || processor::inspect_image(&input))
+        .await
+        .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+/// Backup original image to .pixels/cache before overwriting
+/// Returns the cache path where the backup was saved
+#[tauri::command]
+async fn backup_original_command(
+    workspace_path: String,
+    relative_path: String,
+) -> Result<String> {
+    let ws_path = PathBuf::from(&workspace_path);
+    let rel_path = relative_path.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let manager = WorkspaceManager::open(&ws_path)?;
+        manager.backup_original(&rel_path)
+    })
+    .await
+    .map_err(|e| error::PixelsError::Processing(format!("Task join error: {}", e)))?
+}
+
+// Default settings, so the frontend never hardcodes its own copies
+
+#[tauri::command]
+fn default_processor_settings() -> ProcessorSettings {
+    ProcessorSettings::default()
+}
+
+#[tauri::command]
+fn default_downscaler_settings() -> DownscalerSettings {
+    DownscalerSettings::default()
+}
+
+#[tauri::command]
+fn default_packer_settings() -> PackerSettings {
+    PackerSettings::default()
+}
+
+#[tauri::command]
+fn default_alpha_settings() -> AlphaSettings {
+    AlphaSettings::default()
+}
+
+#[tauri::command]
+fn default_merge_settings() -> MergeSettings {
+    MergeSettings::default()
+}
+
+#[tauri::command]
+fn default_outline_settings() -> OutlineSettings {
+    OutlineSettings::default()
+}
+
+// Database/Project commands
+
+#[tauri::command]
+fn get_projects(db: tauri::State<Database>) -> Result<Vec<Project>> {
+    db.get_projects()
+}
+
+#[tauri::command]
+fn add_project(db: tauri::State<Database>, name: String, path: String) -> Result<Project> {
+    db.add_project(name, path)
+}
+
+#[tauri::command]
+fn remove_project(db: tauri::State<Database>, id: i64) -> Result<()> {
+    db.remove_project(id)
+}
+
+#[tauri::command]
+fn get_current_project_id(db: tauri::State<Database>) -> Result<Option<i64>> {
+    db.get_current_project_id()
+}
+
+#[tauri::command]
+fn set_current_project_id(db: tauri::State<Database>, id: Option<i64>) -> Result<()> {
+    db.set_current_project_id(id)
+}
+
+#[tauri::command]
+fn get_project_settings(db: tauri::State<Database>, project_id: i64) -> Result<ProjectSettings> {
+    db.get_project_settings(project_id)
+}
+
+#[tauri::command]
+fn get_project_setting(
+    db: tauri::State<Database>,
+    project_id: i64,
+    key: String,
+) -> Result<Option<String>> {
+    db.get_project_setting(project_id, &key)
+}
+
+#[tauri::command]
+fn set_project_setting(
+    db: tauri::State<Database>,
+    project_id: i64,
+    key: String,
+    value: String,
+) -> Result<()> {
+    db.set_project_setting(project_id, &key, &value)
+}
+
+#[tauri::command]
+fn get_project_defaults(db: tauri::State<Database>, project_id: i64) -> Result<state::ProcessingDefaults> {
+    db.get_project_defaults(project_id)
+}
+
+#[tauri::command]
+fn set_project_defaults(
+    db: tauri::State<Database>,
+    project_id: i64,
+    defaults: state::ProcessingDefaults,
+) -> Result<()> {
+    db.set_project_defaults(project_id, &defaults)
+}
+
+#[tauri::command]
+fn get_app_setting(db: tauri::State<Database>, key: String) -> Result<Option<String>> {
+    db.get_app_setting(&key)
+}
+
+#[tauri::command]
+fn set_app_setting(db: tauri::State<Database>, key: String, value: String) -> Result<()> {
+    db.set_app_setting(&key, &value)
+}
+
+#[tauri::command]
+fn get_session(db: tauri::State<Database>) -> Result<SessionState> {
+    db.get_session()
+}
+
+#[tauri::command]
+fn set_session(db: tauri::State<Database>, session: SessionState) -> Result<()> {
+    db.set_session(&session)
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_fs::init())
+        .setup(|app| {
+            // Initialize database
+            let app_dir = app.path().app_data_dir()
+                .expect("Failed to get app data directory");
+
+            std::fs::create_dir_all(&app_dir).expect("Failed to create app directory");
+
+            let db_path = app_dir.join("pixels.db");
+            let database = Database::new(db_path).expect("Failed to initialize database");
+
+            app.manage(database);
+            app.manage(PaletteWatchers::default());
+
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            // Legacy v1 commands
+            pack_sprites_command,
+            pack_preview_command,
+            pack_workspace_command,
+            append_to_sheet_command,
+            validate_atlas_command,
+            process_image_command,
+            downscale_image_command,
+            downscale_to_dimensions_command,
+            predict_output_size_command,
+            downscale_folder_command,
+            suggest_canvas_multiple_command,
+            detect_scale_command,
+            content_bounds_command,
+            detect_ground_contact_command,
+            downscale_quality_command,
+            // V2 individual operations
+            normalize_alpha_command,
+            normalize_alpha_by_color_command,
+            merge_colors_command,
+            merge_to_reduction_command,
+            add_outline_command,
+            add_outline_from_mask_command,
+            detect_outline_command,
+            thin_outline_command,
+            remove_background_command,
+            find_phase_command,
+            downsample_with_fixed_scale_phase_command,
+            blur_command,
+            enforce_symmetry_command,
+            apply_color_mappings_command,
+            tint_command,
+            deartifact_command,
+            bleed_edges_command,
+            benchmark_command,
+            flatten_regions_command,
+            remap_to_reference_command,
+            load_aseprite_palette_command,
+            export_palette_command,
+            remap_to_palette_command,
+            quantize_to_size_command,
+            watch_palette_command,
+            stop_watch_command,
+            compare_folders_command,
+            check_tileable_command,
+            make_tileable_command,
+            downscale_preview_command,
+            generate_preview_command,
+            process_and_save_command,
+            process_files_command,
+            process_zip_command,
+            save_recipe_command,
+            apply_recipe_command,
+            // V2 workspace state
+            init_workspace_command,
+            load_workspace_command,
+            save_workspace_command,
+            get_source_state_command,
+            add_version_command,
+            get_history_command,
+            version_contact_sheet_command,
+            find_duplicates_command,
+            list_workspace_images_command,
+            get_thumbnail_command,
+            inspect_image_command,
+            backup_original_command,
+            // Default settings
+            default_processor_settings,
+            default_downscaler_settings,
+            default_packer_settings,
+            default_alpha_settings,
+            default_merge_settings,
+            default_outline_settings,
+            // Database/project commands
+            get_projects,
+            add_project,
+            remove_project,
+            get_current_project_id,
+            set_current_project_id,
+            get_project_settings,
+            get_project_setting,
+            set_project_setting,
+            get_project_defaults,
+            set_project_defaults,
+            get_app_setting,
+            set_app_setting,
+            get_session,
+            set_session,
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    fn json<T: Serialize>(value: T) -> serde_json::Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_default_settings_commands_match_default_impls() {
+        assert_eq!(json(default_processor_settings()), json(ProcessorSettings::default()));
+        assert_eq!(json(default_downscaler_settings()), json(DownscalerSettings::default()));
+        assert_eq!(json(default_packer_settings()), json(PackerSettings::default()));
+        assert_eq!(json(default_alpha_settings()), json(AlphaSettings::default()));
+        assert_eq!(json(default_merge_settings()), json(MergeSettings::default()));
+        assert_eq!(json(default_outline_settings()), json(OutlineSettings::default()));
+
+        // What the frontend receives must read back as the same defaults
+        let roundtrip: DownscalerSettings = serde_json::from_value(json(default_downscaler_settings())).unwrap();
+        assert_eq!(json(roundtrip), json(DownscalerSettings::default()));
+    }
+
+    #[tokio::test]
+    async fn test_pack_workspace_command_rejects_traversal() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("workspace");
+        std::fs::create_dir_all(&root).unwrap();
+        image::RgbaImage::from_pixel(4, 4, image::Rgba([1, 1, 1, 255])).save(root.join("a.png")).unwrap();
+        let mut manager = state::WorkspaceManager::open(&root).unwrap();
+        manager.init().unwrap();
+        manager.get_or_create_source("a.png").unwrap();
+        manager.save().unwrap();
+
+        let outside = tmp.path().join("escape.png");
+        for output in ["../escape.png".to_string(), outside.to_string_lossy().into_owned()] {
+            let err = pack_workspace_command(root.to_string_lossy().into_owned(), PackerSettings::default(), output)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, error::PixelsError::InvalidParameter(_)));
+        }
+        assert!(!outside.exists());
+    }
+}
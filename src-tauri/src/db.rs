@@ -1,8 +1,11 @@
 use rusqlite::{Connection, params};
 use serde::{Deserialize, Serialize};
+use std::ops::Deref;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::Mutex;
+use std::time::Duration;
 use crate::error::Result;
+use crate::state::ProcessingDefaults;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Project {
@@ -19,6 +22,9 @@ pub struct ProjectSettings {
     pub downscale_output_folder: String,
     pub process_output_folder: String,
     pub pack_output_filename: String,
+    /// Settings new sources in this project start from
+    #[serde(default)]
+    pub defaults: ProcessingDefaults,
 }
 
 impl Default for ProjectSettings {
@@ -30,17 +36,80 @@ impl Default for ProjectSettings {
             downscale_output_folder: "downscaled".to_string(),
             process_output_folder: "processed".to_string(),
             pack_output_filename: "spritesheet.png".to_string(),
+            defaults: ProcessingDefaults::default(),
         }
     }
 }
 
+/// Where the user left off, restored on the next launch
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionState {
+    /// Last opened workspace folder
+    pub workspace_path: Option<String>,
+    /// Source selected in that workspace (relative path)
+    pub selected_relative_path: Option<String>,
+    /// Active UI tab
+    pub active_tab: Option<String>,
+}
+
+/// Project setting key holding the serialized `ProcessingDefaults`
+const DEFAULTS_KEY: &str = "defaults";
+
+/// App setting key holding the serialized `SessionState`
+const SESSION_KEY: &str = "session";
+
+/// Idle connections kept around for reuse
+const MAX_IDLE_CONNECTIONS: usize = 4;
+
+/// How long a writer waits for another writer's lock before failing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// SQLite database with a small connection pool
+///
+/// Each call checks out its own connection, so callers on different threads
+/// don't queue behind one another. The database runs in WAL mode so reads
+/// proceed while a write is in progress. Safe to share without a `Mutex`.
 pub struct Database {
-    conn: Arc<Mutex<Connection>>,
+    db_path: PathBuf,
+    idle: Mutex<Vec<Connection>>,
+}
+
+/// A pooled connection, returned to the pool when dropped
+struct PooledConnection<'a> {
+    db: &'a Database,
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            let mut idle = self.db.idle.lock().unwrap();
+            if idle.len() < MAX_IDLE_CONNECTIONS {
+                idle.push(conn);
+            }
+        }
+    }
+}
+
+fn open_connection(db_path: &PathBuf) -> Result<Connection> {
+    let conn = Connection::open(db_path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    Ok(conn)
 }
 
 impl Database {
     pub fn new(db_path: PathBuf) -> Result<Self> {
-        let conn = Connection::open(db_path)?;
+        let conn = open_connection(&db_path)?;
 
         // Create tables
         conn.execute(
@@ -75,14 +144,25 @@ impl Database {
         )?;
 
         Ok(Self {
-            conn: Arc::new(Mutex::new(conn)),
+            db_path,
+            idle: Mutex::new(vec![conn]),
         })
     }
 
+    /// Check out a connection, opening a new one if none are idle
+    fn conn(&self) -> Result<PooledConnection<'_>> {
+        let idle = self.idle.lock().unwrap().pop();
+        let conn = match idle {
+            Some(conn) => conn,
+            None => open_connection(&self.db_path)?,
+        };
+        Ok(PooledConnection { db: self, conn: Some(conn) })
+    }
+
     // Project operations
 
     pub fn add_project(&self, name: String, path: String) -> Result<Project> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.conn()?;
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
@@ -103,7 +183,7 @@ impl Database {
     }
 
     pub fn get_projects(&self) -> Result<Vec<Project>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.conn()?;
         let mut stmt = conn.prepare("SELECT id, name, path FROM projects ORDER BY updated_at DESC")?;
 
         let projects = stmt
@@ -120,7 +200,7 @@ impl Database {
     }
 
     pub fn get_project_by_id(&self, id: i64) -> Result<Option<Project>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.conn()?;
         let mut stmt = conn.prepare("SELECT id, name, path FROM projects WHERE id = ?1")?;
 
         let mut rows = stmt.query(params![id])?;
@@ -137,13 +217,13 @@ impl Database {
     }
 
     pub fn remove_project(&self, id: i64) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.conn()?;
         conn.execute("DELETE FROM projects WHERE id = ?1", params![id])?;
         Ok(())
     }
 
     pub fn update_project_timestamp(&self, id: i64) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.conn()?;
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
@@ -160,7 +240,7 @@ impl Database {
     // Project settings operations
 
     pub fn set_project_setting(&self, project_id: i64, key: &str, value: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.conn()?;
         conn.execute(
             "INSERT OR REPLACE INTO project_settings (project_id, key, value) VALUES (?1, ?2, ?3)",
             params![project_id, key, value],
@@ -169,7 +249,7 @@ impl Database {
     }
 
     pub fn get_project_setting(&self, project_id: i64, key: &str) -> Result<Option<String>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.conn()?;
         let mut stmt = conn.prepare("SELECT value FROM project_settings WHERE project_id = ?1 AND key = ?2")?;
 
         let mut rows = stmt.query(params![project_id, key])?;
@@ -202,14 +282,28 @@ impl Database {
         if let Some(val) = self.get_project_setting(project_id, "pack_output_filename")? {
             settings.pack_output_filename = val;
         }
+        settings.defaults = self.get_project_defaults(project_id)?;
 
         Ok(settings)
     }
 
+    /// Load a project's processing defaults (built-in defaults if none were
+    /// saved or they can't be parsed)
+    pub fn get_project_defaults(&self, project_id: i64) -> Result<ProcessingDefaults> {
+        Ok(self
+            .get_project_setting(project_id, DEFAULTS_KEY)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn set_project_defaults(&self, project_id: i64, defaults: &ProcessingDefaults) -> Result<()> {
+        self.set_project_setting(project_id, DEFAULTS_KEY, &serde_json::to_string(defaults)?)
+    }
+
     // App settings operations
 
     pub fn set_app_setting(&self, key: &str, value: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.conn()?;
         conn.execute(
             "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
             params![key, value],
@@ -218,7 +312,7 @@ impl Database {
     }
 
     pub fn get_app_setting(&self, key: &str) -> Result<Option<String>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.conn()?;
         let mut stmt = conn.prepare("SELECT value FROM app_settings WHERE key = ?1")?;
 
         let mut rows = stmt.query(params![key])?;
@@ -243,11 +337,23 @@ impl Database {
             self.set_app_setting("current_project_id", &id.to_string())?;
             self.update_project_timestamp(id)?;
         } else {
-            let conn = self.conn.lock().unwrap();
+            let conn = self.conn()?;
             conn.execute("DELETE FROM app_settings WHERE key = 'current_project_id'", [])?;
         }
         Ok(())
     }
+
+    /// Load the saved session (default if none was saved or it can't be parsed)
+    pub fn get_session(&self) -> Result<SessionState> {
+        Ok(self
+            .get_app_setting(SESSION_KEY)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn set_session(&self, session: &SessionState) -> Result<()> {
+        self.set_app_setting(SESSION_KEY, &serde_json::to_string(session)?)
+    }
 }
 
 #[cfg(test)]
@@ -278,4 +384,78 @@ mod tests {
         // Clean up
         let _ = fs::remove_file(&temp_db);
     }
+
+    #[test]
+    fn test_session_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let temp_db = tmp.path().join("pixels.db");
+
+        let db = Database::new(temp_db.clone()).unwrap();
+        assert_eq!(db.get_session().unwrap(), SessionState::default());
+
+        let session = SessionState {
+            workspace_path: Some("/art/sprites".to_string()),
+            selected_relative_path: Some("heroes/knight.png".to_string()),
+            active_tab: Some("process".to_string()),
+        };
+        db.set_session(&session).unwrap();
+        drop(db);
+
+        let reopened = Database::new(temp_db.clone()).unwrap();
+        assert_eq!(reopened.get_session().unwrap(), session);
+    }
+
+    #[test]
+    fn test_concurrent_access() {
+        let tmp = tempfile::tempdir().unwrap();
+        let temp_db = tmp.path().join("pixels.db");
+
+        let db = std::sync::Arc::new(Database::new(temp_db.clone()).unwrap());
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let db = db.clone();
+                std::thread::spawn(move || {
+                    for i in 0..20 {
+                        let key = format!("thread{}_{}", t, i);
+                        db.set_app_setting(&key, &i.to_string()).unwrap();
+                        assert_eq!(db.get_app_setting(&key).unwrap(), Some(i.to_string()));
+                        db.get_projects().unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(db.get_app_setting("thread7_19").unwrap(), Some("19".to_string()));
+    }
+
+    #[test]
+    fn test_new_source_inherits_project_defaults() {
+        use crate::state::WorkspaceManager;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let temp_db = tmp.path().join("pixels.db");
+        let workspace = tmp.path().join("workspace");
+        fs::create_dir_all(&workspace).unwrap();
+        image::RgbaImage::new(4, 4).save(workspace.join("hero.png")).unwrap();
+
+        let db = Database::new(temp_db.clone()).unwrap();
+        let project = db.add_project("Defaults".to_string(), workspace.to_string_lossy().to_string()).unwrap();
+        let mut defaults = db.get_project_defaults(project.id).unwrap();
+        assert_eq!(defaults.processor.lab_merge_threshold, 3.0);
+        defaults.processor.lab_merge_threshold = 2.0;
+        db.set_project_defaults(project.id, &defaults).unwrap();
+        assert_eq!(db.get_project_settings(project.id).unwrap().defaults.processor.lab_merge_threshold, 2.0);
+
+        let mut manager = WorkspaceManager::open(&workspace).unwrap();
+        manager.set_source_defaults(Some(db.get_project_defaults(project.id).unwrap()));
+        manager.init().unwrap();
+
+        // The defaults are saved with the workspace, so they survive a reload
+        let mut manager = WorkspaceManager::open(&workspace).unwrap();
+        let source = manager.get_or_create_source("hero.png").unwrap();
+        assert_eq!(source.settings.as_ref().unwrap().processor.lab_merge_threshold, 2.0);
+    }
 }
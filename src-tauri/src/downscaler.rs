@@ -4,11 +4,15 @@
 //! to the actual resolution. Uses FFT for grid detection and block variance
 //! with phase search for optimal alignment.
 
-use image::{RgbaImage, Rgba, ImageBuffer};
+use image::{GrayImage, RgbaImage, Rgba, ImageBuffer};
 use rustfft::{FftPlanner, num_complex::Complex};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use rayon::prelude::*;
+use crate::color::luma;
 use crate::error::{Result, PixelsError};
+use crate::processor::{resolve_output_path, Connectivity, SaveOptions};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownscalerSettings {
@@ -17,24 +21,450 @@ pub struct DownscalerSettings {
     /// Pad output canvas to a multiple of this value (0 = disabled)
     pub pad_canvas: bool,
     pub canvas_multiple: u32,
+    /// Background removal applied before trimming (default: none)
+    #[serde(default)]
+    pub bg_removal_mode: BgRemovalMode,
+    /// Max summed RGB difference from the seed color for a pixel to count as background (default: 15)
+    #[serde(default = "default_bg_tolerance")]
+    pub bg_tolerance: u32,
+    /// Where the image sits inside the padded canvas (default: center)
+    #[serde(default)]
+    pub pad_align: Align,
+    /// If the trimmed content isn't a whole number of detected-scale blocks,
+    /// switch to the nearest scale that is (default: false - warn only)
+    #[serde(default)]
+    pub snap_to_divisible_scale: bool,
+    /// Step through the source by the FFT-detected (fractional) grid size
+    /// instead of the integer scale, so a slightly non-integer upscale
+    /// (e.g. 10.3x) doesn't drift out of alignment (default: false)
+    #[serde(default)]
+    pub fractional_stride: bool,
+    /// Neighborhood the background flood spreads through; with eight,
+    /// background reaches through diagonal gaps (default: four)
+    #[serde(default)]
+    pub bg_connectivity: Connectivity,
+    /// Extra passes growing the removed background into neighboring visible
+    /// pixels, to eat anti-aliased fringes (default: 0)
+    #[serde(default)]
+    pub bg_dilation_iterations: u32,
+    /// Neighborhood used by each dilation pass (default: four)
+    #[serde(default)]
+    pub bg_dilation_connectivity: Connectivity,
+    /// After detecting the scale, widen the auto-trim bounds outward to the
+    /// nearest grid lines so the trimmed size is a multiple of the scale
+    /// (default: false)
+    #[serde(default)]
+    pub grid_aligned_trim: bool,
+    /// Re-run the phase search from all four quadrant offsets and keep the
+    /// global best, for art where the coarse search lands in a local minimum
+    /// (default: false)
+    #[serde(default)]
+    pub multi_seed_phase: bool,
+    /// Swap in a block's brightest pixel when the center sample is much
+    /// darker, so single-pixel highlights survive the downscale (default: false)
+    #[serde(default)]
+    pub restore_highlights: bool,
+    /// Which scale wins when several score within 2x of the lowest block
+    /// variance (default: closest to the FFT hint)
+    #[serde(default)]
+    pub scale_tiebreak: ScaleTiebreak,
+    /// How each output pixel's alpha is chosen from its source block
+    /// (default: the center pixel's alpha, as sampled)
+    #[serde(default)]
+    pub alpha_resolution: AlphaResolution,
+    /// How a partial block at the right/bottom edge is counted when sizing
+    /// the output (default: floor, dropping it)
+    #[serde(default)]
+    pub dimension_rounding: DimensionRounding,
+    /// Smallest width or height the downscaled content may have; a detected
+    /// scale that would go below it is lowered to the largest scale that
+    /// doesn't (default: None - no floor)
+    #[serde(default)]
+    pub min_output_dimension: Option<u32>,
+    /// PNG text keyword holding the upscale factor (conventionally
+    /// `DEFAULT_SCALE_HINT_KEY`); a valid value in the input overrides scale
+    /// detection (default: None - always detect)
+    #[serde(default)]
+    pub scale_hint_key: Option<String>,
+    /// What to do when background removal is off and the input has no
+    /// transparent pixels at all (default: warn)
+    #[serde(default)]
+    pub on_opaque_input: OpaqueInput,
+    /// Window applied to the edge profiles before the FFT grid detection,
+    /// to cut spectral leakage from trends and the profile ends (default: none)
+    #[serde(default)]
+    pub fft_window: FftWindow,
 }
 
+fn default_bg_tolerance() -> u32 {
+    15
+}
+
+/// Conventional PNG text keyword for an upscale factor hint
+pub const DEFAULT_SCALE_HINT_KEY: &str = "upscale_factor";
+
+/// PNG text keyword `downscale_image` records the removed scale under.
+/// Provenance only: it is never read back as a hint, since the output is no
+/// longer upscaled.
+pub const DOWNSCALED_BY_KEY: &str = "downscaled_by";
+
 impl Default for DownscalerSettings {
     fn default() -> Self {
         Self {
             auto_trim: true,
             pad_canvas: false,
             canvas_multiple: 16,
+            bg_removal_mode: BgRemovalMode::None,
+            bg_tolerance: default_bg_tolerance(),
+            pad_align: Align::Center,
+            snap_to_divisible_scale: false,
+            fractional_stride: false,
+            bg_connectivity: Connectivity::Four,
+            bg_dilation_iterations: 0,
+            bg_dilation_connectivity: Connectivity::Four,
+            grid_aligned_trim: false,
+            multi_seed_phase: false,
+            restore_highlights: false,
+            scale_tiebreak: ScaleTiebreak::PreferHint,
+            alpha_resolution: AlphaResolution::CenterAlpha,
+            dimension_rounding: DimensionRounding::Floor,
+            min_output_dimension: None,
+            scale_hint_key: None,
+            on_opaque_input: OpaqueInput::Warn,
+            fft_window: FftWindow::None,
+        }
+    }
+}
+
+/// How the background region is located before it is cleared
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BgRemovalMode {
+    /// Leave the image untouched
+    #[default]
+    None,
+    /// Flood fill from every border pixel matching the dominant edge color
+    EdgeFlood,
+    /// Flood fill from a single user-picked point ("magic wand")
+    FloodFromPoint { x: u32, y: u32 },
+}
+
+/// Handling of a fully opaque input when `bg_removal_mode` is `None`, where
+/// trim and outline would otherwise treat the whole canvas as content
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpaqueInput {
+    /// Continue unchanged, with a warning suggesting background removal
+    #[default]
+    Warn,
+    /// Clear the background with `EdgeFlood` at `bg_tolerance`, without dilation
+    AutoRemove,
+    /// Continue unchanged, silently
+    Proceed,
+}
+
+/// Window applied to an edge profile before its FFT
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FftWindow {
+    /// Raw mean-subtracted profile, as before windowing was configurable
+    #[default]
+    None,
+    /// Tapers to zero at both ends; lowest leakage far from the peak
+    Hann,
+    /// Tapers to 0.08 at both ends; narrower peak than Hann
+    Hamming,
+}
+
+impl FftWindow {
+    /// Weight of sample `i` out of `n`
+    fn weight(self, i: usize, n: usize) -> f32 {
+        if n < 2 {
+            return 1.0;
+        }
+        let cos = (2.0 * std::f32::consts::PI * i as f32 / (n - 1) as f32).cos();
+        match self {
+            FftWindow::None => 1.0,
+            FftWindow::Hann => 0.5 - 0.5 * cos,
+            FftWindow::Hamming => 0.54 - 0.46 * cos,
+        }
+    }
+}
+
+/// Choice among scales whose block variance is within 2x of the minimum
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleTiebreak {
+    /// Closest to the FFT grid hint, or the largest if there is no hint
+    #[default]
+    PreferHint,
+    /// Largest scale (smallest output)
+    PreferLargest,
+    /// Smallest scale (largest output, least aggressive downscale)
+    PreferSmallest,
+}
+
+/// How a downsampled pixel's alpha is decided
+///
+/// Center sampling can land on a half-transparent anti-aliased edge pixel;
+/// the block-based policies look at the whole block instead and produce
+/// either fully opaque or fully transparent output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlphaResolution {
+    /// Keep the center pixel's alpha as-is
+    #[default]
+    CenterAlpha,
+    /// Opaque if more than half the block has alpha of at least 128
+    MajorityOpaque,
+    /// Opaque if the block's mean alpha is at least this value
+    ThresholdAt(u8),
+}
+
+/// Rounding of `(size - phase) / scale` to an output dimension
+///
+/// The v4 integer path has always floored (a trailing partial block is
+/// dropped); older tools rounded. Only the integer path uses this: the
+/// fractional stride always rounds, since its scale is itself an estimate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DimensionRounding {
+    /// Drop a trailing partial block
+    #[default]
+    Floor,
+    /// Keep a trailing partial block if it's at least half a block
+    Round,
+    /// Keep any trailing partial block
+    Ceil,
+}
+
+impl DimensionRounding {
+    /// Number of output pixels covering `span` source pixels at `scale`
+    pub fn output_len(self, span: u32, scale: u32) -> u32 {
+        match self {
+            DimensionRounding::Floor => span / scale,
+            DimensionRounding::Round => (span + scale / 2) / scale,
+            DimensionRounding::Ceil => span.div_ceil(scale),
         }
     }
 }
 
+/// Anchor position of content within a padded canvas
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Align {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    #[default]
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+impl Align {
+    /// Offset of content of size `inner` placed inside `outer` along each axis
+    fn offset(self, outer: (u32, u32), inner: (u32, u32)) -> (u32, u32) {
+        let free_x = outer.0.saturating_sub(inner.0);
+        let free_y = outer.1.saturating_sub(inner.1);
+        let x = match self {
+            Align::TopLeft | Align::CenterLeft | Align::BottomLeft => 0,
+            Align::TopCenter | Align::Center | Align::BottomCenter => free_x / 2,
+            Align::TopRight | Align::CenterRight | Align::BottomRight => free_x,
+        };
+        let y = match self {
+            Align::TopLeft | Align::TopCenter | Align::TopRight => 0,
+            Align::CenterLeft | Align::Center | Align::CenterRight => free_y / 2,
+            Align::BottomLeft | Align::BottomCenter | Align::BottomRight => free_y,
+        };
+        (x, y)
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct DownscaleResult {
     pub original_size: (u32, u32),
     pub final_size: (u32, u32),
     pub scale_factor: f32,
     pub grid_detected: bool,
+    /// Problems noticed along the way (e.g. content not divisible by the scale)
+    pub warnings: Vec<String>,
+}
+
+/// Per-file outcome of a folder downscale
+#[derive(Debug, Clone, Serialize)]
+pub struct FolderDownscaleEntry {
+    /// File name relative to the input folder
+    pub file: String,
+    /// Where the output is (or, in a dry run, would be) written
+    pub output_path: String,
+    /// Downscale result (None if this file failed)
+    pub result: Option<DownscaleResult>,
+    /// Error message if this file failed
+    pub error: Option<String>,
+    /// Already written by an earlier, interrupted run and not redone
+    /// (see `BatchOptions::resume`)
+    pub skipped: bool,
+}
+
+/// Options for a folder batch run
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct BatchOptions {
+    /// Also write a `BatchManifest` to `MANIFEST_FILE` in the output folder
+    #[serde(default)]
+    pub manifest: bool,
+    /// Compute every result but write nothing (no outputs, folder, manifest
+    /// or checkpoint)
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Skip files recorded as done in the checkpoint left by an interrupted
+    /// run into the same output folder
+    #[serde(default)]
+    pub resume: bool,
+}
+
+/// Progress file kept in a batch's output folder while it runs
+pub const CHECKPOINT_FILE: &str = "batch-checkpoint.json";
+
+/// Files completed between checkpoint writes
+const CHECKPOINT_INTERVAL: usize = 8;
+
+/// Files finished so far by a batch run, so a crashed run can be resumed
+///
+/// Written every `CHECKPOINT_INTERVAL` files and when the batch ends with
+/// failures; deleted once every file has succeeded.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatchCheckpoint {
+    /// Batch operation being checkpointed (e.g. "downscale")
+    pub operation: String,
+    /// Output folder the completed files were written to
+    pub output_dir: String,
+    /// File names (relative to the input folder) written successfully
+    pub completed: Vec<String>,
+}
+
+/// Where a batch writing into `output_dir` keeps its checkpoint
+///
+/// Kept beside the outputs rather than the inputs, so a batch never leaves
+/// anything behind in a source folder.
+pub fn checkpoint_path(output_dir: &Path) -> PathBuf {
+    output_dir.join(CHECKPOINT_FILE)
+}
+
+/// Write a checkpoint via a temporary file, so a crash mid-write can't
+/// leave a truncated one behind
+fn write_checkpoint(path: &Path, checkpoint: &BatchCheckpoint) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, serde_json::to_string_pretty(checkpoint)?)?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Files already completed into `output_dir` by an interrupted run (empty if
+/// there's no usable checkpoint)
+fn read_checkpoint(path: &Path, operation: &str, output_dir: &str) -> Vec<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str::<BatchCheckpoint>(&json).ok())
+        .filter(|c| c.operation == operation && c.output_dir == output_dir)
+        .map(|c| c.completed)
+        .unwrap_or_default()
+}
+
+/// Checkpoint bookkeeping shared by the batch operations
+///
+/// Completed files are recorded as they finish and written out every
+/// `CHECKPOINT_INTERVAL` files. `finish` deletes the checkpoint once every
+/// file has succeeded and otherwise leaves it for a resumed run.
+pub(crate) struct CheckpointTracker {
+    path: PathBuf,
+    done_before: std::collections::HashSet<String>,
+    checkpoint: std::sync::Mutex<BatchCheckpoint>,
+}
+
+impl CheckpointTracker {
+    /// Start tracking into `path`, picking up the completed files of an
+    /// earlier run of `operation` into the same `output` when `resume` is set
+    pub(crate) fn start(path: PathBuf, operation: &str, output: &Path, resume: bool) -> Self {
+        let output_key = output.to_string_lossy().to_string();
+        let done_before: Vec<String> = if resume {
+            read_checkpoint(&path, operation, &output_key)
+        } else {
+            Vec::new()
+        };
+        let checkpoint = BatchCheckpoint {
+            operation: operation.to_string(),
+            output_dir: output_key,
+            completed: done_before.clone(),
+        };
+        CheckpointTracker {
+            path,
+            done_before: done_before.into_iter().collect(),
+            checkpoint: std::sync::Mutex::new(checkpoint),
+        }
+    }
+
+    /// Whether an earlier run already completed `file`
+    pub(crate) fn was_done(&self, file: &str) -> bool {
+        self.done_before.contains(file)
+    }
+
+    /// Record `file` as completed
+    pub(crate) fn complete(&self, file: String) {
+        let mut checkpoint = self.checkpoint.lock().unwrap();
+        checkpoint.completed.push(file);
+        if checkpoint.completed.len().is_multiple_of(CHECKPOINT_INTERVAL) {
+            // Best effort: a missed checkpoint only means redoing some files
+            let _ = write_checkpoint(&self.path, &checkpoint);
+        }
+    }
+
+    /// Delete the checkpoint if every file succeeded, otherwise write it out
+    pub(crate) fn finish(&self, all_succeeded: bool) -> Result<()> {
+        if all_succeeded {
+            match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        } else {
+            write_checkpoint(&self.path, &self.checkpoint.lock().unwrap())
+        }
+    }
+}
+
+/// File written into the output folder by `downscale_folder` when requested
+pub const MANIFEST_FILE: &str = "pixels-manifest.json";
+
+/// Bumped whenever `BatchManifest`'s layout changes incompatibly
+pub const MANIFEST_SCHEMA_VERSION: u32 = 1;
+
+/// Machine-readable record of a batch run, for downstream build steps
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchManifest {
+    pub schema_version: u32,
+    /// Batch operation that produced the outputs (e.g. "downscale")
+    pub operation: String,
+    /// When the manifest was written (RFC 3339)
+    pub created: String,
+    /// One entry per input, in file-name order
+    pub entries: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    pub input: String,
+    /// Output path (None if this file failed)
+    pub output: Option<String>,
+    pub result: Option<DownscaleResult>,
+    pub error: Option<String>,
+    pub started: String,
+    pub finished: String,
 }
 
 /// Result of scale detection analysis
@@ -52,6 +482,52 @@ pub struct ScaleDetectionResult {
     pub dimensions: (u32, u32),
     /// Estimated native dimensions after downscaling
     pub estimated_native_size: (u32, u32),
+    /// Downscaler settings tuned to this image, for pre-filling the UI
+    pub suggested_settings: DownscalerSettings,
+}
+
+/// Bounding box and centroid of non-transparent content
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BoundsInfo {
+    /// Leftmost content column (inclusive)
+    pub min_x: u32,
+    /// Topmost content row (inclusive)
+    pub min_y: u32,
+    /// Rightmost content column (inclusive)
+    pub max_x: u32,
+    /// Bottom content row (inclusive)
+    pub max_y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Alpha-weighted centroid in pixel coordinates
+    pub centroid_x: f32,
+    pub centroid_y: f32,
+}
+
+/// How well a downscaled image reproduces its source
+#[derive(Debug, Clone, Serialize)]
+pub struct QualityReport {
+    /// Mean absolute per-channel difference (0-255) between the source and
+    /// the downscaled image upscaled back by `scale`, within content bounds
+    pub mean_error: f32,
+    /// Fraction of `scale`x`scale` source blocks that were not a single color
+    /// (detail that nearest-neighbor downscaling had to drop)
+    pub nonuniform_block_fraction: f32,
+    /// Number of source pixels compared
+    pub compared_pixels: u64,
+}
+
+/// Settings for inline downscale during preview
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewDownscaleSettings {
+    /// Enable downscaling
+    pub enabled: bool,
+    /// Auto-trim transparent borders
+    pub auto_trim: bool,
+    /// Manual target width (if set, uses manual dimensions instead of auto-detect)
+    pub target_width: Option<u32>,
+    /// Manual target height (if set, uses manual dimensions instead of auto-detect)
+    pub target_height: Option<u32>,
 }
 
 /// Settings for manual downscale with user-specified dimensions
@@ -63,6 +539,10 @@ pub struct ManualDownscaleSettings {
     pub target_height: u32,
     /// Auto-trim transparent borders before downscaling
     pub auto_trim: bool,
+    /// Fit inside the target box keeping the source aspect ratio, leaving
+    /// the rest transparent, instead of stretching to fill it (default: false)
+    #[serde(default)]
+    pub preserve_aspect: bool,
 }
 
 // ============================================================================
@@ -70,7 +550,7 @@ pub struct ManualDownscaleSettings {
 // ============================================================================
 
 /// Detect grid size using FFT on edge profiles
-fn detect_grid_size(img: &RgbaImage) -> Option<f32> {
+fn detect_grid_size(img: &RgbaImage, window: FftWindow) -> Option<f32> {
     let (width, height) = img.dimensions();
 
     // Convert to grayscale, masking transparent pixels
@@ -107,8 +587,8 @@ fn detect_grid_size(img: &RgbaImage) -> Option<f32> {
         }
     }
 
-    let h_period = fft_detect_period(&h_profile, 6.0, 20.0);
-    let v_period = fft_detect_period(&v_profile, 6.0, 20.0);
+    let h_period = fft_detect_period(&h_profile, 6.0, 20.0, window);
+    let v_period = fft_detect_period(&v_profile, 6.0, 20.0, window);
 
     match (h_period, v_period) {
         (Some(h), Some(v)) => Some((h + v) / 2.0),
@@ -119,7 +599,7 @@ fn detect_grid_size(img: &RgbaImage) -> Option<f32> {
 }
 
 /// Detect period using FFT
-fn fft_detect_period(signal: &[f32], min_period: f32, max_period: f32) -> Option<f32> {
+fn fft_detect_period(signal: &[f32], min_period: f32, max_period: f32, window: FftWindow) -> Option<f32> {
     let n = signal.len();
     if n < 20 {
         return None;
@@ -131,7 +611,8 @@ fn fft_detect_period(signal: &[f32], min_period: f32, max_period: f32) -> Option
     let mean: f32 = signal.iter().sum::<f32>() / n as f32;
     let mut buffer: Vec<Complex<f32>> = signal
         .iter()
-        .map(|&x| Complex::new(x - mean, 0.0))
+        .enumerate()
+        .map(|(i, &x)| Complex::new((x - mean) * window.weight(i, n), 0.0))
         .collect();
 
     fft.process(&mut buffer);
@@ -332,9 +813,60 @@ fn find_best_phase_for_scale(img: &RgbaImage, scale: u32) -> (u32, u32, f32) {
     (best_px, best_py, best_var)
 }
 
+/// Phase search that also hill-climbs from each quadrant offset
+///
+/// Starts local refinement from the single-search result and from (0, 0),
+/// (s/2, 0), (0, s/2) and (s/2, s/2), stepping to any lower-variance
+/// neighboring phase (wrapping mod `scale`) until none improves, and keeps
+/// the best. Never worse than `find_best_phase_for_scale`, which can settle
+/// in a local minimum on textured art.
+fn find_best_phase_multi_seed(img: &RgbaImage, scale: u32) -> (u32, u32, f32) {
+    let single = find_best_phase_for_scale(img, scale);
+    if scale < 2 {
+        return single;
+    }
+
+    let half = scale / 2;
+    let seeds = [(single.0, single.1), (0, 0), (half, 0), (0, half), (half, half)];
+    let mut best = single;
+
+    for (sx, sy) in seeds {
+        let (mut px, mut py) = (sx, sy);
+        let mut var = calculate_block_variance(img, scale, px, py);
+        loop {
+            let mut improved = false;
+            for (dx, dy) in [(1, 0), (scale - 1, 0), (0, 1), (0, scale - 1), (1, 1), (scale - 1, scale - 1), (1, scale - 1), (scale - 1, 1)] {
+                let (nx, ny) = ((px + dx) % scale, (py + dy) % scale);
+                let candidate = calculate_block_variance(img, scale, nx, ny);
+                if candidate < var {
+                    (px, py, var) = (nx, ny, candidate);
+                    improved = true;
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+        if var < best.2 {
+            best = (px, py, var);
+        }
+    }
+
+    best
+}
+
 /// Find optimal scale using block variance + phase search
 /// Returns (scale, phase_x, phase_y, all_results) - includes results for confidence calculation
-fn find_optimal_scale_v4_with_results(img: &RgbaImage, grid_hint: Option<f32>) -> (u32, u32, u32, Vec<ScaleResult>) {
+fn find_optimal_scale_v4_with_results(
+    img: &RgbaImage,
+    grid_hint: Option<f32>,
+    tiebreak: ScaleTiebreak,
+) -> (u32, u32, u32, Vec<ScaleResult>) {
+    // No blocks to measure: treat as native resolution
+    if crate::processor::is_empty_or_tiny(img) {
+        return (1, 0, 0, Vec::new());
+    }
+
     let min_scale = 6u32;
     let max_scale = 20u32;
 
@@ -384,9 +916,10 @@ fn find_optimal_scale_v4_with_results(img: &RgbaImage, grid_hint: Option<f32>) -
         .fold(f32::MAX, f32::min);
 
     if min_var == f32::MAX {
-        // Fallback to grid hint or default
-        let scale = grid_hint.map(|g| g.round() as u32).unwrap_or(10);
-        return (scale.clamp(min_scale, max_scale), 0, 0, all_results);
+        // Too small to measure any scale: fall back to the grid hint, or
+        // treat as native resolution rather than guessing
+        let scale = grid_hint.map(|g| (g.round() as u32).clamp(min_scale, max_scale)).unwrap_or(1);
+        return (scale, 0, 0, all_results);
     }
 
     // Find all "valid" scales (variance within 2x of minimum)
@@ -402,22 +935,23 @@ fn find_optimal_scale_v4_with_results(img: &RgbaImage, grid_hint: Option<f32>) -
             .iter()
             .min_by(|a, b| a.variance.partial_cmp(&b.variance).unwrap())
             .unwrap()
-    } else if let Some(hint) = grid_hint {
-        // Prefer scale closest to FFT hint among valid scales
-        valid_scales
-            .iter()
-            .min_by(|a, b| {
-                let dist_a = (a.scale as f32 - hint).abs();
-                let dist_b = (b.scale as f32 - hint).abs();
-                dist_a.partial_cmp(&dist_b).unwrap()
-            })
-            .unwrap()
     } else {
-        // Take largest valid scale
-        valid_scales
-            .iter()
-            .max_by_key(|r| r.scale)
-            .unwrap()
+        match (tiebreak, grid_hint) {
+            (ScaleTiebreak::PreferHint, Some(hint)) => {
+                // Prefer scale closest to FFT hint among valid scales
+                valid_scales
+                    .iter()
+                    .min_by(|a, b| {
+                        let dist_a = (a.scale as f32 - hint).abs();
+                        let dist_b = (b.scale as f32 - hint).abs();
+                        dist_a.partial_cmp(&dist_b).unwrap()
+                    })
+                    .unwrap()
+            }
+            (ScaleTiebreak::PreferSmallest, _) => valid_scales.iter().min_by_key(|r| r.scale).unwrap(),
+            // Take largest valid scale
+            _ => valid_scales.iter().max_by_key(|r| r.scale).unwrap(),
+        }
     };
 
     (best.scale, best.phase_x, best.phase_y, all_results)
@@ -425,79 +959,450 @@ fn find_optimal_scale_v4_with_results(img: &RgbaImage, grid_hint: Option<f32>) -
 
 /// Find optimal scale using block variance + phase search
 /// Returns (scale, phase_x, phase_y)
-fn find_optimal_scale_v4(img: &RgbaImage, grid_hint: Option<f32>) -> (u32, u32, u32) {
-    let (scale, px, py, _) = find_optimal_scale_v4_with_results(img, grid_hint);
+fn find_optimal_scale_v4(img: &RgbaImage, grid_hint: Option<f32>, tiebreak: ScaleTiebreak) -> (u32, u32, u32) {
+    let (scale, px, py, _) = find_optimal_scale_v4_with_results(img, grid_hint, tiebreak);
     (scale, px, py)
 }
 
 /// Downsample image using phase-aware sampling
-fn downsample_with_phase(img: &RgbaImage, scale: u32, phase_x: u32, phase_y: u32) -> RgbaImage {
+fn downsample_with_phase(
+    img: &RgbaImage,
+    scale: u32,
+    phase_x: u32,
+    phase_y: u32,
+    alpha: AlphaResolution,
+    rounding: DimensionRounding,
+) -> RgbaImage {
     let (width, height) = img.dimensions();
-
-    let out_width = (width.saturating_sub(phase_x)) / scale;
-    let out_height = (height.saturating_sub(phase_y)) / scale;
-
-    if out_width == 0 || out_height == 0 {
+    let Some((out_width, out_height)) = phase_output_size((width, height), scale, phase_x, phase_y, rounding) else {
         return img.clone();
-    }
+    };
 
     let mut result = ImageBuffer::new(out_width, out_height);
     let center_offset = scale / 2;
 
     for out_y in 0..out_height {
         for out_x in 0..out_width {
-            let src_x = phase_x + out_x * scale + center_offset;
-            let src_y = phase_y + out_y * scale + center_offset;
-
-            if src_x < width && src_y < height {
-                result.put_pixel(out_x, out_y, *img.get_pixel(src_x, src_y));
+            // A partial edge block (Round/Ceil) samples its last pixel
+            let src_x = (phase_x + out_x * scale + center_offset).min(width - 1);
+            let src_y = (phase_y + out_y * scale + center_offset).min(height - 1);
+
+            let mut sample = *img.get_pixel(src_x, src_y);
+            if alpha != AlphaResolution::CenterAlpha {
+                let block_x = phase_x + out_x * scale..(phase_x + (out_x + 1) * scale).min(width);
+                let block_y = phase_y + out_y * scale..(phase_y + (out_y + 1) * scale).min(height);
+                resolve_block_alpha(img, block_x, block_y, alpha, &mut sample);
             }
+            result.put_pixel(out_x, out_y, sample);
         }
     }
 
     result
 }
 
+/// Output size of `downsample_with_phase` (None if it would leave the image
+/// unchanged because no whole output row or column fits)
+fn phase_output_size(
+    size: (u32, u32),
+    scale: u32,
+    phase_x: u32,
+    phase_y: u32,
+    rounding: DimensionRounding,
+) -> Option<(u32, u32)> {
+    let out_width = rounding.output_len(size.0.saturating_sub(phase_x), scale);
+    let out_height = rounding.output_len(size.1.saturating_sub(phase_y), scale);
+    (out_width > 0 && out_height > 0).then_some((out_width, out_height))
+}
+
+/// Output size of `downsample_fractional` (None if it would leave the image
+/// unchanged)
+fn fractional_output_size(size: (u32, u32), scale: f32, phase_x: f32, phase_y: f32) -> Option<(u32, u32)> {
+    let out_width = ((size.0 as f32 - phase_x) / scale).round().max(0.0) as u32;
+    let out_height = ((size.1 as f32 - phase_y) / scale).round().max(0.0) as u32;
+    (scale > 1.0 && out_width > 0 && out_height > 0).then_some((out_width, out_height))
+}
+
+/// Alpha at or above which a source pixel counts as opaque for `MajorityOpaque`
+const MAJORITY_ALPHA: u8 = 128;
+
+/// Apply a block-based `AlphaResolution` to one downsampled pixel
+///
+/// The sample's alpha becomes 0 or 255. If the block resolves to opaque but
+/// the sample itself is mostly transparent, its color is taken from the
+/// block's most opaque pixel, since a transparent pixel's RGB is often junk.
+fn resolve_block_alpha(
+    img: &RgbaImage,
+    block_x: std::ops::Range<u32>,
+    block_y: std::ops::Range<u32>,
+    policy: AlphaResolution,
+    sample: &mut Rgba<u8>,
+) {
+    let pixels: Vec<&Rgba<u8>> = block_y
+        .flat_map(|y| block_x.clone().map(move |x| (x, y)))
+        .map(|(x, y)| img.get_pixel(x, y))
+        .collect();
+    if pixels.is_empty() {
+        return;
+    }
+
+    let opaque = match policy {
+        AlphaResolution::CenterAlpha => return,
+        AlphaResolution::MajorityOpaque => {
+            pixels.iter().filter(|p| p[3] >= MAJORITY_ALPHA).count() * 2 > pixels.len()
+        }
+        AlphaResolution::ThresholdAt(threshold) => {
+            let total: u64 = pixels.iter().map(|p| p[3] as u64).sum();
+            total >= threshold as u64 * pixels.len() as u64
+        }
+    };
+
+    if !opaque {
+        sample[3] = 0;
+        return;
+    }
+    if sample[3] < MAJORITY_ALPHA {
+        if let Some(source) = pixels.iter().max_by_key(|p| p[3]) {
+            *sample = **source;
+        }
+    }
+    sample[3] = 255;
+}
+
+/// Downsample by a fractional scale, rounding each sample position
+///
+/// Output pixel `i` samples the center of its cell at
+/// `phase + (i + 0.5) * scale`, so rounding error stays under one source
+/// pixel everywhere instead of accumulating across the row as it does with
+/// an integer stride.
+fn downsample_fractional(
+    img: &RgbaImage,
+    scale: f32,
+    phase_x: f32,
+    phase_y: f32,
+    alpha: AlphaResolution,
+) -> RgbaImage {
+    let (width, height) = img.dimensions();
+    let Some((out_width, out_height)) = fractional_output_size((width, height), scale, phase_x, phase_y) else {
+        return img.clone();
+    };
+
+    ImageBuffer::from_fn(out_width, out_height, |out_x, out_y| {
+        let src_x = ((phase_x + (out_x as f32 + 0.5) * scale) as u32).min(width - 1);
+        let src_y = ((phase_y + (out_y as f32 + 0.5) * scale) as u32).min(height - 1);
+        let mut sample = *img.get_pixel(src_x, src_y);
+        if alpha != AlphaResolution::CenterAlpha {
+            let bounds = |i: u32, phase: f32, limit: u32| {
+                ((phase + i as f32 * scale) as u32).min(limit)..((phase + (i + 1) as f32 * scale) as u32).min(limit)
+            };
+            resolve_block_alpha(img, bounds(out_x, phase_x, width), bounds(out_y, phase_y, height), alpha, &mut sample);
+        }
+        sample
+    })
+}
+
+/// Luma gap above the sampled pixel at which a block's brightest pixel is
+/// treated as an intentional highlight
+const HIGHLIGHT_LUMA_DELTA: u8 = 64;
+
+/// Restore highlights lost to center sampling
+///
+/// For each output pixel, finds the brightest visible source pixel in its
+/// block (`phase + i * scale` .. `phase + (i + 1) * scale`). If it's more
+/// than `HIGHLIGHT_LUMA_DELTA` brighter than the current sample, the output
+/// takes that source pixel as-is, so no new colors are introduced.
+fn restore_highlights(src: &RgbaImage, out: &mut RgbaImage, scale: f32, phase_x: f32, phase_y: f32) {
+    let (width, height) = src.dimensions();
+    let bounds = |i: u32, phase: f32, limit: u32| {
+        let start = ((phase + i as f32 * scale) as u32).min(limit);
+        let end = ((phase + (i + 1) as f32 * scale) as u32).min(limit);
+        start..end
+    };
+    let brightness = |p: &Rgba<u8>| luma(p[0], p[1], p[2]);
+
+    for (out_x, out_y, sample) in out.enumerate_pixels_mut() {
+        let brightest = bounds(out_y, phase_y, height)
+            .flat_map(|y| bounds(out_x, phase_x, width).map(move |x| (x, y)))
+            .map(|(x, y)| src.get_pixel(x, y))
+            .filter(|p| p[3] > 0)
+            .max_by_key(|p| brightness(p));
+
+        if let Some(bright) = brightest {
+            if brightness(bright) > brightness(sample).saturating_add(HIGHLIGHT_LUMA_DELTA) {
+                *sample = *bright;
+            }
+        }
+    }
+}
+
 // ============================================================================
-// UTILITY FUNCTIONS
+// BACKGROUND REMOVAL
 // ============================================================================
 
-/// Trim transparent borders
-fn auto_trim(img: &RgbaImage) -> RgbaImage {
-    let (width, height) = img.dimensions();
+/// Summed absolute RGB difference between two pixels
+fn rgb_distance(a: &Rgba<u8>, b: &Rgba<u8>) -> u32 {
+    (0..3).map(|i| (a[i] as i32 - b[i] as i32).unsigned_abs()).sum()
+}
 
-    let mut min_x = width;
-    let mut max_x = 0;
-    let mut min_y = height;
-    let mut max_y = 0;
+/// Most common color along the image border
+fn dominant_edge_color(img: &RgbaImage) -> Option<Rgba<u8>> {
+    let (width, height) = img.dimensions();
+    let mut counts: HashMap<Rgba<u8>, u32> = HashMap::new();
 
+    for x in 0..width {
+        for y in [0, height - 1] {
+            let p = img.get_pixel(x, y);
+            if p[3] > 0 {
+                *counts.entry(*p).or_insert(0) += 1;
+            }
+        }
+    }
     for y in 0..height {
-        for x in 0..width {
-            if img.get_pixel(x, y)[3] > 0 {
-                min_x = min_x.min(x);
-                max_x = max_x.max(x);
-                min_y = min_y.min(y);
-                max_y = max_y.max(y);
+        for x in [0, width - 1] {
+            let p = img.get_pixel(x, y);
+            if p[3] > 0 {
+                *counts.entry(*p).or_insert(0) += 1;
             }
         }
     }
 
-    if min_x > max_x || min_y > max_y {
-        return ImageBuffer::new(1, 1);
+    counts.into_iter().max_by_key(|&(p, count)| (count, p.0)).map(|(p, _)| p)
+}
+
+/// Whether a pixel counts as background for a flood seeded at `reference`
+///
+/// Opaque pixels match within `tolerance`. Semi-transparent pixels are
+/// treated as intentional content (smoke, glass) and match only on an exact
+/// RGB match, so translucent effects survive and also stop the flood.
+fn is_background(p: &Rgba<u8>, reference: &Rgba<u8>, tolerance: u32) -> bool {
+    match p[3] {
+        0 => false,
+        255 => rgb_distance(p, reference) <= tolerance,
+        _ => rgb_distance(p, reference) == 0,
     }
+}
+
+/// Clear the region connected (per `connectivity`) to `seeds` whose color is
+/// within `tolerance` of `reference` (see `is_background`). Returns the
+/// number of pixels cleared.
+fn flood_clear(
+    img: &mut RgbaImage,
+    seeds: Vec<(u32, u32)>,
+    reference: Rgba<u8>,
+    tolerance: u32,
+    connectivity: &Connectivity,
+) -> usize {
+    let (width, height) = img.dimensions();
+    let mut visited = vec![false; (width * height) as usize];
+    let mut stack: Vec<(u32, u32)> = Vec::new();
+
+    for (x, y) in seeds {
+        let idx = (y * width + x) as usize;
+        let p = img.get_pixel(x, y);
+        if !visited[idx] && is_background(p, &reference, tolerance) {
+            visited[idx] = true;
+            stack.push((x, y));
+        }
+    }
+
+    let mut cleared = 0;
+    while let Some((x, y)) = stack.pop() {
+        img.get_pixel_mut(x, y)[3] = 0;
+        cleared += 1;
+
+        for (nx, ny) in crate::processor::get_neighbors(x, y, width, height, connectivity) {
+            let idx = (ny * width + nx) as usize;
+            let p = img.get_pixel(nx, ny);
+            if !visited[idx] && is_background(p, &reference, tolerance) {
+                visited[idx] = true;
+                stack.push((nx, ny));
+            }
+        }
+    }
+
+    cleared
+}
 
-    let crop_width = max_x - min_x + 1;
-    let crop_height = max_y - min_y + 1;
+/// Make the background transparent using the given mode
+///
+/// `tolerance` is the maximum summed RGB difference (0-765) from the seed
+/// color for a pixel to be treated as background; the flood spreads to
+/// neighbors per `connectivity`. Returns the number of pixels cleared; an
+/// out-of-bounds point clears nothing.
+pub fn remove_background(img: &mut RgbaImage, mode: &BgRemovalMode, tolerance: u32, connectivity: &Connectivity) -> usize {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return 0;
+    }
 
-    image::imageops::crop_imm(img, min_x, min_y, crop_width, crop_height).to_image()
+    match *mode {
+        BgRemovalMode::None => 0,
+        BgRemovalMode::EdgeFlood => {
+            let reference = match dominant_edge_color(img) {
+                Some(color) => color,
+                None => return 0,
+            };
+            let mut seeds = Vec::new();
+            for x in 0..width {
+                seeds.push((x, 0));
+                seeds.push((x, height - 1));
+            }
+            for y in 0..height {
+                seeds.push((0, y));
+                seeds.push((width - 1, y));
+            }
+            flood_clear(img, seeds, reference, tolerance, connectivity)
+        }
+        BgRemovalMode::FloodFromPoint { x, y } => {
+            if x >= width || y >= height {
+                return 0;
+            }
+            let reference = *img.get_pixel(x, y);
+            flood_clear(img, vec![(x, y)], reference, tolerance, connectivity)
+        }
+    }
 }
 
-/// Pad canvas to multiple
-fn pad_to_multiple(img: &RgbaImage, multiple: u32) -> RgbaImage {
+/// Background removal followed by `iterations` dilation passes
+///
+/// Each pass clears every visible pixel adjacent (per
+/// `dilation_connectivity`) to a pixel cleared so far, so the removed region
+/// grows into soft edges one pixel per pass. Returns the total number of
+/// pixels cleared.
+pub fn remove_background_dilated(
+    img: &mut RgbaImage,
+    mode: &BgRemovalMode,
+    tolerance: u32,
+    connectivity: &Connectivity,
+    iterations: u32,
+    dilation_connectivity: &Connectivity,
+) -> usize {
+    if iterations == 0 {
+        return remove_background(img, mode, tolerance, connectivity);
+    }
+
+    let before = img.clone();
+    let mut cleared = remove_background(img, mode, tolerance, connectivity);
     let (width, height) = img.dimensions();
+    let mut frontier: Vec<(u32, u32)> = background_mask(&before, img)
+        .enumerate_pixels()
+        .filter(|(_, _, p)| p[0] > 0)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+
+    for _ in 0..iterations {
+        let mut next = Vec::new();
+        for &(x, y) in &frontier {
+            for (nx, ny) in crate::processor::get_neighbors(x, y, width, height, dilation_connectivity) {
+                let p = img.get_pixel_mut(nx, ny);
+                if p[3] > 0 {
+                    p[3] = 0;
+                    next.push((nx, ny));
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        cleared += next.len();
+        frontier = next;
+    }
+
+    cleared
+}
+
+/// Background removal that refuses a seed point outside the image
+///
+/// With `mask_output`, also writes a mask of the removed pixels (white =
+/// background) there, for `processor::add_outline_in_region`. Returns the
+/// number of pixels cleared.
+pub fn remove_background_with_mask(
+    img: &mut RgbaImage,
+    mode: &BgRemovalMode,
+    tolerance: u32,
+    connectivity: &Connectivity,
+    mask_output: Option<&Path>,
+) -> Result<usize> {
+    if let BgRemovalMode::FloodFromPoint { x, y } = *mode {
+        let (width, height) = img.dimensions();
+        if x >= width || y >= height {
+            return Err(PixelsError::InvalidParameter(format!(
+                "Seed point ({}, {}) is outside the {}x{} image", x, y, width, height
+            )));
+        }
+    }
+    let before = mask_output.map(|_| img.clone());
+    let cleared = remove_background(img, mode, tolerance, connectivity);
+    if let (Some(mask_path), Some(before)) = (mask_output, before) {
+        if let Some(parent) = mask_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        background_mask(&before, img).save(mask_path)?;
+    }
+    Ok(cleared)
+}
+
+/// Mask of pixels cleared by background removal (255 = removed background)
+///
+/// `before` and `after` are the image before and after `remove_background`.
+pub fn background_mask(before: &RgbaImage, after: &RgbaImage) -> GrayImage {
+    GrayImage::from_fn(after.width(), after.height(), |x, y| {
+        let was_opaque = x < before.width() && y < before.height() && before.get_pixel(x, y)[3] > 0;
+        let removed = was_opaque && after.get_pixel(x, y)[3] == 0;
+        image::Luma([if removed { 255 } else { 0 }])
+    })
+}
+
+// ============================================================================
+// UTILITY FUNCTIONS
+// ============================================================================
+
+/// Trim transparent borders
+fn auto_trim(img: &RgbaImage) -> RgbaImage {
+    auto_trim_above(img, 0)
+}
+
+/// Trim borders whose pixels all have alpha at or below `alpha_threshold`
+///
+/// A fully trimmed image keeps its canvas rather than collapsing to 1x1.
+pub fn auto_trim_above(img: &RgbaImage, alpha_threshold: u8) -> RgbaImage {
+    match content_bounds_above(img, alpha_threshold) {
+        Some(b) => image::imageops::crop_imm(img, b.min_x, b.min_y, b.width, b.height).to_image(),
+        // Nothing visible: keep the canvas rather than collapsing to 1x1
+        None => img.clone(),
+    }
+}
+
+/// Trim to content, widened outward to the pixel grid
+///
+/// `phase_x`/`phase_y` locate the grid within the tight content bounds (as
+/// returned by phase detection on the auto-trimmed image). A partial cell at
+/// any edge is extended to a full cell, padding with transparency where it
+/// runs past the canvas, so the result is a whole number of `scale` blocks
+/// with the grid at phase 0.
+pub fn grid_aligned_trim(img: &RgbaImage, scale: u32, phase_x: u32, phase_y: u32) -> RgbaImage {
+    let bounds = match content_bounds(img) {
+        Some(b) if scale > 1 => b,
+        _ => return auto_trim(img),
+    };
+    let scale = scale as i64;
+    let align_start = |min: u32, phase: u32| {
+        let phase = phase as i64 % scale;
+        min as i64 + phase - if phase > 0 { scale } else { 0 }
+    };
+    let left = align_start(bounds.min_x, phase_x);
+    let top = align_start(bounds.min_y, phase_y);
+    let width = (bounds.max_x as i64 + 1 - left + scale - 1) / scale * scale;
+    let height = (bounds.max_y as i64 + 1 - top + scale - 1) / scale * scale;
+
+    let mut out = RgbaImage::new(width as u32, height as u32);
+    image::imageops::replace(&mut out, img, -left, -top);
+    out
+}
 
-    let new_width = ((width + multiple - 1) / multiple) * multiple;
-    let new_height = ((height + multiple - 1) / multiple) * multiple;
+/// Pad canvas to multiple, placing the original according to `align`
+fn pad_to_multiple(img: &RgbaImage, multiple: u32, align: Align) -> RgbaImage {
+    let (width, height) = img.dimensions();
+    let (new_width, new_height) = padded_size((width, height), multiple);
 
     if new_width == width && new_height == height {
         return img.clone();
@@ -505,18 +1410,87 @@ fn pad_to_multiple(img: &RgbaImage, multiple: u32) -> RgbaImage {
 
     let mut canvas = ImageBuffer::from_pixel(new_width, new_height, Rgba([0, 0, 0, 0]));
 
-    let offset_x = (new_width - width) / 2;
-    let offset_y = (new_height - height) / 2;
+    let (offset_x, offset_y) = align.offset((new_width, new_height), (width, height));
 
     image::imageops::overlay(&mut canvas, img, offset_x as i64, offset_y as i64);
 
     canvas
 }
 
+/// Size after `pad_to_multiple`
+fn padded_size(size: (u32, u32), multiple: u32) -> (u32, u32) {
+    (size.0.div_ceil(multiple) * multiple, size.1.div_ceil(multiple) * multiple)
+}
+
 // ============================================================================
 // PUBLIC API
 // ============================================================================
 
+/// Compute the content bounding box (alpha > 0) and alpha-weighted centroid
+/// Returns None for a fully transparent image
+pub fn content_bounds(img: &RgbaImage) -> Option<BoundsInfo> {
+    content_bounds_above(img, 0)
+}
+
+/// `content_bounds` counting only pixels with alpha above `alpha_threshold`
+fn content_bounds_above(img: &RgbaImage, alpha_threshold: u8) -> Option<BoundsInfo> {
+    let (width, height) = img.dimensions();
+
+    let mut min_x = width;
+    let mut max_x = 0;
+    let mut min_y = height;
+    let mut max_y = 0;
+    let mut weight = 0.0f64;
+    let mut sum_x = 0.0f64;
+    let mut sum_y = 0.0f64;
+
+    for y in 0..height {
+        for x in 0..width {
+            let alpha = img.get_pixel(x, y)[3];
+            if alpha > alpha_threshold {
+                min_x = min_x.min(x);
+                max_x = max_x.max(x);
+                min_y = min_y.min(y);
+                max_y = max_y.max(y);
+                let a = alpha as f64;
+                weight += a;
+                sum_x += a * x as f64;
+                sum_y += a * y as f64;
+            }
+        }
+    }
+
+    if min_x > max_x || min_y > max_y {
+        return None;
+    }
+
+    Some(BoundsInfo {
+        min_x,
+        min_y,
+        max_x,
+        max_y,
+        width: max_x - min_x + 1,
+        height: max_y - min_y + 1,
+        centroid_x: (sum_x / weight) as f32,
+        centroid_y: (sum_y / weight) as f32,
+    })
+}
+
+/// Find a pivot candidate where the sprite touches the ground
+///
+/// Takes the lowest row with any content (alpha > 0) and returns the
+/// centroid of that row's content pixels, normalized to 0.0-1.0 of the image
+/// size using pixel centers. Returns None for a fully transparent image.
+pub fn detect_ground_contact(img: &RgbaImage) -> Option<(f32, f32)> {
+    let (width, height) = img.dimensions();
+
+    let row = (0..height).rev().find(|&y| (0..width).any(|x| img.get_pixel(x, y)[3] > 0))?;
+    let xs: Vec<u32> = (0..width).filter(|&x| img.get_pixel(x, row)[3] > 0).collect();
+    let mean_x = xs.iter().map(|&x| x as f32).sum::<f32>() / xs.len() as f32;
+
+    Some(((mean_x + 0.5) / width as f32, (row as f32 + 0.5) / height as f32))
+}
+
 /// Public wrapper: Auto-trim transparent borders from an image
 pub fn auto_trim_image(img: &RgbaImage) -> RgbaImage {
     auto_trim(img)
@@ -524,23 +1498,72 @@ pub fn auto_trim_image(img: &RgbaImage) -> RgbaImage {
 
 /// Public wrapper: Detect grid size using FFT
 pub fn detect_grid_for_image(img: &RgbaImage) -> Option<f32> {
-    detect_grid_size(img)
+    detect_grid_size(img, FftWindow::None)
 }
 
 /// Public wrapper: Find optimal scale and phase
 pub fn find_optimal_scale_for_image(img: &RgbaImage, grid_hint: Option<f32>) -> (u32, u32, u32) {
-    find_optimal_scale_v4(img, grid_hint)
+    find_optimal_scale_v4(img, grid_hint, ScaleTiebreak::default())
+}
+
+/// Public wrapper: Find best phase for a known scale
+/// Returns (phase_x, phase_y, variance)
+pub fn find_phase_for_image(img: &RgbaImage, scale: u32) -> (u32, u32, f32) {
+    find_best_phase_for_scale(img, scale)
+}
+
+/// Public wrapper: Find best phase, refining from all four quadrant seeds
+/// Returns (phase_x, phase_y, variance)
+pub fn find_phase_multi_seed_for_image(img: &RgbaImage, scale: u32) -> (u32, u32, f32) {
+    find_best_phase_multi_seed(img, scale)
 }
 
 /// Public wrapper: Downsample with phase-aware sampling
 pub fn downsample_image(img: &RgbaImage, scale: u32, phase_x: u32, phase_y: u32) -> RgbaImage {
-    downsample_with_phase(img, scale, phase_x, phase_y)
+    downsample_with_phase(img, scale, phase_x, phase_y, AlphaResolution::CenterAlpha, DimensionRounding::Floor)
 }
 
-/// Downscale image to exact target dimensions using nearest-neighbor sampling
-/// This is for manual user-specified dimensions when auto-detection isn't right
-pub fn downscale_to_dimensions(img: &RgbaImage, target_width: u32, target_height: u32) -> RgbaImage {
-    let (src_width, src_height) = img.dimensions();
+/// Reject a zero scale before it reaches the sampling code
+fn check_scale(scale: u32) -> Result<()> {
+    if scale == 0 {
+        return Err(PixelsError::InvalidParameter("Scale must be at least 1".to_string()));
+    }
+    Ok(())
+}
+
+/// `find_phase_for_image` on an image file
+pub fn find_phase_for_file(input: &Path, scale: u32) -> Result<(u32, u32, f32)> {
+    check_scale(scale)?;
+    let img = crate::processor::load_image(input)?;
+    Ok(find_phase_for_image(&img, scale))
+}
+
+/// Downsample an image file with an exact scale and phase (no detection),
+/// writing the result under `options`
+pub fn downsample_file(
+    input: &Path,
+    output: &Path,
+    scale: u32,
+    phase_x: u32,
+    phase_y: u32,
+    options: &SaveOptions,
+) -> Result<()> {
+    check_scale(scale)?;
+    crate::processor::edit_file(input, output, options, |img| {
+        *img = downsample_image(img, scale, phase_x, phase_y);
+        Ok(())
+    })
+}
+
+/// Public wrapper: Downsample with a fractional stride
+pub fn downsample_image_fractional(img: &RgbaImage, scale: f32, phase_x: f32, phase_y: f32) -> RgbaImage {
+    downsample_fractional(img, scale, phase_x, phase_y, AlphaResolution::CenterAlpha)
+}
+
+/// Downscale image to exact target dimensions using nearest-neighbor sampling
+/// This is for manual user-specified dimensions when auto-detection isn't right
+pub fn downscale_to_dimensions(img: &RgbaImage, target_width: u32, target_height: u32) -> RgbaImage {
+    let (src_width, src_height) = img.dimensions();
 
     if target_width == 0 || target_height == 0 {
         return img.clone();
@@ -574,6 +1597,31 @@ pub fn downscale_to_dimensions(img: &RgbaImage, target_width: u32, target_height
     result
 }
 
+/// Downscale to fit inside the target box while keeping the aspect ratio
+///
+/// The image is shrunk (never enlarged) by the same factor on both axes
+/// until it fits, then centered on a transparent canvas of exactly the
+/// target size.
+pub fn fit_to_dimensions(img: &RgbaImage, target_width: u32, target_height: u32) -> RgbaImage {
+    let (src_width, src_height) = img.dimensions();
+
+    if target_width == 0 || target_height == 0 || src_width == 0 || src_height == 0 {
+        return img.clone();
+    }
+
+    let fit = (target_width as f32 / src_width as f32)
+        .min(target_height as f32 / src_height as f32)
+        .min(1.0);
+    let fit_width = ((src_width as f32 * fit).round() as u32).clamp(1, target_width);
+    let fit_height = ((src_height as f32 * fit).round() as u32).clamp(1, target_height);
+    let fitted = downscale_to_dimensions(img, fit_width, fit_height);
+
+    let mut canvas = ImageBuffer::from_pixel(target_width, target_height, Rgba([0, 0, 0, 0]));
+    let (offset_x, offset_y) = Align::Center.offset((target_width, target_height), fitted.dimensions());
+    image::imageops::overlay(&mut canvas, &fitted, offset_x as i64, offset_y as i64);
+    canvas
+}
+
 /// Downscale image with manual settings (target dimensions)
 /// Returns PNG bytes for preview
 pub fn downscale_manual_preview(img: &RgbaImage, settings: &ManualDownscaleSettings) -> RgbaImage {
@@ -585,27 +1633,73 @@ pub fn downscale_manual_preview(img: &RgbaImage, settings: &ManualDownscaleSetti
     }
 
     // Downscale to target dimensions
-    downscale_to_dimensions(&working, settings.target_width, settings.target_height)
+    if settings.preserve_aspect {
+        fit_to_dimensions(&working, settings.target_width, settings.target_height)
+    } else {
+        downscale_to_dimensions(&working, settings.target_width, settings.target_height)
+    }
+}
+
+/// `downscale_manual_preview` on an image file, as PNG bytes
+pub fn manual_preview_png(input: &Path, settings: &ManualDownscaleSettings) -> Result<Vec<u8>> {
+    let img = crate::processor::load_image(input)?;
+    crate::processor::encode_png(&downscale_manual_preview(&img, settings))
+}
+
+/// Downscale a file to manual target dimensions and save it, bypassing
+/// grid detection entirely
+///
+/// `scale_factor` in the result is the larger per-axis shrink applied to
+/// the (trimmed) content.
+pub fn downscale_image_to_dimensions(
+    input_path: PathBuf,
+    output_path: PathBuf,
+    settings: &ManualDownscaleSettings,
+    options: &SaveOptions,
+) -> Result<DownscaleResult> {
+    let target = resolve_output_path(&input_path, &output_path, options)?;
+    let rgba = crate::processor::load_image(&input_path)?;
+    let original_size = rgba.dimensions();
+
+    let content = if settings.auto_trim { auto_trim(&rgba) } else { rgba.clone() };
+    let output = downscale_manual_preview(&rgba, settings);
+
+    // Measure the shrink on the content itself, not the letterbox around it
+    let (content_width, content_height) = content.dimensions();
+    let (out_width, out_height) = content_bounds(&output)
+        .filter(|_| settings.preserve_aspect)
+        .map_or(output.dimensions(), |b| (b.width, b.height));
+    let scale_factor = (content_width as f32 / out_width.max(1) as f32)
+        .max(content_height as f32 / out_height.max(1) as f32);
+
+    if let Some(path) = target {
+        crate::processor::save_image(&output, &path)?;
+    }
+
+    Ok(DownscaleResult {
+        original_size,
+        final_size: output.dimensions(),
+        scale_factor,
+        grid_detected: false,
+        warnings: Vec::new(),
+    })
 }
 
 /// Detect the scale factor of an image without modifying it
 /// Returns detection results including whether the image appears to be AI-upscaled
 pub fn detect_scale(input_path: PathBuf) -> Result<ScaleDetectionResult> {
     // Load image
-    let img = image::open(&input_path)
-        .map_err(|e| PixelsError::Processing(format!("Failed to load {}: {}", input_path.display(), e)))?;
-
-    let rgba = img.to_rgba8();
+    let rgba = crate::processor::load_image(&input_path)?;
     let dimensions = rgba.dimensions();
 
     // Trim for accurate detection (same as downscale_image does)
     let trimmed = auto_trim(&rgba);
 
     // Detect grid using FFT
-    let grid_hint = detect_grid_size(&trimmed);
+    let grid_hint = detect_grid_size(&trimmed, FftWindow::None);
 
     // Find optimal scale and get all variance results (reused for confidence)
-    let (scale, _phase_x, _phase_y, all_results) = find_optimal_scale_v4_with_results(&trimmed, grid_hint);
+    let (scale, _phase_x, _phase_y, all_results) = find_optimal_scale_v4_with_results(&trimmed, grid_hint, ScaleTiebreak::default());
 
     // Calculate confidence from the already-computed variance results
     let min_var = all_results.iter().map(|r| r.variance).fold(f32::MAX, f32::min);
@@ -637,62 +1731,551 @@ pub fn detect_scale(input_path: PathBuf) -> Result<ScaleDetectionResult> {
         is_ai_upscaled,
         dimensions,
         estimated_native_size,
+        suggested_settings: suggest_settings(&rgba, estimated_native_size),
     })
 }
 
+/// Downscaler settings suited to an image
+///
+/// - `auto_trim` only when there is a transparent border to trim
+/// - `bg_removal_mode` is `EdgeFlood` only for fully opaque images; images
+///   that already have transparency keep it as-is (`None`)
+/// - `canvas_multiple` from `suggest_canvas_multiple` on the native size
+pub fn suggest_settings(img: &RgbaImage, native_size: (u32, u32)) -> DownscalerSettings {
+    let has_border = content_bounds(img).is_some_and(|b| (b.width, b.height) != img.dimensions());
+    let has_transparency = img.pixels().any(|p| p[3] < 255);
+
+    DownscalerSettings {
+        auto_trim: has_border,
+        bg_removal_mode: if has_transparency { BgRemovalMode::None } else { BgRemovalMode::EdgeFlood },
+        canvas_multiple: suggest_canvas_multiple(&[native_size]),
+        ..DownscalerSettings::default()
+    }
+}
+
+// ============================================================================
+// QUALITY REPORT
+// ============================================================================
+
+/// Per-channel tolerance for counting a block as uniform
+const UNIFORM_BLOCK_TOLERANCE: u8 = 8;
+
+/// Compare a downscaled image against its source at the given scale
+///
+/// The downscaled image is upscaled back by `scale` (nearest-neighbor,
+/// aligned at the origin) and compared pixel by pixel against `original`
+/// inside its content bounds. High error or many non-uniform blocks suggest
+/// the scale (or phase) was wrong.
+pub fn downscale_quality(original: &RgbaImage, downscaled: &RgbaImage, scale: u32) -> QualityReport {
+    let scale = scale.max(1);
+    let (up_w, up_h) = (downscaled.width() * scale, downscaled.height() * scale);
+    let bounds = content_bounds(original);
+
+    let (x0, y0, x1, y1) = match bounds {
+        Some(b) => (b.min_x, b.min_y, (b.max_x + 1).min(up_w), (b.max_y + 1).min(up_h)),
+        None => (0, 0, 0, 0),
+    };
+
+    let mut total_error = 0u64;
+    let mut compared = 0u64;
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let a = original.get_pixel(x, y);
+            let b = downscaled.get_pixel(x / scale, y / scale);
+            total_error += (0..4).map(|c| (a[c] as i32 - b[c] as i32).unsigned_abs() as u64).sum::<u64>();
+            compared += 1;
+        }
+    }
+
+    let mut blocks = 0u32;
+    let mut nonuniform = 0u32;
+    if x1 > x0 && y1 > y0 {
+        for by in (y0 / scale)..y1.div_ceil(scale) {
+            for bx in (x0 / scale)..x1.div_ceil(scale) {
+                let first = original.get_pixel(bx * scale, by * scale);
+                let uniform = (by * scale..((by + 1) * scale).min(original.height())).all(|y| {
+                    (bx * scale..((bx + 1) * scale).min(original.width())).all(|x| {
+                        let p = original.get_pixel(x, y);
+                        (0..4).all(|c| p[c].abs_diff(first[c]) <= UNIFORM_BLOCK_TOLERANCE)
+                    })
+                });
+                blocks += 1;
+                if !uniform {
+                    nonuniform += 1;
+                }
+            }
+        }
+    }
+
+    QualityReport {
+        mean_error: if compared > 0 { total_error as f32 / (compared * 4) as f32 } else { 0.0 },
+        nonuniform_block_fraction: if blocks > 0 { nonuniform as f32 / blocks as f32 } else { 0.0 },
+        compared_pixels: compared,
+    }
+}
+
+/// `downscale_quality` on two image files
+pub fn downscale_quality_for_files(original: &Path, downscaled: &Path, scale: u32) -> Result<QualityReport> {
+    check_scale(scale)?;
+    let original_img = crate::processor::load_image(original)?;
+    let downscaled_img = crate::processor::load_image(downscaled)?;
+    Ok(downscale_quality(&original_img, &downscaled_img, scale))
+}
+
 // ============================================================================
 // MAIN ENTRY POINT
 // ============================================================================
 
 /// Main downscale function using v4 algorithm (block variance + phase search)
+///
+/// Refuses to write over the input; see `downscale_image_with_options`.
 pub fn downscale_image(
     input_path: PathBuf,
     output_path: PathBuf,
     settings: DownscalerSettings,
 ) -> Result<DownscaleResult> {
-    // Load image
-    let img = image::open(&input_path)
-        .map_err(|e| PixelsError::Processing(format!("Failed to load {}: {}", input_path.display(), e)))?;
+    downscale_image_with_options(input_path, output_path, settings, &SaveOptions::default())
+}
+
+/// `downscale_image`, writing the output under `options`
+pub fn downscale_image_with_options(
+    input_path: PathBuf,
+    output_path: PathBuf,
+    settings: DownscalerSettings,
+    options: &SaveOptions,
+) -> Result<DownscaleResult> {
+    let Some(output_path) = resolve_output_path(&input_path, &output_path, options)? else {
+        return downscale_file(&input_path, &settings).map(|(_, result)| result);
+    };
+    let (rgba, result) = downscale_file(&input_path, &settings)?;
+
+    // Ensure output directory exists
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Save result, recording the removed scale in PNG outputs
+    let is_png = output_path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    if is_png && result.scale_factor > 1.0 {
+        let png = crate::processor::encode_png(&rgba)?;
+        let tagged = crate::png_text::write_text(&png, DOWNSCALED_BY_KEY, &result.scale_factor.to_string())?;
+        std::fs::write(&output_path, tagged)?;
+    } else {
+        rgba.save(&output_path)?;
+    }
+
+    Ok(result)
+}
 
-    let mut rgba = img.to_rgba8();
+/// Read an upscale factor recorded under `key` in a PNG's text chunks
+///
+/// None if the file isn't a PNG, has no such chunk, or the value isn't a
+/// number of at least 1.
+pub fn read_scale_hint(path: &Path, key: &str) -> Option<f32> {
+    let bytes = std::fs::read(path).ok()?;
+    let text = crate::png_text::read_text(&bytes, key).ok()??;
+    text.trim().parse::<f32>().ok().filter(|hint| hint.is_finite() && *hint >= 1.0)
+}
+
+/// Run the `downscale_image` pipeline in memory without writing anything
+pub fn downscale_file(input_path: &Path, settings: &DownscalerSettings) -> Result<(RgbaImage, DownscaleResult)> {
+    // Load image
+    let rgba = crate::processor::load_image(input_path)?;
     let original_size = rgba.dimensions();
+    let scale_hint = settings.scale_hint_key.as_deref().and_then(|key| read_scale_hint(input_path, key));
+
+    let DownscalePlan { mut rgba, grid_hint, scale, phase_x, phase_y, fractional, warnings } =
+        plan_downscale(rgba, settings, scale_hint);
+
+    // Step 4: Downsample with phase-aware sampling, optionally stepping by
+    // the FFT grid size when it's close to the chosen integer scale
+    let scale_factor = fractional.unwrap_or(scale as f32);
+    if let Some(stride) = fractional.filter(|_| scale > 1) {
+        let mut out = downsample_fractional(&rgba, stride, phase_x as f32, phase_y as f32, settings.alpha_resolution);
+        if settings.restore_highlights {
+            restore_highlights(&rgba, &mut out, stride, phase_x as f32, phase_y as f32);
+        }
+        rgba = out;
+    } else if scale > 1 {
+        let mut out = downsample_with_phase(
+            &rgba,
+            scale,
+            phase_x,
+            phase_y,
+            settings.alpha_resolution,
+            settings.dimension_rounding,
+        );
+        if settings.restore_highlights {
+            restore_highlights(&rgba, &mut out, scale as f32, phase_x as f32, phase_y as f32);
+        }
+        rgba = out;
+    }
+
+    // Step 5: Pad canvas if enabled
+    if settings.pad_canvas {
+        rgba = pad_to_multiple(&rgba, settings.canvas_multiple, settings.pad_align);
+    }
+
+    let result = DownscaleResult {
+        original_size,
+        final_size: rgba.dimensions(),
+        scale_factor,
+        grid_detected: grid_hint.is_some(),
+        warnings,
+    };
+    Ok((rgba, result))
+}
+
+/// Detection results `downscale_file` samples with
+struct DownscalePlan {
+    /// Background-removed and trimmed source
+    rgba: RgbaImage,
+    grid_hint: Option<f32>,
+    scale: u32,
+    phase_x: u32,
+    phase_y: u32,
+    /// Grid stride to sample with instead of `scale`, when enabled
+    fractional: Option<f32>,
+    warnings: Vec<String>,
+}
+
+/// Steps 0-3 of the pipeline: background removal, trim and scale/phase
+/// detection. A `scale_hint` (see `read_scale_hint`) replaces detection.
+fn plan_downscale(mut rgba: RgbaImage, settings: &DownscalerSettings, scale_hint: Option<f32>) -> DownscalePlan {
+    // Step 0: Remove background so trim and detection only see the sprite
+    let mut warnings = Vec::new();
+    remove_background_dilated(
+        &mut rgba,
+        &settings.bg_removal_mode,
+        settings.bg_tolerance,
+        &settings.bg_connectivity,
+        settings.bg_dilation_iterations,
+        &settings.bg_dilation_connectivity,
+    );
+    if settings.bg_removal_mode == BgRemovalMode::None && rgba.pixels().all(|p| p[3] == 255) {
+        match settings.on_opaque_input {
+            OpaqueInput::Warn => warnings.push(
+                "Image has no transparency and background removal is off; the whole canvas is treated as content"
+                    .to_string(),
+            ),
+            OpaqueInput::AutoRemove => {
+                let cleared = remove_background(&mut rgba, &BgRemovalMode::EdgeFlood, settings.bg_tolerance, &settings.bg_connectivity);
+                warnings.push(format!("Image had no transparency; removed {} background pixels", cleared));
+            }
+            OpaqueInput::Proceed => {}
+        }
+    }
 
     // Step 1: Auto trim before scale detection (important for accurate FFT)
+    let untrimmed = (settings.auto_trim && settings.grid_aligned_trim).then(|| rgba.clone());
     if settings.auto_trim {
         rgba = auto_trim(&rgba);
     }
 
     // Step 2: Detect grid size using FFT
-    let grid_hint = detect_grid_size(&rgba);
+    let mut grid_hint = detect_grid_size(&rgba, settings.fft_window);
+
+    // Step 3: Find optimal scale and phase using v4 algorithm, unless the
+    // file recorded its own upscale factor
+    let (width, height) = rgba.dimensions();
+    let scale_hint = scale_hint.filter(|&hint| {
+        let fits = hint.round() as u32 <= width.min(height);
+        if !fits {
+            warnings.push(format!("Ignoring recorded {}x scale; content is only {}x{}", hint, width, height));
+        }
+        fits
+    });
+    let (mut scale, mut phase_x, mut phase_y) = match scale_hint {
+        Some(hint) => {
+            grid_hint = Some(hint);
+            let scale = hint.round() as u32;
+            let (phase_x, phase_y, _) = if scale > 1 { find_best_phase_for_scale(&rgba, scale) } else { (0, 0, 0.0) };
+            (scale, phase_x, phase_y)
+        }
+        None => find_optimal_scale_v4(&rgba, grid_hint, settings.scale_tiebreak),
+    };
+    if let Some(floor) = settings.min_output_dimension {
+        let fits = |s: u32, px: u32, py: u32| {
+            phase_output_size(rgba.dimensions(), s, px, py, settings.dimension_rounding)
+                .is_some_and(|(w, h)| w.min(h) >= floor)
+        };
+        if scale > 1 && !fits(scale, phase_x, phase_y) {
+            let detected = scale;
+            (scale, phase_x, phase_y) = (2..detected)
+                .rev()
+                .map(|s| {
+                    let (px, py, _) = find_best_phase_for_scale(&rgba, s);
+                    (s, px, py)
+                })
+                .find(|&(s, px, py)| fits(s, px, py))
+                .unwrap_or((1, 0, 0));
+            warnings.push(format!(
+                "Detected {}x scale would shrink the content below {}px; using {}x instead",
+                detected, floor, scale
+            ));
+        }
+    }
+    if settings.multi_seed_phase && scale > 1 {
+        (phase_x, phase_y, _) = find_best_phase_multi_seed(&rgba, scale);
+    }
 
-    // Step 3: Find optimal scale and phase using v4 algorithm
-    let (scale, phase_x, phase_y) = find_optimal_scale_v4(&rgba, grid_hint);
+    // Step 3a: Redo the trim on grid lines now that the grid is known
+    if let Some(untrimmed) = untrimmed.filter(|_| scale > 1) {
+        rgba = grid_aligned_trim(&untrimmed, scale, phase_x, phase_y);
+        (phase_x, phase_y) = (0, 0);
+    }
 
-    // Step 4: Downsample with phase-aware sampling
-    let scale_factor = scale as f32;
-    if scale > 1 {
-        rgba = downsample_with_phase(&rgba, scale, phase_x, phase_y);
+    // Step 3b: Sanity-check the scale against the content size
+    if crate::processor::is_empty_or_tiny(&rgba) {
+        warnings.push("Image is empty or fully transparent; output unchanged".to_string());
+    } else if scale <= 1 {
+        warnings.push(match grid_hint {
+            Some(_) => "No upscale detected; output unchanged".to_string(),
+            None => "No pixel grid detected; output unchanged".to_string(),
+        });
+    } else if grid_hint.is_none() {
+        warnings.push(format!(
+            "No pixel grid detected; scale {}x chosen from block variance alone",
+            scale
+        ));
     }
+    let scale = if scale > 1 {
+        check_scale_divisibility(rgba.dimensions(), scale, settings.snap_to_divisible_scale, &mut warnings)
+    } else {
+        scale
+    };
+    let fractional = grid_hint.filter(|hint| settings.fractional_stride && (hint - scale as f32).abs() < 0.5);
+
+    DownscalePlan { rgba, grid_hint, scale, phase_x, phase_y, fractional, warnings }
+}
+
+/// Predict the final size `downscale_image` would produce, without
+/// rendering the output
+///
+/// Runs the same background removal, trim and scale/phase detection (or
+/// uses `scale_hint`, as read from the file), then works out the sampled
+/// (and padded) size arithmetically.
+pub fn predict_output_size(img: &RgbaImage, settings: &DownscalerSettings, scale_hint: Option<f32>) -> (u32, u32) {
+    let plan = plan_downscale(img.clone(), settings, scale_hint);
+    let size = plan.rgba.dimensions();
+
+    let sampled = match plan.fractional {
+        Some(stride) if plan.scale > 1 => {
+            fractional_output_size(size, stride, plan.phase_x as f32, plan.phase_y as f32)
+        }
+        _ if plan.scale > 1 => {
+            phase_output_size(size, plan.scale, plan.phase_x, plan.phase_y, settings.dimension_rounding)
+        }
+        _ => None,
+    }
+    .unwrap_or(size);
 
-    // Step 5: Pad canvas if enabled
     if settings.pad_canvas {
-        rgba = pad_to_multiple(&rgba, settings.canvas_multiple);
+        padded_size(sampled, settings.canvas_multiple)
+    } else {
+        sampled
     }
+}
 
-    // Ensure output directory exists
-    if let Some(parent) = output_path.parent() {
-        std::fs::create_dir_all(parent)?;
+/// `predict_output_size` for an image file, reading its scale hint when
+/// `settings.scale_hint_key` names one
+pub fn predict_output_size_for_file(input: &Path, settings: &DownscalerSettings) -> Result<(u32, u32)> {
+    let img = crate::processor::load_image(input)?;
+    let scale_hint = settings
+        .scale_hint_key
+        .as_deref()
+        .and_then(|key| read_scale_hint(input, key));
+    Ok(predict_output_size(&img, settings, scale_hint))
+}
+
+/// Pixels of slack allowed when checking content size against a scale
+const DIVISIBILITY_TOLERANCE: u32 = 1;
+
+/// Whether `size` is a whole number of `scale` blocks, within tolerance
+fn divides_evenly(size: u32, scale: u32) -> bool {
+    let rem = size % scale;
+    rem <= DIVISIBILITY_TOLERANCE || scale - rem <= DIVISIBILITY_TOLERANCE
+}
+
+/// Check trimmed content dimensions against the detected scale
+///
+/// Pushes a warning when either side isn't a whole number of blocks. With
+/// `snap` set, returns the nearest scale (>= 2) that divides both sides
+/// instead, if there is one; otherwise returns `scale` unchanged.
+fn check_scale_divisibility(size: (u32, u32), scale: u32, snap: bool, warnings: &mut Vec<String>) -> u32 {
+    let (width, height) = size;
+    if divides_evenly(width, scale) && divides_evenly(height, scale) {
+        return scale;
     }
 
-    // Save result
-    rgba.save(&output_path)?;
+    warnings.push(format!(
+        "Content {}x{} is not a multiple of the detected {}x scale; output may be off by a pixel",
+        width, height, scale
+    ));
+    if !snap {
+        return scale;
+    }
 
-    Ok(DownscaleResult {
-        original_size,
-        final_size: rgba.dimensions(),
-        scale_factor,
-        grid_detected: grid_hint.is_some(),
-    })
+    let fits = |s: u32| s >= 2 && divides_evenly(width, s) && divides_evenly(height, s);
+    for distance in 1..scale {
+        for candidate in [scale - distance, scale + distance] {
+            if fits(candidate) {
+                warnings.push(format!("Using {}x instead", candidate));
+                return candidate;
+            }
+        }
+    }
+    scale
+}
+
+/// Upper bound on worker threads used by batch operations
+const MAX_BATCH_THREADS: usize = 8;
+
+/// Rayon pool for a batch of files, sized to the machine but capped at
+/// `MAX_BATCH_THREADS` so a batch doesn't claim every core
+pub(crate) fn batch_pool() -> Result<rayon::ThreadPool> {
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(MAX_BATCH_THREADS);
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| PixelsError::Processing(format!("Failed to build thread pool: {}", e)))
+}
+
+/// Downscale every image in `input_dir` into `output_dir` (as PNG)
+///
+/// Files run in parallel on a bounded rayon pool. A failure on one file is
+/// recorded in its entry rather than aborting the batch. Entries are returned
+/// in file-name order. Progress is checkpointed to `checkpoint_path`. See
+/// `BatchOptions` for the manifest, dry runs and resuming.
+pub fn downscale_folder(
+    input_dir: &Path,
+    output_dir: &Path,
+    settings: &DownscalerSettings,
+    options: BatchOptions,
+) -> Result<Vec<FolderDownscaleEntry>> {
+    let files = crate::processor::list_image_files(input_dir)?;
+    if !options.dry_run {
+        std::fs::create_dir_all(output_dir)?;
+    }
+
+    let pool = batch_pool()?;
+
+    let tracker = CheckpointTracker::start(checkpoint_path(output_dir), "downscale", output_dir, options.resume);
+
+    let (entries, manifest_entries): (Vec<_>, Vec<_>) = pool.install(|| {
+        files
+            .par_iter()
+            .map(|path| {
+                let file = path.file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let stem = path.file_stem()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| file.clone());
+                let output = output_dir.join(format!("{}.png", stem));
+                let output_path = output.to_string_lossy().to_string();
+
+                if tracker.was_done(&file) {
+                    let entry = FolderDownscaleEntry { file, output_path, result: None, error: None, skipped: true };
+                    return (entry, None);
+                }
+
+                let started = crate::state::now_iso();
+                let outcome = if options.dry_run {
+                    downscale_file(path, settings).map(|(_, result)| result)
+                } else {
+                    downscale_image(path.clone(), output.clone(), settings.clone())
+                };
+                let finished = crate::state::now_iso();
+
+                if outcome.is_ok() && !options.dry_run {
+                    tracker.complete(file.clone());
+                }
+
+                let entry = match outcome {
+                    Ok(result) => FolderDownscaleEntry { file, output_path, result: Some(result), error: None, skipped: false },
+                    Err(e) => FolderDownscaleEntry { file, output_path, result: None, error: Some(e.to_string()), skipped: false },
+                };
+                let manifest_entry = ManifestEntry {
+                    input: path.to_string_lossy().to_string(),
+                    output: entry.result.as_ref().map(|_| output.to_string_lossy().to_string()),
+                    result: entry.result.clone(),
+                    error: entry.error.clone(),
+                    started,
+                    finished,
+                };
+                (entry, Some(manifest_entry))
+            })
+            .unzip()
+    });
+    let manifest_entries: Vec<ManifestEntry> = manifest_entries.into_iter().flatten().collect();
+
+    if !options.dry_run {
+        tracker.finish(entries.iter().all(|e| e.error.is_none()))?;
+    }
+
+    if options.manifest && !options.dry_run {
+        let manifest = BatchManifest {
+            schema_version: MANIFEST_SCHEMA_VERSION,
+            operation: "downscale".to_string(),
+            created: crate::state::now_iso(),
+            entries: manifest_entries,
+        };
+        std::fs::write(output_dir.join(MANIFEST_FILE), serde_json::to_string_pretty(&manifest)?)?;
+    }
+
+    Ok(entries)
+}
+
+/// Largest canvas multiple `suggest_canvas_multiple` will propose
+const MAX_SUGGESTED_MULTIPLE: u32 = 64;
+
+/// Fraction of total padded area allowed to be padding for a multiple to qualify
+const MAX_PADDING_WASTE: f64 = 0.10;
+
+/// Suggest a power-of-two `canvas_multiple` for a set of sprite sizes
+///
+/// Returns the largest power of two (up to 64) whose padding waste across all
+/// sprites stays within 10% of the padded area. Sizes that already share a
+/// multiple waste nothing, so e.g. all-multiples-of-8 yields 8. Returns the
+/// default (16) for an empty set and 1 when no power of two fits.
+pub fn suggest_canvas_multiple(sizes: &[(u32, u32)]) -> u32 {
+    let sizes: Vec<(u32, u32)> = sizes.iter().copied().filter(|&(w, h)| w > 0 && h > 0).collect();
+    if sizes.is_empty() {
+        return DownscalerSettings::default().canvas_multiple;
+    }
+
+    let content: u64 = sizes.iter().map(|&(w, h)| w as u64 * h as u64).sum();
+
+    let mut multiple = MAX_SUGGESTED_MULTIPLE;
+    while multiple > 1 {
+        let m = multiple as u64;
+        let padded: u64 = sizes
+            .iter()
+            .map(|&(w, h)| (w as u64).div_ceil(m) * m * (h as u64).div_ceil(m) * m)
+            .sum();
+        let waste = (padded - content) as f64 / padded as f64;
+        if waste <= MAX_PADDING_WASTE {
+            return multiple;
+        }
+        multiple /= 2;
+    }
+
+    1
+}
+
+/// Suggest a canvas multiple for every image in a folder (header-only reads)
+pub fn suggest_canvas_multiple_for_folder(dir: &Path) -> Result<u32> {
+    let sizes: Vec<(u32, u32)> = crate::processor::list_image_files(dir)?
+        .iter()
+        .filter_map(|path| image::image_dimensions(path).ok())
+        .collect();
+    Ok(suggest_canvas_multiple(&sizes))
 }
 
 #[cfg(test)]
@@ -705,7 +2288,7 @@ mod tests {
             .map(|i| (i as f32 * std::f32::consts::PI / 5.0).sin())
             .collect();
 
-        let period = fft_detect_period(&signal, 5.0, 15.0);
+        let period = fft_detect_period(&signal, 5.0, 15.0, FftWindow::None);
         assert!(period.is_some());
 
         if let Some(p) = period {
@@ -720,4 +2303,723 @@ mod tests {
         let var = calculate_block_variance(&img, 10, 0, 0);
         assert!(var < 0.1, "Uniform image should have near-zero variance");
     }
+
+    #[test]
+    fn test_flood_from_point_clears_only_seeded_region() {
+        // Two gray background regions split by a red vertical wall
+        let bg = Rgba([200, 200, 200, 255]);
+        let wall = Rgba([255, 0, 0, 255]);
+        let mut img: RgbaImage = ImageBuffer::from_pixel(9, 5, bg);
+        for y in 0..5 {
+            img.put_pixel(4, y, wall);
+        }
+        // Slight noise within tolerance should still be cleared
+        img.put_pixel(1, 1, Rgba([205, 198, 200, 255]));
+
+        let cleared = remove_background(&mut img, &BgRemovalMode::FloodFromPoint { x: 0, y: 0 }, 15, &Connectivity::Four);
+
+        assert_eq!(cleared, 20);
+        assert_eq!(img.get_pixel(1, 1)[3], 0);
+        assert_eq!(img.get_pixel(3, 4)[3], 0);
+        assert_eq!(img.get_pixel(4, 2)[3], 255, "wall must survive");
+        assert_eq!(img.get_pixel(5, 0)[3], 255, "region past the wall must survive");
+    }
+
+    #[test]
+    fn test_flood_from_point_out_of_bounds() {
+        let mut img: RgbaImage = ImageBuffer::from_pixel(4, 4, Rgba([10, 10, 10, 255]));
+        let cleared = remove_background(&mut img, &BgRemovalMode::FloodFromPoint { x: 4, y: 0 }, 15, &Connectivity::Four);
+        assert_eq!(cleared, 0);
+    }
+
+    #[test]
+    fn test_flood_connectivity_controls_diagonal_gaps() {
+        // Background pockets along the diagonal, touching only at corners
+        let bg = Rgba([200, 200, 200, 255]);
+        let mut img: RgbaImage = ImageBuffer::from_pixel(4, 4, Rgba([255, 0, 0, 255]));
+        for i in 0..4 {
+            img.put_pixel(i, i, bg);
+        }
+        let seed = BgRemovalMode::FloodFromPoint { x: 0, y: 0 };
+
+        let mut four = img.clone();
+        assert_eq!(remove_background(&mut four, &seed, 15, &Connectivity::Four), 1);
+        assert_eq!(four.get_pixel(1, 1)[3], 255);
+
+        let mut eight = img;
+        assert_eq!(remove_background(&mut eight, &seed, 15, &Connectivity::Eight), 4);
+        assert_eq!(eight.get_pixel(3, 3)[3], 0);
+        assert_eq!(eight.get_pixel(1, 0)[3], 255);
+    }
+
+    /// Build a `native`-sized random block image upscaled by `scale`
+    fn synthetic_upscaled(native: u32, scale: u32, seed: u32) -> RgbaImage {
+        let mut state = seed;
+        let mut next = move || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        };
+        let small: RgbaImage = ImageBuffer::from_fn(native, native, |_, _| Rgba([next(), next(), next(), 255]));
+        ImageBuffer::from_fn(native * scale, native * scale, |x, y| *small.get_pixel(x / scale, y / scale))
+    }
+
+    #[test]
+    fn test_downscale_folder_reports_each_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+        let input = root.join("in");
+        let output = root.join("out");
+        std::fs::create_dir_all(&input).unwrap();
+
+        synthetic_upscaled(16, 8, 1).save(input.join("a.png")).unwrap();
+        synthetic_upscaled(12, 10, 2).save(input.join("b.png")).unwrap();
+        std::fs::write(input.join("notes.txt"), "not an image").unwrap();
+
+        let entries = downscale_folder(&input, &output, &DownscalerSettings::default(), BatchOptions::default()).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].file, "a.png");
+        assert_eq!(entries[1].file, "b.png");
+        let a = entries[0].result.as_ref().unwrap();
+        let b = entries[1].result.as_ref().unwrap();
+        assert_eq!(a.original_size, (128, 128));
+        assert_eq!(a.scale_factor, 8.0);
+        assert_eq!(b.original_size, (120, 120));
+        assert_eq!(b.scale_factor, 10.0);
+        assert!(output.join("a.png").exists());
+        assert!(!output.join(MANIFEST_FILE).exists());
+    }
+
+    #[test]
+    fn test_downscale_folder_writes_manifest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+        let input = root.join("in");
+        let output = root.join("out");
+        std::fs::create_dir_all(&input).unwrap();
+
+        synthetic_upscaled(16, 8, 3).save(input.join("a.png")).unwrap();
+        synthetic_upscaled(12, 10, 4).save(input.join("b.png")).unwrap();
+
+        let options = BatchOptions { manifest: true, ..BatchOptions::default() };
+        downscale_folder(&input, &output, &DownscalerSettings::default(), options).unwrap();
+
+        let json = std::fs::read_to_string(output.join(MANIFEST_FILE)).unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(manifest["schema_version"], MANIFEST_SCHEMA_VERSION);
+        assert_eq!(manifest["operation"], "downscale");
+
+        let entries = manifest["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        for (entry, name, scale) in [(&entries[0], "a.png", 8.0), (&entries[1], "b.png", 10.0)] {
+            assert!(entry["input"].as_str().unwrap().ends_with(name));
+            let out = entry["output"].as_str().unwrap();
+            assert!(std::path::Path::new(out).exists());
+            assert_eq!(entry["result"]["scale_factor"], scale);
+            assert!(entry["error"].is_null());
+            assert!(entry["started"].as_str().unwrap() <= entry["finished"].as_str().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_content_bounds_off_center_blob() {
+        let mut img: RgbaImage = ImageBuffer::from_pixel(20, 16, Rgba([0, 0, 0, 0]));
+        // 4x3 opaque blob at (10, 8)
+        for y in 8..11 {
+            for x in 10..14 {
+                img.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+        // Faint pixel extends the box but barely moves the centroid
+        img.put_pixel(2, 8, Rgba([255, 255, 255, 1]));
+
+        let b = content_bounds(&img).unwrap();
+        assert_eq!((b.min_x, b.min_y, b.max_x, b.max_y), (2, 8, 13, 10));
+        assert_eq!((b.width, b.height), (12, 3));
+        assert!((b.centroid_x - 11.5).abs() < 0.05, "centroid_x = {}", b.centroid_x);
+        assert!((b.centroid_y - 9.0).abs() < 0.05, "centroid_y = {}", b.centroid_y);
+
+        let empty: RgbaImage = ImageBuffer::new(5, 5);
+        assert!(content_bounds(&empty).is_none());
+    }
+
+    #[test]
+    fn test_pad_align_top_left() {
+        let img: RgbaImage = ImageBuffer::from_pixel(10, 6, Rgba([9, 9, 9, 255]));
+
+        let padded = pad_to_multiple(&img, 16, Align::TopLeft);
+        assert_eq!(padded.dimensions(), (16, 16));
+        assert_eq!(padded.get_pixel(0, 0)[3], 255);
+        assert_eq!(padded.get_pixel(9, 5)[3], 255);
+        assert_eq!(padded.get_pixel(10, 0)[3], 0);
+        assert_eq!(padded.get_pixel(0, 6)[3], 0);
+
+        // Default keeps the historical centered placement
+        let centered = pad_to_multiple(&img, 16, Align::default());
+        assert_eq!(centered.get_pixel(0, 0)[3], 0);
+        assert_eq!(centered.get_pixel(3, 5)[3], 255);
+
+        let bottom = pad_to_multiple(&img, 16, Align::BottomCenter);
+        assert_eq!(bottom.get_pixel(3, 15)[3], 255);
+        assert_eq!(bottom.get_pixel(3, 9)[3], 0);
+    }
+
+    #[test]
+    fn test_suggest_canvas_multiple() {
+        assert_eq!(suggest_canvas_multiple(&[(16, 24), (32, 8), (40, 48), (8, 8)]), 8);
+        assert_eq!(suggest_canvas_multiple(&[(32, 32), (64, 32), (96, 128)]), 32);
+        // Odd sizes only fit a multiple of 1
+        assert_eq!(suggest_canvas_multiple(&[(7, 9), (13, 5)]), 1);
+        assert_eq!(suggest_canvas_multiple(&[]), 16);
+    }
+
+    #[test]
+    fn test_find_phase_beats_wrong_phase() {
+        let img = synthetic_upscaled(24, 8, 3);
+        let (px, py, var) = find_phase_for_image(&img, 8);
+        let wrong = calculate_block_variance(&img, 8, (px + 4) % 8, (py + 4) % 8);
+        assert!(var < wrong, "best phase variance {} should be below wrong phase {}", var, wrong);
+    }
+
+    #[test]
+    fn test_downscale_quality_separates_right_and_wrong_scale() {
+        let img = synthetic_upscaled(16, 8, 5);
+
+        let good = downscale_quality(&img, &downsample_image(&img, 8, 0, 0), 8);
+        assert!(good.mean_error < 0.01, "correct scale error {}", good.mean_error);
+        assert_eq!(good.nonuniform_block_fraction, 0.0);
+
+        let bad = downscale_quality(&img, &downsample_image(&img, 6, 0, 0), 6);
+        assert!(bad.mean_error > 10.0, "wrong scale error {}", bad.mean_error);
+        assert!(bad.nonuniform_block_fraction > 0.5);
+    }
+
+    #[test]
+    fn test_ground_contact_uses_feet_not_body() {
+        // Wide body on the left, a single narrow leg off to the right
+        let mut img: RgbaImage = ImageBuffer::new(20, 20);
+        for y in 2..12 {
+            for x in 2..14 {
+                img.put_pixel(x, y, Rgba([100, 50, 50, 255]));
+            }
+        }
+        for y in 12..18 {
+            for x in 11..14 {
+                img.put_pixel(x, y, Rgba([40, 40, 40, 255]));
+            }
+        }
+
+        let (px, py) = detect_ground_contact(&img).unwrap();
+        assert!((px - 12.5 / 20.0).abs() < 1e-4, "pivot x {}", px);
+        assert!((py - 17.5 / 20.0).abs() < 1e-4, "pivot y {}", py);
+
+        let body_center = content_bounds(&img).unwrap().centroid_x / 20.0;
+        assert!((px - body_center).abs() > 0.1);
+
+        assert!(detect_ground_contact(&ImageBuffer::new(4, 4)).is_none());
+    }
+
+    #[test]
+    fn test_degenerate_images_downscale_as_no_op() {
+        assert_eq!(find_optimal_scale_for_image(&ImageBuffer::new(0, 0), None), (1, 0, 0));
+        assert_eq!(auto_trim(&ImageBuffer::new(7, 3)).dimensions(), (7, 3));
+
+        let tmp = tempfile::tempdir().unwrap();
+
+        let dir = tmp.path().to_path_buf();
+        for (name, w, h) in [("dot.png", 1, 1), ("strip.png", 1, 9)] {
+            let img: RgbaImage = ImageBuffer::new(w, h);
+            img.save(dir.join(name)).unwrap();
+            let result = downscale_image(dir.join(name), dir.join("out").join(name), DownscalerSettings::default()).unwrap();
+            assert_eq!(result.scale_factor, 1.0);
+            assert_eq!(result.final_size, (w, h));
+            assert!(result.warnings.iter().any(|w| w.contains("fully transparent")), "{:?}", result.warnings);
+
+            let detection = detect_scale(dir.join(name)).unwrap();
+            assert_eq!(detection.detected_scale, 1);
+            assert!(!detection.is_ai_upscaled);
+        }
+    }
+
+    #[test]
+    fn test_scale_divisibility_check() {
+        let mut warnings = Vec::new();
+        assert_eq!(check_scale_divisibility((80, 121), 10, false, &mut warnings), 10);
+        assert!(warnings.is_empty());
+
+        assert_eq!(check_scale_divisibility((87, 87), 10, false, &mut warnings), 10);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("87x87"), "{}", warnings[0]);
+
+        let mut warnings = Vec::new();
+        let snapped = check_scale_divisibility((87, 87), 10, true, &mut warnings);
+        assert_eq!(snapped, 11);
+        assert_eq!(warnings.len(), 2);
+
+        // End to end: a 10x sprite cut off mid-cell surfaces the warning
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let sprite = synthetic_upscaled(8, 10, 3);
+        image::imageops::crop_imm(&sprite, 0, 0, 75, 75).to_image().save(dir.join("cut.png")).unwrap();
+
+        let result = downscale_image(dir.join("cut.png"), dir.join("out.png"), DownscalerSettings::default()).unwrap();
+        assert_eq!(result.scale_factor, 10.0);
+        assert!(
+            result.warnings.iter().any(|w| w.contains("75x75 is not a multiple of the detected 10x scale")),
+            "{:?}",
+            result.warnings
+        );
+    }
+
+    #[test]
+    fn test_gridless_downscale_warns_about_fallback() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let img: RgbaImage = ImageBuffer::from_pixel(24, 24, Rgba([255, 0, 0, 255]));
+        img.save(dir.join("native.png")).unwrap();
+
+        let result = downscale_image(dir.join("native.png"), dir.join("out.png"), DownscalerSettings::default()).unwrap();
+        assert!(!result.grid_detected);
+        assert!(result.warnings.iter().any(|w| w.contains("No pixel grid detected")), "{:?}", result.warnings);
+    }
+
+    #[test]
+    fn test_fractional_stride_recovers_non_integer_grid() {
+        // 40x40 native image upscaled by 10.3 (cells alternate 10 and 11 px)
+        let native: RgbaImage = ImageBuffer::from_fn(40, 40, |x, y| {
+            Rgba([(x * 6) as u8, (y * 6) as u8, ((x + y) % 2 * 200) as u8, 255])
+        });
+        let size = (40.0f32 * 10.3).round() as u32;
+        let upscaled: RgbaImage = ImageBuffer::from_fn(size, size, |x, y| {
+            *native.get_pixel(((x as f32 / 10.3) as u32).min(39), ((y as f32 / 10.3) as u32).min(39))
+        });
+
+        let fractional = downsample_image_fractional(&upscaled, 10.3, 0.0, 0.0);
+        assert_eq!(fractional, native);
+
+        let integer = downsample_image(&upscaled, 10, 0, 0);
+        assert_ne!(integer.width(), 40);
+        assert_ne!(integer.get_pixel(35, 0), native.get_pixel(35, 0));
+    }
+
+    #[test]
+    fn test_bg_removal_keeps_semi_transparent_content() {
+        let mut img: RgbaImage = ImageBuffer::from_pixel(12, 12, Rgba([255, 255, 255, 255]));
+        // Translucent "glass" close to the background color, touching the border
+        for y in 0..6 {
+            for x in 3..9 {
+                img.put_pixel(x, y, Rgba([250, 250, 250, 128]));
+            }
+        }
+        // Translucent pixel exactly the background color is still removable
+        img.put_pixel(11, 11, Rgba([255, 255, 255, 90]));
+
+        let cleared = remove_background(&mut img, &BgRemovalMode::EdgeFlood, 15, &Connectivity::Four);
+
+        assert_eq!(cleared, 12 * 12 - 36);
+        assert_eq!(*img.get_pixel(5, 0), Rgba([250, 250, 250, 128]));
+        assert_eq!(img.get_pixel(0, 0)[3], 0);
+        assert_eq!(img.get_pixel(11, 11)[3], 0);
+    }
+
+    #[test]
+    fn test_bg_dilation_clears_more_fringe() {
+        // Opaque sprite on green with a two-pixel blended fringe outside tolerance
+        let make = || {
+            ImageBuffer::from_fn(20, 20, |x, y| {
+                let d = (x as i32 - 10).abs().max((y as i32 - 10).abs());
+                match d {
+                    0..=3 => Rgba([200, 40, 40, 255]),
+                    4 => Rgba([150, 100, 40, 255]),
+                    5 => Rgba([100, 160, 40, 255]),
+                    _ => Rgba([0, 255, 0, 255]),
+                }
+            })
+        };
+        let count_visible = |img: &RgbaImage| img.pixels().filter(|p| p[3] > 0).count();
+
+        let mut none = make();
+        remove_background_dilated(&mut none, &BgRemovalMode::EdgeFlood, 15, &Connectivity::Four, 0, &Connectivity::Four);
+        let mut one = make();
+        remove_background_dilated(&mut one, &BgRemovalMode::EdgeFlood, 15, &Connectivity::Four, 1, &Connectivity::Four);
+        let mut two = make();
+        remove_background_dilated(&mut two, &BgRemovalMode::EdgeFlood, 15, &Connectivity::Four, 2, &Connectivity::Four);
+
+        assert_eq!(count_visible(&none), 11 * 11);
+        assert_eq!(count_visible(&one), 9 * 9);
+        assert_eq!(count_visible(&two), 7 * 7);
+        assert_eq!(two.get_pixel(10, 10)[3], 255);
+    }
+
+    #[test]
+    fn test_suggested_settings_follow_image() {
+        let mut sprite = synthetic_upscaled(16, 8, 5);
+        for x in 0..sprite.width() {
+            sprite.put_pixel(x, 0, Rgba([0, 0, 0, 0]));
+        }
+        let suggested = suggest_settings(&sprite, (16, 16));
+        assert_eq!(suggested.bg_removal_mode, BgRemovalMode::None);
+        assert!(suggested.auto_trim);
+        assert_eq!(suggested.canvas_multiple, 16);
+
+        let opaque = synthetic_upscaled(16, 8, 6);
+        let suggested = suggest_settings(&opaque, (16, 16));
+        assert_eq!(suggested.bg_removal_mode, BgRemovalMode::EdgeFlood);
+        assert!(!suggested.auto_trim);
+    }
+
+    #[test]
+    fn test_grid_aligned_trim_is_divisible_by_scale() {
+        // 6x5 native sprite at 10x, placed off-grid, with the outer 3 columns
+        // and 4 rows of its edge cells erased so the tight trim cuts mid-cell
+        let sprite = synthetic_upscaled(6, 10, 7);
+        let mut canvas: RgbaImage = ImageBuffer::new(100, 100);
+        image::imageops::replace(&mut canvas, &image::imageops::crop_imm(&sprite, 0, 0, 60, 50).to_image(), 13, 17);
+        for y in 17..67 {
+            for x in 13..16 {
+                canvas.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+            }
+        }
+        for y in 17..21 {
+            for x in 13..73 {
+                canvas.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+            }
+        }
+
+        let tight = auto_trim(&canvas);
+        assert_eq!(tight.dimensions(), (57, 46));
+
+        let aligned = grid_aligned_trim(&canvas, 10, 7, 6);
+        assert_eq!(aligned.dimensions(), (60, 50));
+        assert_eq!(aligned.width() % 10, 0);
+        assert_eq!(aligned.height() % 10, 0);
+        // Grid now starts at 0: the first full cell is uniform
+        assert_eq!(aligned.get_pixel(10, 10), aligned.get_pixel(19, 19));
+        assert_eq!(aligned.get_pixel(10, 10), canvas.get_pixel(23, 27));
+    }
+
+    #[test]
+    fn test_downscale_folder_dry_run_writes_nothing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+        let input = root.join("in");
+        let output = root.join("out");
+        std::fs::create_dir_all(&input).unwrap();
+        synthetic_upscaled(16, 8, 1).save(input.join("a.png")).unwrap();
+        synthetic_upscaled(12, 10, 2).save(input.join("b.png")).unwrap();
+
+        let options = BatchOptions { manifest: true, dry_run: true, ..BatchOptions::default() };
+        let entries = downscale_folder(&input, &output, &DownscalerSettings::default(), options).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert!(entries[1].output_path.ends_with("b.png"));
+        assert!(!output.exists());
+
+        // Same results as a real run
+        let written = downscale_folder(&input, &output, &DownscalerSettings::default(), BatchOptions::default()).unwrap();
+        for (dry, real) in entries.iter().zip(&written) {
+            let (dry, real) = (dry.result.as_ref().unwrap(), real.result.as_ref().unwrap());
+            assert_eq!((dry.original_size, dry.final_size, dry.scale_factor), (real.original_size, real.final_size, real.scale_factor));
+        }
+        assert_eq!(entries[0].result.as_ref().unwrap().scale_factor, 8.0);
+    }
+
+    #[test]
+    fn test_multi_seed_phase_escapes_local_minimum() {
+        // Random 10x10 art at 9x, grid offset by 5px. Relative to the
+        // variance sampling region this puts the true phase at 8, one step
+        // from 0 across the wrap - the single search refines around the
+        // coarse winner 0 without wrapping and settles there.
+        let (scale, offset, n) = (9u32, 5u32, 10u32);
+        let mut state = 1u32;
+        let small: RgbaImage = ImageBuffer::from_fn(n + 1, n + 1, |_, _| {
+            let mut next = || {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as u8
+            };
+            Rgba([next(), next(), next(), 255])
+        });
+        let size = n * scale;
+        let img: RgbaImage = ImageBuffer::from_fn(size, size, |x, y| {
+            *small.get_pixel((x + scale - offset) / scale, (y + scale - offset) / scale)
+        });
+
+        let single = find_best_phase_for_scale(&img, scale);
+        let multi = find_best_phase_multi_seed(&img, scale);
+
+        assert_eq!((single.0, single.1), (0, 0));
+        assert_eq!((multi.0, multi.1), (8, 8));
+        assert_eq!(multi.2, 0.0);
+        assert!(multi.2 < single.2);
+    }
+
+    #[test]
+    fn test_restore_highlights_keeps_single_bright_pixel() {
+        // 4x4 dark art at 8x with a one-pixel glint off-center in block (1, 2)
+        let dark = Rgba([40, 30, 60, 255]);
+        let glint = Rgba([250, 250, 240, 255]);
+        let mut src: RgbaImage = ImageBuffer::from_pixel(32, 32, dark);
+        src.put_pixel(8 + 1, 16 + 6, glint);
+
+        let mut out = downsample_with_phase(&src, 8, 0, 0, AlphaResolution::CenterAlpha, DimensionRounding::Floor);
+        assert_eq!(*out.get_pixel(1, 2), dark);
+
+        restore_highlights(&src, &mut out, 8.0, 0.0, 0.0);
+        assert_eq!(*out.get_pixel(1, 2), glint);
+        // Every other block stays on its sampled color
+        assert_eq!(out.pixels().filter(|p| **p == glint).count(), 1);
+        assert!(out.pixels().all(|p| *p == dark || *p == glint));
+    }
+
+    #[test]
+    fn test_scale_tiebreak_policies() {
+        // Random art whose cells come in 2x2 same-color groups, at 6x:
+        // both 6 and 12 split it into perfectly uniform blocks
+        let mut state = 99u32;
+        let groups: RgbaImage = ImageBuffer::from_fn(8, 8, |_, _| {
+            let mut next = || {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as u8
+            };
+            Rgba([next(), next(), next(), 255])
+        });
+        let img: RgbaImage = ImageBuffer::from_fn(96, 96, |x, y| *groups.get_pixel(x / 12, y / 12));
+
+        let pick = |hint, tiebreak| find_optimal_scale_v4(&img, hint, tiebreak).0;
+        assert_eq!(pick(None, ScaleTiebreak::PreferLargest), 12);
+        assert_eq!(pick(None, ScaleTiebreak::PreferSmallest), 6);
+        assert_eq!(pick(Some(12.0), ScaleTiebreak::PreferSmallest), 6);
+        assert_eq!(pick(Some(6.2), ScaleTiebreak::PreferHint), 6);
+        assert_eq!(pick(Some(11.8), ScaleTiebreak::PreferHint), 12);
+        // Without a hint the default keeps choosing the largest scale
+        assert_eq!(pick(None, ScaleTiebreak::PreferHint), 12);
+    }
+
+    #[test]
+    fn test_alpha_resolution_majority_opaque() {
+        // One 5x5 block, 60% opaque, with a transparent center column
+        let img = RgbaImage::from_fn(5, 5, |x, _| {
+            if x == 2 || x == 3 { Rgba([0, 0, 0, 0]) } else { Rgba([200, 30, 30, 255]) }
+        });
+
+        let center = downsample_with_phase(&img, 5, 0, 0, AlphaResolution::CenterAlpha, DimensionRounding::Floor);
+        assert_eq!(center.get_pixel(0, 0)[3], 0);
+
+        let majority = downsample_with_phase(&img, 5, 0, 0, AlphaResolution::MajorityOpaque, DimensionRounding::Floor);
+        assert_eq!(*majority.get_pixel(0, 0), Rgba([200, 30, 30, 255]));
+
+        // Mean alpha is 153
+        let strict = downsample_with_phase(&img, 5, 0, 0, AlphaResolution::ThresholdAt(200), DimensionRounding::Floor);
+        assert_eq!(strict.get_pixel(0, 0)[3], 0);
+        let loose = downsample_with_phase(&img, 5, 0, 0, AlphaResolution::ThresholdAt(150), DimensionRounding::Floor);
+        assert_eq!(loose.get_pixel(0, 0)[3], 255);
+    }
+
+    #[test]
+    fn test_downscale_folder_resumes_from_checkpoint() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+        let input = root.join("in");
+        let output = root.join("out");
+        std::fs::create_dir_all(&input).unwrap();
+
+        synthetic_upscaled(8, 4, 1).save(input.join("a.png")).unwrap();
+        synthetic_upscaled(8, 4, 2).save(input.join("b.png")).unwrap();
+        // An unreadable file stands in for a run that stopped before finishing
+        std::fs::write(input.join("c.png"), b"not an image").unwrap();
+
+        let settings = DownscalerSettings::default();
+        let first = downscale_folder(&input, &output, &settings, BatchOptions::default()).unwrap();
+        assert!(first[2].error.is_some());
+        let json = std::fs::read_to_string(checkpoint_path(&output)).unwrap();
+        let checkpoint: BatchCheckpoint = serde_json::from_str(&json).unwrap();
+        let mut completed = checkpoint.completed.clone();
+        completed.sort();
+        assert_eq!(completed, vec!["a.png", "b.png"]);
+
+        // Done files are not redone on resume
+        std::fs::remove_file(output.join("a.png")).unwrap();
+        synthetic_upscaled(8, 4, 3).save(input.join("c.png")).unwrap();
+        let options = BatchOptions { resume: true, ..BatchOptions::default() };
+        let resumed = downscale_folder(&input, &output, &settings, options).unwrap();
+
+        assert!(resumed[0].skipped && resumed[1].skipped);
+        assert!(!resumed[2].skipped && resumed[2].result.is_some());
+        assert!(!output.join("a.png").exists());
+        assert!(output.join("c.png").exists());
+        assert!(!checkpoint_path(&output).exists());
+        assert!(!input.join(".pixels").exists());
+    }
+
+    #[test]
+    fn test_dimension_rounding_modes() {
+        // 30 px at scale 8 is 3.75 blocks
+        let img: RgbaImage = ImageBuffer::from_fn(30, 30, |x, y| Rgba([(x * 8) as u8, (y * 8) as u8, 0, 255]));
+        let sized = |rounding| downsample_with_phase(&img, 8, 0, 0, AlphaResolution::CenterAlpha, rounding).dimensions();
+
+        assert_eq!(sized(DimensionRounding::Floor), (3, 3));
+        assert_eq!(sized(DimensionRounding::Round), (4, 4));
+        assert_eq!(sized(DimensionRounding::Ceil), (4, 4));
+        assert_eq!(DimensionRounding::Round.output_len(27, 8), 3);
+        assert_eq!(DimensionRounding::Ceil.output_len(25, 8), 4);
+
+        // A partial block too narrow to hold its center samples its last pixel
+        let narrow = image::imageops::crop_imm(&img, 0, 0, 26, 26).to_image();
+        let ceiled = downsample_with_phase(&narrow, 8, 0, 0, AlphaResolution::CenterAlpha, DimensionRounding::Ceil);
+        assert_eq!(ceiled.dimensions(), (4, 4));
+        assert_eq!(*ceiled.get_pixel(3, 3), *img.get_pixel(25, 25));
+    }
+
+    #[test]
+    fn test_predict_output_size_matches_downscale() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+
+        let padded = DownscalerSettings { pad_canvas: true, canvas_multiple: 16, ..Default::default() };
+        for (i, (img, settings)) in [
+            (synthetic_upscaled(16, 8, 1), DownscalerSettings::default()),
+            (synthetic_upscaled(12, 10, 2), DownscalerSettings::default()),
+            (synthetic_upscaled(12, 10, 3), padded.clone()),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let input = root.join(format!("{}.png", i));
+            img.save(&input).unwrap();
+            let result = downscale_image(input, root.join(format!("{}_out.png", i)), settings.clone()).unwrap();
+            assert_eq!(predict_output_size(&img, &settings, None), result.final_size);
+        }
+        assert_eq!(predict_output_size(&synthetic_upscaled(12, 10, 3), &padded, None), (16, 16));
+    }
+
+    #[test]
+    fn test_min_output_dimension_caps_scale() {
+        // Tiny noisy art where detection overshoots to an 8px result
+        let img = synthetic_upscaled(16, 5, 5);
+        assert_eq!(predict_output_size(&img, &DownscalerSettings::default(), None), (8, 8));
+
+        let settings = DownscalerSettings { min_output_dimension: Some(16), ..Default::default() };
+        let (width, height) = predict_output_size(&img, &settings, None);
+        assert!(width >= 16 && height >= 16, "{}x{}", width, height);
+
+        // A floor larger than the content disables downscaling altogether
+        let settings = DownscalerSettings { min_output_dimension: Some(100), ..Default::default() };
+        assert_eq!(predict_output_size(&img, &settings, None), (80, 80));
+    }
+
+    #[test]
+    fn test_scale_hint_overrides_detection() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+
+        // 5x is below the smallest scale detection tries
+        let img = synthetic_upscaled(16, 5, 5);
+        let png = crate::processor::encode_png(&img).unwrap();
+        let input = root.join("tagged.png");
+        std::fs::write(&input, crate::png_text::write_text(&png, DEFAULT_SCALE_HINT_KEY, "5").unwrap()).unwrap();
+        assert_eq!(read_scale_hint(&input, DEFAULT_SCALE_HINT_KEY), Some(5.0));
+
+        let output = root.join("out.png");
+        let hinted = DownscalerSettings { scale_hint_key: Some(DEFAULT_SCALE_HINT_KEY.to_string()), ..Default::default() };
+        let result = downscale_image(input.clone(), output.clone(), hinted.clone()).unwrap();
+        assert_eq!(result.scale_factor, 5.0);
+        assert_eq!(predict_output_size(&img, &hinted, Some(5.0)), result.final_size);
+
+        // The output records the removed scale, but not as an upscale hint
+        assert_eq!(read_scale_hint(&output, DOWNSCALED_BY_KEY), Some(5.0));
+        assert_eq!(read_scale_hint(&output, DEFAULT_SCALE_HINT_KEY), None);
+
+        // Hints are off by default
+        assert_ne!(downscale_image(input, output, DownscalerSettings::default()).unwrap().scale_factor, 5.0);
+    }
+
+    #[test]
+    fn test_opaque_input_policies() {
+        // A diamond on a solid, fully opaque backdrop
+        let img: RgbaImage = ImageBuffer::from_fn(24, 24, |x, y| {
+            if x.abs_diff(12) + y.abs_diff(12) < 8 {
+                Rgba([200, 40, 40, 255])
+            } else {
+                Rgba([30, 90, 160, 255])
+            }
+        });
+        let plan = |on_opaque_input| {
+            let settings = DownscalerSettings { auto_trim: false, on_opaque_input, ..Default::default() };
+            plan_downscale(img.clone(), &settings, None)
+        };
+
+        let removed = plan(OpaqueInput::AutoRemove);
+        assert_eq!(removed.rgba.get_pixel(0, 0)[3], 0);
+        assert_eq!(removed.rgba.get_pixel(12, 12)[3], 255);
+
+        let untouched = plan(OpaqueInput::Proceed);
+        assert_eq!(untouched.rgba, img);
+        assert!(untouched.warnings.iter().all(|w| !w.contains("transparency")));
+
+        let warned = plan(OpaqueInput::Warn);
+        assert_eq!(warned.rgba, img);
+        assert!(warned.warnings.iter().any(|w| w.contains("no transparency")));
+    }
+
+    #[test]
+    fn test_fft_window_resists_trend() {
+        // Period-10 ripple on a steep ramp, like an edge profile across a
+        // gradient; the ramp's leakage swamps the ripple's bin unwindowed
+        let signal: Vec<f32> = (0..200)
+            .map(|i| i as f32 * 0.5 + (i as f32 * std::f32::consts::PI / 5.0).sin())
+            .collect();
+
+        let error = |window| (fft_detect_period(&signal, 6.0, 20.0, window).unwrap() - 10.0).abs();
+        for window in [FftWindow::Hann, FftWindow::Hamming] {
+            assert!(error(window) < 0.5, "{:?} missed the period", window);
+            assert!(error(window) < error(FftWindow::None));
+        }
+    }
+
+    #[test]
+    fn test_fit_to_dimensions_letterboxes() {
+        // 2:1 source, left half red and right half blue
+        let img: RgbaImage = ImageBuffer::from_fn(40, 20, |x, _| {
+            if x < 20 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) }
+        });
+
+        let fitted = fit_to_dimensions(&img, 10, 10);
+        assert_eq!(fitted.dimensions(), (10, 10));
+        // Content is 10x5, centered with 2-3 transparent rows above and below
+        let bounds = content_bounds(&fitted).unwrap();
+        assert_eq!((bounds.width, bounds.height), (10, 5));
+        assert_eq!(bounds.min_y, 2);
+        assert_eq!(fitted.get_pixel(5, 0)[3], 0);
+        assert_eq!(fitted.get_pixel(5, 9)[3], 0);
+        assert_eq!(*fitted.get_pixel(4, 4), Rgba([255, 0, 0, 255]));
+        assert_eq!(*fitted.get_pixel(5, 4), Rgba([0, 0, 255, 255]));
+
+        // Stretching fills the box instead
+        let settings = ManualDownscaleSettings { target_width: 10, target_height: 10, auto_trim: false, preserve_aspect: false };
+        let stretched = downscale_manual_preview(&img, &settings);
+        assert!(stretched.pixels().all(|p| p[3] == 255));
+    }
+
+    #[test]
+    fn test_downscaling_an_output_again_keeps_it() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+        let input = root.join("in.png");
+        synthetic_upscaled(16, 8, 3).save(&input).unwrap();
+
+        for settings in [
+            DownscalerSettings::default(),
+            DownscalerSettings { scale_hint_key: Some(DEFAULT_SCALE_HINT_KEY.to_string()), ..Default::default() },
+        ] {
+            let once = root.join("once.png");
+            let twice = root.join("twice.png");
+            let first = downscale_image(input.clone(), once.clone(), settings.clone()).unwrap();
+            assert_eq!(first.scale_factor, 8.0);
+
+            // The tagged output is native-size art: nothing left to remove
+            let second = downscale_image(once, twice, settings).unwrap();
+            assert_eq!(second.scale_factor, 1.0);
+            assert_eq!(second.final_size, first.final_size);
+        }
+    }
 }
@@ -8,6 +8,7 @@ pub enum PixelsError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
+    #[cfg(feature = "app")]
     #[error("Database error: {0}")]
     Database(#[from] rusqlite::Error),
 
@@ -19,6 +20,15 @@ pub enum PixelsError {
 
     #[error("Processing error: {0}")]
     Processing(String),
+
+    #[error("Unsupported format: {0}")]
+    UnsupportedFormat(String),
+
+    #[error("Decode error: {0}")]
+    Decode(String),
+
+    #[error("Read-only: {0}")]
+    ReadOnly(String),
 }
 
 pub type Result<T> = std::result::Result<T, PixelsError>;
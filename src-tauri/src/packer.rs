@@ -1,6 +1,6 @@
 use image::{DynamicImage, RgbaImage, Rgba, GenericImageView};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use crate::error::{Result, PixelsError};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,6 +12,61 @@ pub struct PackerSettings {
     pub background_color: (u8, u8, u8, u8),
     pub sort_order: SortOrder,
     pub export_metadata: bool,
+    /// Origin for metadata coordinates (default: top-left)
+    #[serde(default)]
+    pub coordinate_origin: Origin,
+    /// Largest width or height accepted for a single sprite (default: 4096)
+    #[serde(default = "default_max_sprite_dimension")]
+    pub max_sprite_dimension: u32,
+    /// Template for metadata keys, e.g. `"{name}_{n:4}"` (see `rename_sprite`)
+    #[serde(default)]
+    pub rename_pattern: Option<String>,
+    /// Indent the metadata JSON; compact output is much smaller for large
+    /// atlases (default: true)
+    #[serde(default = "default_metadata_pretty")]
+    pub metadata_pretty: bool,
+    /// Alpha-bleed passes run over the finished sheet so transparent padding
+    /// carries its neighbors' color (default: 0 - off)
+    #[serde(default)]
+    pub bleed_iterations: u32,
+    /// Animation clips to group packed frames into (default: none)
+    #[serde(default)]
+    pub clips: Vec<Clip>,
+    /// Place every sprite, trimmed and centered, in its own (width, height)
+    /// cell of a regular grid instead of packing freely. Cells are spaced by
+    /// `item_padding`/`row_padding` and as many columns as fit `max_width`
+    /// are used (default: None - free-form rows)
+    #[serde(default)]
+    pub uniform_grid: Option<(u32, u32)>,
+}
+
+fn default_max_sprite_dimension() -> u32 {
+    4096
+}
+
+fn default_metadata_pretty() -> bool {
+    true
+}
+
+/// Bumped whenever the metadata JSON layout changes incompatibly
+pub const METADATA_SCHEMA_VERSION: u32 = 1;
+
+/// Generator string embedded in the metadata JSON
+pub const METADATA_GENERATOR: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+/// Coordinate system used for `SpriteMetadata` positions
+///
+/// Only the reported coordinates change: the sheet's pixels are always laid
+/// out with row 0 at the top of the image file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    /// `y` measured down from the top edge to the sprite's top edge
+    #[default]
+    TopLeft,
+    /// `y` measured up from the bottom edge to the sprite's bottom edge
+    /// (OpenGL-style), i.e. `sheet_height - top - h`
+    BottomLeft,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,10 +88,37 @@ impl Default for PackerSettings {
             background_color: (0, 0, 0, 0),
             sort_order: SortOrder::Height,
             export_metadata: true,
+            coordinate_origin: Origin::TopLeft,
+            max_sprite_dimension: default_max_sprite_dimension(),
+            rename_pattern: None,
+            metadata_pretty: default_metadata_pretty(),
+            bleed_iterations: 0,
+            clips: Vec::new(),
+            uniform_grid: None,
         }
     }
 }
 
+/// An animation clip made of the packed frames whose metadata keys match
+/// `frame_pattern` (`*` matches any run of characters, `?` any one)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Clip {
+    pub name: String,
+    pub frame_pattern: String,
+    pub fps: f32,
+}
+
+/// A clip as written to the metadata
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipMetadata {
+    pub name: String,
+    /// Item keys in playback order
+    pub frames: Vec<String>,
+    pub fps: f32,
+    /// Time each frame is shown, in milliseconds
+    pub frame_duration_ms: u32,
+}
+
 #[derive(Debug, Clone)]
 struct SpriteItem {
     name: String,
@@ -45,20 +127,145 @@ struct SpriteItem {
     height: u32,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpriteMetadata {
     pub x: u32,
     pub y: u32,
     pub w: u32,
     pub h: u32,
+    /// Grid row, for sheets packed with `uniform_grid`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row: Option<u32>,
+    /// Grid column, for sheets packed with `uniform_grid`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub col: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PackerResult {
+    /// Metadata layout version (0 for files written before it was recorded)
+    #[serde(default)]
+    pub schema_version: u32,
+    /// Tool and version that wrote the metadata
+    #[serde(default)]
+    pub generator: String,
     pub sprite_sheet: String, // Path to output
     pub width: u32,
     pub height: u32,
     pub items: std::collections::HashMap<String, SpriteMetadata>,
+    /// Coordinate system the item positions are expressed in
+    #[serde(default)]
+    pub coordinate_origin: Origin,
+    /// Original name -> metadata key, for sprites renamed by `rename_pattern`
+    #[serde(default, skip_serializing_if = "std::collections::HashMap::is_empty")]
+    pub renamed: std::collections::HashMap<String, String>,
+    /// Animation clips built from `PackerSettings::clips`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clips: Vec<ClipMetadata>,
+    /// Sheet pixels covered by sprite regions
+    #[serde(default)]
+    pub used_pixels: u64,
+    /// width * height
+    #[serde(default)]
+    pub total_pixels: u64,
+    /// Largest empty rectangle on the sheet, in `coordinate_origin`
+    /// coordinates (None if the sheet is full)
+    #[serde(default)]
+    pub largest_free_rect: Option<SpriteMetadata>,
+    /// How scattered the free space is: 0 when it is one rectangle, towards
+    /// 1 as it splits into many small gaps (`1 - largest_free / total_free`)
+    #[serde(default)]
+    pub fragmentation: f32,
+}
+
+/// Split a sprite name's file stem into its base and trailing frame digits
+fn split_frame_number(name: &str) -> (&str, &str) {
+    let stem = Path::new(name)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(name);
+    let digits_start = stem.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    stem.split_at(digits_start)
+}
+
+/// Derive a clean metadata key from a sprite name using a template
+///
+/// The name's file stem is split into a base and a trailing frame number
+/// (`Walk_1` -> `walk`, `1`). The base is lowercased with runs of spaces,
+/// `-` and `_` collapsed to a single `_`. Placeholders in `pattern`:
+/// - `{name}`: the normalized base
+/// - `{n}`: the frame number without leading zeros (empty if there is none)
+/// - `{n:W}`: the frame number zero-padded to `W` digits
+pub fn rename_sprite(name: &str, pattern: &str) -> Result<String> {
+    let (base, digits) = split_frame_number(name);
+
+    let mut normalized = String::new();
+    for word in base.split([' ', '-', '_']).filter(|w| !w.is_empty()) {
+        if !normalized.is_empty() {
+            normalized.push('_');
+        }
+        normalized.push_str(&word.to_lowercase());
+    }
+    let number = digits.trim_start_matches('0');
+    let number = if number.is_empty() && !digits.is_empty() { "0" } else { number };
+
+    let mut out = String::new();
+    let mut rest = pattern;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let close = rest[open..].find('}').map(|i| open + i).ok_or_else(|| {
+            PixelsError::InvalidParameter(format!("Unclosed placeholder in rename pattern '{}'", pattern))
+        })?;
+        match &rest[open + 1..close] {
+            "name" => out.push_str(&normalized),
+            "n" => out.push_str(number),
+            spec => {
+                let width = spec
+                    .strip_prefix("n:")
+                    .and_then(|w| w.parse::<usize>().ok())
+                    .ok_or_else(|| {
+                        PixelsError::InvalidParameter(format!("Unknown placeholder '{{{}}}' in rename pattern", spec))
+                    })?;
+                out.push_str(&format!("{:0>width$}", number, width = width));
+            }
+        }
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Apply `rename_sprite` to every name, returning (original, renamed) pairs
+///
+/// Fails if two names produce the same key.
+pub fn rename_sprites(names: &[String], pattern: &str) -> Result<Vec<(String, String)>> {
+    let mut seen: std::collections::HashMap<String, &String> = std::collections::HashMap::new();
+    let mut mapping = Vec::with_capacity(names.len());
+    for name in names {
+        let renamed = rename_sprite(name, pattern)?;
+        if let Some(other) = seen.insert(renamed.clone(), name) {
+            return Err(PixelsError::InvalidParameter(format!(
+                "'{}' and '{}' both rename to '{}'",
+                other, name, renamed
+            )));
+        }
+        mapping.push((name.clone(), renamed));
+    }
+    Ok(mapping)
+}
+
+/// Name each path by its file stem
+fn named_by_stem(input_paths: Vec<PathBuf>) -> Vec<(String, PathBuf)> {
+    input_paths
+        .into_iter()
+        .map(|path| {
+            let name = path.file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("unknown")
+                .to_string();
+            (name, path)
+        })
+        .collect()
 }
 
 pub fn pack_sprites(
@@ -66,21 +273,218 @@ pub fn pack_sprites(
     output_path: PathBuf,
     settings: PackerSettings,
 ) -> Result<PackerResult> {
-    if input_paths.is_empty() {
+    pack_named_sprites(named_by_stem(input_paths), output_path, settings)
+}
+
+/// Largest side of each thumbnail in a pack preview
+pub const PREVIEW_THUMBNAIL_SIZE: u32 = 32;
+
+/// Transparent gap between thumbnails in a pack preview
+const PREVIEW_GAP: u32 = 2;
+
+/// A thumbnail strip of sprites in the order they would be packed
+#[derive(Debug, Clone, Serialize)]
+pub struct PackPreview {
+    /// PNG bytes of the strip
+    pub png: Vec<u8>,
+    /// Metadata keys, left to right
+    pub names: Vec<String>,
+}
+
+/// Lay out small thumbnails of the inputs in pack order, without building
+/// the atlas
+///
+/// Names, renaming and sorting follow `pack_sprites`; each sprite is shrunk
+/// to `PREVIEW_THUMBNAIL_SIZE` and placed left to right in a single row.
+pub fn pack_preview(input_paths: Vec<PathBuf>, settings: &PackerSettings) -> Result<PackPreview> {
+    let (sprites, _) = load_sprites(named_by_stem(input_paths), settings)?;
+
+    let thumbs: Vec<RgbaImage> = sprites
+        .iter()
+        .map(|sprite| crate::processor::make_thumbnail(&sprite.image.to_rgba8(), PREVIEW_THUMBNAIL_SIZE))
+        .collect();
+    let width = thumbs.iter().map(|t| t.width() + PREVIEW_GAP).sum::<u32>() + PREVIEW_GAP;
+    let height = thumbs.iter().map(|t| t.height()).max().unwrap_or(0) + 2 * PREVIEW_GAP;
+
+    let mut strip = RgbaImage::new(width, height);
+    let mut x = PREVIEW_GAP;
+    for thumb in &thumbs {
+        image::imageops::overlay(&mut strip, thumb, x as i64, PREVIEW_GAP as i64);
+        x += thumb.width() + PREVIEW_GAP;
+    }
+
+    Ok(PackPreview {
+        png: crate::processor::encode_png(&strip)?,
+        names: sprites.into_iter().map(|sprite| sprite.name).collect(),
+    })
+}
+
+/// Pack every source in a workspace using its current version
+///
+/// Each source contributes its current version's cached image (or the
+/// original if it has none), named in the metadata by its relative path.
+/// `output_path` is resolved against the workspace root and must stay
+/// inside it.
+pub fn pack_workspace(
+    workspace_root: &Path,
+    output_path: &Path,
+    settings: PackerSettings,
+) -> Result<PackerResult> {
+    let manager = crate::state::WorkspaceManager::open_readonly(workspace_root)?;
+    let mut relative_paths: Vec<&String> = manager.source_paths();
+    relative_paths.sort();
+
+    let output = workspace_root.join(crate::state::normalize_relative(&output_path.to_string_lossy())?);
+    let inputs = relative_paths
+        .into_iter()
+        .map(|rel| Ok((rel.clone(), manager.current_image_path(rel)?)))
+        .collect::<Result<_>>()?;
+
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    pack_named_sprites(inputs, output, settings)
+}
+
+/// Read every input's header before decoding anything
+///
+/// Collects unreadable files and sprites larger than `max_dimension` on
+/// either side, and reports them all in one error.
+fn check_inputs(inputs: &[(String, PathBuf)], max_dimension: u32) -> Result<()> {
+    let mut problems = Vec::new();
+
+    for (_, path) in inputs {
+        let dimensions = image::ImageReader::open(path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(image::ImageError::from)
+            .and_then(|reader| reader.into_dimensions());
+        match dimensions {
+            Ok((w, h)) if w > max_dimension || h > max_dimension => problems.push(format!(
+                "{} is {}x{} (max {})",
+                path.display(), w, h, max_dimension
+            )),
+            Ok(_) => {}
+            Err(e) => problems.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(PixelsError::InvalidParameter(format!(
+            "Cannot pack {} input(s): {}",
+            problems.len(),
+            problems.join("; ")
+        )))
+    }
+}
+
+/// Whether `name` matches a glob `pattern` with `*` and `?` wildcards
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Group item keys into clips, ordering each clip's frames by their trailing
+/// frame number (then by key)
+///
+/// Fails if a clip matches no frames or has a non-positive fps.
+pub fn build_clips<'a>(clips: &[Clip], keys: impl IntoIterator<Item = &'a String> + Clone) -> Result<Vec<ClipMetadata>> {
+    clips
+        .iter()
+        .map(|clip| {
+            if clip.fps.is_nan() || clip.fps <= 0.0 {
+                return Err(PixelsError::InvalidParameter(format!(
+                    "Clip '{}' needs a positive fps, got {}",
+                    clip.name, clip.fps
+                )));
+            }
+            let mut frames: Vec<String> = keys
+                .clone()
+                .into_iter()
+                .filter(|key| glob_match(&clip.frame_pattern, key))
+                .cloned()
+                .collect();
+            if frames.is_empty() {
+                return Err(PixelsError::InvalidParameter(format!(
+                    "Clip '{}' pattern '{}' matches no packed frames",
+                    clip.name, clip.frame_pattern
+                )));
+            }
+            frames.sort_by_cached_key(|key| (split_frame_number(key).1.parse::<u64>().ok(), key.clone()));
+
+            Ok(ClipMetadata {
+                name: clip.name.clone(),
+                frames,
+                fps: clip.fps,
+                frame_duration_ms: (1000.0 / clip.fps).round() as u32,
+            })
+        })
+        .collect()
+}
+
+/// Check, decode, rename and sort the inputs for packing
+///
+/// Returns the sprites in pack order and the original name -> key mapping
+/// for renamed sprites.
+fn load_sprites(
+    inputs: Vec<(String, PathBuf)>,
+    settings: &PackerSettings,
+) -> Result<(Vec<SpriteItem>, std::collections::HashMap<String, String>)> {
+    if inputs.is_empty() {
         return Err(PixelsError::InvalidParameter("No input files provided".to_string()));
     }
 
+    check_inputs(&inputs, settings.max_sprite_dimension)?;
+
+    let mut renamed = std::collections::HashMap::new();
+    let inputs = match &settings.rename_pattern {
+        Some(pattern) => {
+            let names: Vec<String> = inputs.iter().map(|(name, _)| name.clone()).collect();
+            let mapping = rename_sprites(&names, pattern)?;
+            inputs
+                .into_iter()
+                .zip(mapping)
+                .map(|((_, path), (original, key))| {
+                    renamed.insert(original, key.clone());
+                    (key, path)
+                })
+                .collect()
+        }
+        None => inputs,
+    };
+
     // Load all sprites
     let mut sprites: Vec<SpriteItem> = Vec::new();
-    for path in &input_paths {
-        let img = image::open(path)
-            .map_err(|e| PixelsError::Processing(format!("Failed to load {}: {}", path.display(), e)))?;
+    for (name, path) in inputs {
+        let img = crate::processor::decode_image(&path)?;
 
         let (width, height) = img.dimensions();
-        let name = path.file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("unknown")
-            .to_string();
 
         sprites.push(SpriteItem {
             name,
@@ -98,6 +502,50 @@ pub fn pack_sprites(
         SortOrder::None => {}
     }
 
+    Ok((sprites, renamed))
+}
+
+/// Pack sprites given as (metadata name, image path) pairs
+fn pack_named_sprites(
+    inputs: Vec<(String, PathBuf)>,
+    output_path: PathBuf,
+    settings: PackerSettings,
+) -> Result<PackerResult> {
+    let (sprites, renamed) = load_sprites(inputs, &settings)?;
+
+    let (mut sheet, metadata_items) = match settings.uniform_grid {
+        Some(cell) => layout_uniform_grid(&sprites, cell, &settings)?,
+        None => layout_rows(&sprites, &settings),
+    };
+    let (sheet_width, sheet_height) = sheet.dimensions();
+
+    let clips = build_clips(&settings.clips, metadata_items.keys())?;
+
+    if settings.bleed_iterations > 0 {
+        crate::processor::bleed_edges(&mut sheet, settings.bleed_iterations);
+    }
+
+    let result = PackerResult {
+        schema_version: METADATA_SCHEMA_VERSION,
+        generator: METADATA_GENERATOR.to_string(),
+        sprite_sheet: String::new(),
+        width: sheet_width,
+        height: sheet_height,
+        items: metadata_items,
+        coordinate_origin: settings.coordinate_origin,
+        renamed,
+        clips,
+        used_pixels: 0,
+        total_pixels: 0,
+        largest_free_rect: None,
+        fragmentation: 0.0,
+    };
+    let result = measure_packing(result);
+    save_pack(&sheet, &output_path, &output_path.with_extension("json"), &settings, result)
+}
+
+/// Lay sprites out left to right in rows, wrapping at `max_width`
+fn layout_rows(sprites: &[SpriteItem], settings: &PackerSettings) -> (RgbaImage, std::collections::HashMap<String, SpriteMetadata>) {
     // Layout algorithm (greedy bin packing)
     let mut positions: Vec<(u32, u32)> = Vec::new();
     let mut current_x = settings.border_padding;
@@ -105,7 +553,7 @@ pub fn pack_sprites(
     let mut row_height = 0u32;
     let max_width = settings.max_width;
 
-    for sprite in &sprites {
+    for sprite in sprites {
         let sprite_width = sprite.width + settings.item_padding;
         let sprite_height = sprite.height + settings.item_padding;
 
@@ -143,43 +591,433 @@ pub fn pack_sprites(
         let rgba = sprite.image.to_rgba8();
         image::imageops::overlay(&mut sheet, &rgba, *x as i64, *y as i64);
 
+        let meta_y = match settings.coordinate_origin {
+            Origin::TopLeft => *y,
+            Origin::BottomLeft => sheet_height - *y - sprite.height,
+        };
         metadata_items.insert(
             sprite.name.clone(),
             SpriteMetadata {
                 x: *x,
-                y: *y,
+                y: meta_y,
                 w: sprite.width,
                 h: sprite.height,
+                row: None,
+                col: None,
             },
         );
     }
 
+    (sheet, metadata_items)
+}
+
+/// Lay sprites out one per `cell` in a regular grid, in pack order
+///
+/// Each sprite is trimmed to its visible pixels and centered in its cell;
+/// the metadata records the whole cell and its row/column. Fails, naming
+/// every offender, if a trimmed sprite is larger than the cell.
+fn layout_uniform_grid(
+    sprites: &[SpriteItem],
+    cell: (u32, u32),
+    settings: &PackerSettings,
+) -> Result<(RgbaImage, std::collections::HashMap<String, SpriteMetadata>)> {
+    let (cell_w, cell_h) = cell;
+    if cell_w == 0 || cell_h == 0 {
+        return Err(PixelsError::InvalidParameter(format!(
+            "Grid cells must be at least 1x1, got {}x{}",
+            cell_w, cell_h
+        )));
+    }
+
+    let trimmed: Vec<RgbaImage> = sprites
+        .iter()
+        .map(|sprite| crate::downscaler::auto_trim_image(&sprite.image.to_rgba8()))
+        .collect();
+    let too_big: Vec<String> = sprites
+        .iter()
+        .zip(&trimmed)
+        .filter(|(_, img)| img.pixels().any(|p| p[3] > 0) && (img.width() > cell_w || img.height() > cell_h))
+        .map(|(sprite, img)| format!("{} ({}x{})", sprite.name, img.width(), img.height()))
+        .collect();
+    if !too_big.is_empty() {
+        return Err(PixelsError::InvalidParameter(format!(
+            "Larger than the {}x{} grid cell: {}",
+            cell_w,
+            cell_h,
+            too_big.join(", ")
+        )));
+    }
+
+    let border = settings.border_padding;
+    let fit = (settings.max_width.saturating_sub(2 * border) + settings.item_padding) / (cell_w + settings.item_padding);
+    let cols = fit.clamp(1, sprites.len().max(1) as u32);
+    let rows = (sprites.len() as u32).div_ceil(cols);
+    let sheet_width = 2 * border + cols * cell_w + (cols - 1) * settings.item_padding;
+    let sheet_height = 2 * border + rows * cell_h + rows.saturating_sub(1) * settings.row_padding;
+
+    let background = settings.background_color;
+    let mut sheet = RgbaImage::from_pixel(
+        sheet_width,
+        sheet_height,
+        Rgba([background.0, background.1, background.2, background.3]),
+    );
+
+    let mut metadata_items = std::collections::HashMap::new();
+    for (i, (sprite, img)) in sprites.iter().zip(&trimmed).enumerate() {
+        let (row, col) = (i as u32 / cols, i as u32 % cols);
+        let x = border + col * (cell_w + settings.item_padding);
+        let y = border + row * (cell_h + settings.row_padding);
+        if img.pixels().any(|p| p[3] > 0) {
+            let (dx, dy) = ((cell_w - img.width()) / 2, (cell_h - img.height()) / 2);
+            image::imageops::overlay(&mut sheet, img, (x + dx) as i64, (y + dy) as i64);
+        }
+
+        let meta_y = match settings.coordinate_origin {
+            Origin::TopLeft => y,
+            Origin::BottomLeft => sheet_height - y - cell_h,
+        };
+        metadata_items.insert(
+            sprite.name.clone(),
+            SpriteMetadata { x, y: meta_y, w: cell_w, h: cell_h, row: Some(row), col: Some(col) },
+        );
+    }
+
+    Ok((sheet, metadata_items))
+}
+
+/// Save the sheet, and its metadata JSON if requested
+///
+/// Returns `result` with `sprite_sheet` set to the full output path; the
+/// JSON records just the file name.
+fn save_pack(
+    sheet: &RgbaImage,
+    output_path: &Path,
+    metadata_path: &Path,
+    settings: &PackerSettings,
+    mut result: PackerResult,
+) -> Result<PackerResult> {
     // Save sprite sheet
-    sheet.save(&output_path)?;
+    sheet.save(output_path)?;
 
     // Save metadata if requested
     if settings.export_metadata {
-        let metadata_path = output_path.with_extension("json");
-        let result = PackerResult {
-            sprite_sheet: output_path.file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or("spritesheet.png")
-                .to_string(),
-            width: sheet_width,
-            height: sheet_height,
-            items: metadata_items.clone(),
-        };
+        result.sprite_sheet = output_path.file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("spritesheet.png")
+            .to_string();
 
-        let json = serde_json::to_string_pretty(&result)?;
+        let json = if settings.metadata_pretty {
+            serde_json::to_string_pretty(&result)?
+        } else {
+            serde_json::to_string(&result)?
+        };
         std::fs::write(metadata_path, json)?;
     }
 
-    Ok(PackerResult {
-        sprite_sheet: output_path.to_string_lossy().to_string(),
-        width: sheet_width,
-        height: sheet_height,
-        items: metadata_items,
-    })
+    result.sprite_sheet = output_path.to_string_lossy().to_string();
+    Ok(result)
+}
+
+/// A placed region in top-left coordinates: (x, y, w, h)
+type Rect = (u32, u32, u32, u32);
+
+/// Top-left-most free position for a `w` x `h` sprite among `occupied`
+///
+/// Tries the sheet's corner and the spots just right of and just below each
+/// region, keeping the larger of `item_padding`/`row_padding` clear around
+/// them. A spot
+/// below everything at the left border always exists, so this never fails;
+/// the caller grows the sheet when the spot runs past its edges.
+fn find_free_spot(occupied: &[Rect], w: u32, h: u32, sheet_width: u32, settings: &PackerSettings) -> (u32, u32) {
+    let border = settings.border_padding;
+    let pad = settings.item_padding.max(settings.row_padding);
+    let below_all = (border, occupied.iter().map(|r| r.1 + r.3 + pad).max().unwrap_or(border));
+
+    let mut candidates = vec![(border, border), below_all];
+    for &(x, y, rw, rh) in occupied {
+        candidates.push((x + rw + pad, y));
+        candidates.push((x, y + rh + pad));
+    }
+
+    let free = |&(x, y): &(u32, u32)| {
+        (x == border || x + w + border <= sheet_width)
+            && occupied.iter().all(|&(ox, oy, ow, oh)| {
+                x >= ox + ow + pad || ox >= x + w + pad || y >= oy + oh + pad || oy >= y + h + pad
+            })
+    };
+    candidates
+        .into_iter()
+        .filter(free)
+        .min_by_key(|&(x, y)| (y, x))
+        .unwrap_or(below_all)
+}
+
+/// Pack `new_sprites` into the free space of an existing sheet, leaving
+/// every existing region where it is
+///
+/// The sheet grows down (or right, for a sprite wider than it) when the new
+/// sprites don't fit. Naming, renaming and sort order follow `pack_sprites`;
+/// a new sprite whose key is already in the sheet is an error. The sheet and
+/// metadata are rewritten in place, keeping the metadata's coordinate origin.
+/// Clips are rebuilt when `settings.clips` is set, otherwise kept.
+pub fn append_to_sheet(
+    existing_sheet: &Path,
+    existing_metadata: &Path,
+    new_sprites: Vec<PathBuf>,
+    settings: PackerSettings,
+) -> Result<PackerResult> {
+    if settings.uniform_grid.is_some() {
+        return Err(PixelsError::InvalidParameter(
+            "Appending doesn't support uniform_grid; repack the sheet instead".to_string(),
+        ));
+    }
+    let old_sheet = crate::processor::load_image(existing_sheet)?;
+    let mut result: PackerResult = serde_json::from_str(&std::fs::read_to_string(existing_metadata)?)?;
+    let (old_w, old_h) = old_sheet.dimensions();
+
+    let (sprites, renamed) = load_sprites(named_by_stem(new_sprites), &settings)?;
+    let clashes: Vec<&str> = sprites
+        .iter()
+        .filter(|sprite| result.items.contains_key(&sprite.name))
+        .map(|sprite| sprite.name.as_str())
+        .collect();
+    if !clashes.is_empty() {
+        return Err(PixelsError::InvalidParameter(format!(
+            "Already in the sheet: {}",
+            clashes.join(", ")
+        )));
+    }
+
+    // The metadata was read from disk; every region must fit the old sheet
+    // before any coordinate arithmetic is done on it
+    let mut outside: Vec<&str> = result
+        .items
+        .iter()
+        .filter(|(_, m)| m.x.checked_add(m.w).is_none_or(|r| r > old_w) || m.y.checked_add(m.h).is_none_or(|b| b > old_h))
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if !outside.is_empty() {
+        outside.sort_unstable();
+        return Err(PixelsError::InvalidParameter(format!(
+            "Metadata regions outside the {}x{} sheet: {}",
+            old_w, old_h, outside.join(", ")
+        )));
+    }
+
+    // Existing regions in top-left coordinates
+    let mut occupied: Vec<Rect> = result
+        .items
+        .values()
+        .map(|m| {
+            let y = match result.coordinate_origin {
+                Origin::TopLeft => m.y,
+                Origin::BottomLeft => old_h - m.y - m.h,
+            };
+            (m.x, y, m.w, m.h)
+        })
+        .collect();
+    let mut existing: Vec<(String, Rect)> = result.items.keys().cloned().zip(occupied.iter().copied()).collect();
+
+    let (mut sheet_width, mut sheet_height) = (old_w, old_h);
+    let mut placed = Vec::with_capacity(sprites.len());
+    for sprite in &sprites {
+        let (x, y) = find_free_spot(&occupied, sprite.width, sprite.height, sheet_width, &settings);
+        sheet_width = sheet_width.max(x + sprite.width + settings.border_padding);
+        sheet_height = sheet_height.max(y + sprite.height + settings.item_padding + settings.border_padding);
+        occupied.push((x, y, sprite.width, sprite.height));
+        placed.push((x, y));
+    }
+
+    let background = settings.background_color;
+    let mut sheet = RgbaImage::from_pixel(
+        sheet_width,
+        sheet_height,
+        Rgba([background.0, background.1, background.2, background.3]),
+    );
+    image::imageops::replace(&mut sheet, &old_sheet, 0, 0);
+    for (sprite, &(x, y)) in sprites.iter().zip(&placed) {
+        image::imageops::overlay(&mut sheet, &sprite.image.to_rgba8(), x as i64, y as i64);
+    }
+
+    // Report every region in the original origin against the new height
+    let to_meta = |(x, y, w, h): Rect| {
+        let y = match result.coordinate_origin {
+            Origin::TopLeft => y,
+            Origin::BottomLeft => sheet_height - y - h,
+        };
+        SpriteMetadata { x, y, w, h, row: None, col: None }
+    };
+    existing.extend(sprites.iter().zip(&placed).map(|(sprite, &(x, y))| {
+        (sprite.name.clone(), (x, y, sprite.width, sprite.height))
+    }));
+    let items = existing
+        .into_iter()
+        .map(|(name, rect)| {
+            // Existing sprites keep the grid cell they were given
+            let (row, col) = result.items.get(&name).map_or((None, None), |m| (m.row, m.col));
+            (name, SpriteMetadata { row, col, ..to_meta(rect) })
+        })
+        .collect();
+    result.items = items;
+    result.renamed.extend(renamed);
+    if !settings.clips.is_empty() {
+        result.clips = build_clips(&settings.clips, result.items.keys())?;
+    }
+    result.schema_version = METADATA_SCHEMA_VERSION;
+    result.generator = METADATA_GENERATOR.to_string();
+    result.width = sheet_width;
+    result.height = sheet_height;
+
+    if settings.bleed_iterations > 0 {
+        crate::processor::bleed_edges(&mut sheet, settings.bleed_iterations);
+    }
+
+    save_pack(&sheet, existing_sheet, existing_metadata, &settings, measure_packing(result))
+}
+
+/// Largest all-false rectangle in a row-major `width` x `height` grid, as
+/// (x, y, w, h) in top-left coordinates
+fn largest_empty_rect(covered: &[bool], width: usize, height: usize) -> Option<Rect> {
+    let mut heights = vec![0usize; width];
+    let mut best: Option<(usize, Rect)> = None;
+
+    for y in 0..height {
+        for x in 0..width {
+            heights[x] = if covered[y * width + x] { 0 } else { heights[x] + 1 };
+        }
+        // Largest rectangle under this row's histogram of empty run lengths
+        let mut stack: Vec<usize> = Vec::new();
+        for x in 0..=width {
+            let h = if x < width { heights[x] } else { 0 };
+            while let Some(&top) = stack.last() {
+                if heights[top] < h {
+                    break;
+                }
+                stack.pop();
+                let left = stack.last().map_or(0, |&l| l + 1);
+                let (w, rh) = (x - left, heights[top]);
+                if w * rh > best.map_or(0, |(area, _)| area) {
+                    let rect = (left as u32, (y + 1 - rh) as u32, w as u32, rh as u32);
+                    best = Some((w * rh, rect));
+                }
+            }
+            stack.push(x);
+        }
+    }
+    best.map(|(_, rect)| rect)
+}
+
+/// Fill in the sheet usage fields of `result` from its items
+///
+/// Padding counts as free space.
+pub fn measure_packing(mut result: PackerResult) -> PackerResult {
+    let (width, height) = (result.width as usize, result.height as usize);
+    let mut covered = vec![false; width * height];
+    // Items may come from metadata on disk, so edges are summed in u64 and
+    // clipped to the sheet
+    let sheet_h = result.height as u64;
+    for m in result.items.values() {
+        let (y, h) = (m.y as u64, m.h as u64);
+        let (top, bottom) = match result.coordinate_origin {
+            Origin::TopLeft => (y, y + h),
+            Origin::BottomLeft => (sheet_h.saturating_sub(y + h), sheet_h.saturating_sub(y)),
+        };
+        let right = (m.x as u64 + m.w as u64).min(width as u64) as usize;
+        for y in top.min(sheet_h) as usize..bottom.min(sheet_h) as usize {
+            for x in (m.x as usize).min(right)..right {
+                covered[y * width + x] = true;
+            }
+        }
+    }
+
+    let used = covered.iter().filter(|&&c| c).count() as u64;
+    let total = (width * height) as u64;
+    let largest = largest_empty_rect(&covered, width, height);
+    let free = total - used;
+
+    result.used_pixels = used;
+    result.total_pixels = total;
+    result.fragmentation = match largest {
+        Some((_, _, w, h)) if free > 0 => 1.0 - (w as u64 * h as u64) as f32 / free as f32,
+        _ => 0.0,
+    };
+    result.largest_free_rect = largest.map(|(x, y, w, h)| SpriteMetadata {
+        x,
+        y: match result.coordinate_origin {
+            Origin::TopLeft => y,
+            Origin::BottomLeft => result.height - y - h,
+        },
+        w,
+        h,
+        row: None,
+        col: None,
+    });
+    result
+}
+
+/// Check a sprite sheet against its metadata
+///
+/// Returns one message per problem found: regions extending beyond the sheet,
+/// regions that overlap each other, and regions that are entirely transparent
+/// (usually a sign the metadata and image are misaligned). Empty means valid.
+pub fn validate_atlas(sheet: &RgbaImage, result: &PackerResult) -> Vec<String> {
+    let mut problems = Vec::new();
+    let (sheet_w, sheet_h) = sheet.dimensions();
+
+    let mut names: Vec<&String> = result.items.keys().collect();
+    names.sort();
+
+    if (result.width, result.height) != (sheet_w, sheet_h) {
+        problems.push(format!(
+            "Metadata size {}x{} does not match sheet size {}x{}",
+            result.width, result.height, sheet_w, sheet_h
+        ));
+    }
+
+    // Sums are taken in u64 since the metadata may come from an untrusted
+    // file; only in-bounds items go on to the pixel and overlap checks
+    let mut in_bounds: Vec<(&String, SpriteMetadata)> = Vec::new();
+    for name in &names {
+        let m = &result.items[*name];
+        if m.x as u64 + m.w as u64 > sheet_w as u64 || m.y as u64 + m.h as u64 > sheet_h as u64 {
+            problems.push(format!(
+                "'{}' ({}, {}, {}x{}) extends beyond the {}x{} sheet",
+                name, m.x, m.y, m.w, m.h, sheet_w, sheet_h
+            ));
+            continue;
+        }
+
+        // Work in top-left coordinates to match the pixel layout
+        let y = match result.coordinate_origin {
+            Origin::TopLeft => m.y,
+            Origin::BottomLeft => sheet_h - m.y - m.h,
+        };
+        let m = SpriteMetadata { y, ..m.clone() };
+
+        let transparent = (m.y..m.y + m.h).all(|y| (m.x..m.x + m.w).all(|x| sheet.get_pixel(x, y)[3] == 0));
+        if transparent {
+            problems.push(format!("'{}' region is entirely transparent", name));
+        }
+        in_bounds.push((*name, m));
+    }
+
+    for (i, (a_name, a)) in in_bounds.iter().enumerate() {
+        for (b_name, b) in &in_bounds[i + 1..] {
+            let overlaps = a.x < b.x + b.w && b.x < a.x + a.w && a.y < b.y + b.h && b.y < a.y + a.h;
+            if overlaps {
+                problems.push(format!("'{}' overlaps '{}'", a_name, b_name));
+            }
+        }
+    }
+
+    problems
+}
+
+/// `validate_atlas` on a sheet and its metadata JSON, read from disk
+pub fn validate_atlas_files(sheet_path: &Path, metadata_path: &Path) -> Result<Vec<String>> {
+    let sheet = crate::processor::load_image(sheet_path)?;
+    let result: PackerResult = serde_json::from_str(&std::fs::read_to_string(metadata_path)?)?;
+    Ok(validate_atlas(&sheet, &result))
 }
 
 #[cfg(test)]
@@ -192,4 +1030,433 @@ mod tests {
         assert_eq!(settings.max_width, 2048);
         assert_eq!(settings.item_padding, 2);
     }
+
+    #[test]
+    fn test_pack_workspace_uses_current_versions() {
+        use crate::state::{ImageVersion, VersionType, WorkspaceManager, now_iso};
+
+        let tmp = tempfile::tempdir().unwrap();
+
+        let root = tmp.path().to_path_buf();
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        RgbaImage::from_pixel(32, 32, Rgba([1, 1, 1, 255])).save(root.join("a.png")).unwrap();
+        RgbaImage::from_pixel(40, 40, Rgba([2, 2, 2, 255])).save(root.join("sub/b.png")).unwrap();
+
+        let mut manager = WorkspaceManager::open(&root).unwrap();
+        manager.init().unwrap();
+        for (rel, size) in [("a.png", 4), ("sub/b.png", 5)] {
+            let cache_name = format!("{}_processed.png", size);
+            RgbaImage::from_pixel(size, size, Rgba([9, 9, 9, 255]))
+                .save(manager.cache_path(&cache_name))
+                .unwrap();
+            let source = manager.get_or_create_source(rel).unwrap();
+            let id = source.next_version_id();
+            source.add_version(ImageVersion {
+                id: id.clone(),
+                version_type: VersionType::PostProcessed,
+                cache_path: Some(cache_name),
+                parent: Some("v1".to_string()),
+                post_process_settings: None,
+                downscale_settings: None,
+                created: now_iso(),
+                width: None,
+                height: None,
+                byte_size: None,
+            });
+            source.current_version = id;
+        }
+        manager.save().unwrap();
+
+        let settings = PackerSettings { export_metadata: false, ..PackerSettings::default() };
+        let result = pack_workspace(&root, Path::new("atlas/sheet.png"), settings).unwrap();
+
+        assert_eq!(result.items.len(), 2);
+        assert_eq!((result.items["a.png"].w, result.items["a.png"].h), (4, 4));
+        assert_eq!((result.items["sub/b.png"].w, result.items["sub/b.png"].h), (5, 5));
+        assert!(root.join("atlas/sheet.png").exists());
+    }
+
+    #[test]
+    fn test_validate_atlas_reports_overlap() {
+        let mut sheet = RgbaImage::new(16, 16);
+        for y in 0..8 {
+            for x in 0..8 {
+                sheet.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+            }
+        }
+        let mut items = std::collections::HashMap::new();
+        items.insert("a".to_string(), SpriteMetadata { x: 0, y: 0, w: 8, h: 8, row: None, col: None });
+        items.insert("b".to_string(), SpriteMetadata { x: 4, y: 4, w: 8, h: 8, row: None, col: None });
+        let result = PackerResult {
+            schema_version: METADATA_SCHEMA_VERSION,
+            generator: METADATA_GENERATOR.to_string(),
+            sprite_sheet: "sheet.png".to_string(),
+            width: 16,
+            height: 16,
+            items,
+            coordinate_origin: Origin::TopLeft,
+            renamed: Default::default(),
+            clips: Vec::new(),
+            used_pixels: 0,
+            total_pixels: 0,
+            largest_free_rect: None,
+            fragmentation: 0.0,
+        };
+
+        let problems = validate_atlas(&sheet, &result);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].contains("overlaps"));
+    }
+
+    #[test]
+    fn test_validate_atlas_reports_overflowing_items() {
+        let sheet = RgbaImage::from_pixel(16, 16, Rgba([255, 0, 0, 255]));
+        let mut items = std::collections::HashMap::new();
+        items.insert("a".to_string(), SpriteMetadata { x: 0, y: 0, w: 8, h: 8, row: None, col: None });
+        items.insert("huge".to_string(), SpriteMetadata { x: u32::MAX, y: u32::MAX, w: u32::MAX, h: 8, row: None, col: None });
+        let result = PackerResult {
+            schema_version: METADATA_SCHEMA_VERSION,
+            generator: METADATA_GENERATOR.to_string(),
+            sprite_sheet: "sheet.png".to_string(),
+            width: 16,
+            height: 16,
+            items,
+            coordinate_origin: Origin::BottomLeft,
+            renamed: Default::default(),
+            clips: Vec::new(),
+            used_pixels: 0,
+            total_pixels: 0,
+            largest_free_rect: None,
+            fragmentation: 0.0,
+        };
+
+        // Reported as out of bounds, and left out of the overlap check
+        let problems = validate_atlas(&sheet, &result);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].contains("'huge'") && problems[0].contains("extends beyond"));
+    }
+
+    #[test]
+    fn test_bottom_left_origin_flips_y() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        RgbaImage::from_pixel(6, 10, Rgba([1, 2, 3, 255])).save(dir.join("tall.png")).unwrap();
+        RgbaImage::from_pixel(4, 4, Rgba([4, 5, 6, 255])).save(dir.join("small.png")).unwrap();
+
+        let settings = PackerSettings {
+            border_padding: 0,
+            export_metadata: false,
+            coordinate_origin: Origin::BottomLeft,
+            ..PackerSettings::default()
+        };
+        let inputs = vec![dir.join("tall.png"), dir.join("small.png")];
+        let result = pack_sprites(inputs, dir.join("sheet.png"), settings).unwrap();
+
+        // Both sprites sit at top-left y=0 in the pixel layout
+        let sheet = image::open(dir.join("sheet.png")).unwrap().to_rgba8();
+        assert_eq!(sheet.get_pixel(0, 0), &Rgba([1, 2, 3, 255]));
+        assert_eq!(result.items["tall"].y, result.height - 10);
+        assert_eq!(result.items["small"].y, result.height - 4);
+    }
+
+    #[test]
+    fn test_oversized_sprite_rejected_before_packing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        RgbaImage::new(8, 8).save(dir.join("ok.png")).unwrap();
+        RgbaImage::new(40, 12).save(dir.join("huge.png")).unwrap();
+        std::fs::write(dir.join("broken.png"), b"not a png").unwrap();
+
+        let settings = PackerSettings { max_sprite_dimension: 32, ..PackerSettings::default() };
+        let inputs = vec![dir.join("ok.png"), dir.join("huge.png"), dir.join("broken.png")];
+        let err = pack_sprites(inputs, dir.join("sheet.png"), settings).unwrap_err().to_string();
+
+        assert!(err.contains("huge.png is 40x12"), "{}", err);
+        assert!(err.contains("broken.png"), "{}", err);
+        assert!(!err.contains("ok.png"), "{}", err);
+        assert!(!dir.join("sheet.png").exists());
+    }
+
+    #[test]
+    fn test_pack_transparent_single_pixel() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        RgbaImage::new(1, 1).save(dir.join("dot.png")).unwrap();
+
+        let settings = PackerSettings { export_metadata: false, ..PackerSettings::default() };
+        let result = pack_sprites(vec![dir.join("dot.png")], dir.join("sheet.png"), settings).unwrap();
+        let dot = &result.items["dot"];
+        assert_eq!((dot.x, dot.y, dot.w, dot.h), (4, 4, 1, 1));
+    }
+
+    #[test]
+    fn test_rename_pattern_normalizes_frame_names() {
+        let names = vec!["Walk_1".to_string(), "walk-2".to_string()];
+        let mapping = rename_sprites(&names, "{name}_{n:4}").unwrap();
+        assert_eq!(mapping[0], ("Walk_1".to_string(), "walk_0001".to_string()));
+        assert_eq!(mapping[1], ("walk-2".to_string(), "walk_0002".to_string()));
+
+        assert!(rename_sprites(&["run_1".to_string(), "Run-01".to_string()], "{name}_{n}").is_err());
+        assert!(rename_sprite("walk_1", "{frame}").is_err());
+
+        let tmp = tempfile::tempdir().unwrap();
+
+        let dir = tmp.path().to_path_buf();
+        let paths: Vec<PathBuf> = names.iter().map(|n| dir.join(format!("{}.png", n))).collect();
+        for path in &paths {
+            RgbaImage::from_pixel(4, 4, Rgba([5, 5, 5, 255])).save(path).unwrap();
+        }
+        let settings = PackerSettings {
+            export_metadata: false,
+            rename_pattern: Some("{name}_{n:4}".to_string()),
+            ..PackerSettings::default()
+        };
+        let result = pack_sprites(paths, dir.join("sheet.png"), settings).unwrap();
+        assert!(result.items.contains_key("walk_0001") && result.items.contains_key("walk_0002"));
+        assert_eq!(result.renamed["walk-2"], "walk_0002");
+    }
+
+    #[test]
+    fn test_compact_metadata_is_smaller_and_versioned() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let inputs: Vec<PathBuf> = (0..60)
+            .map(|i| {
+                let path = dir.join(format!("sprite_{:02}.png", i));
+                RgbaImage::from_pixel(4, 4, Rgba([i as u8, 0, 0, 255])).save(&path).unwrap();
+                path
+            })
+            .collect();
+
+        let mut sizes = Vec::new();
+        for pretty in [true, false] {
+            let settings = PackerSettings { metadata_pretty: pretty, ..PackerSettings::default() };
+            let sheet = dir.join(format!("sheet_{}.png", pretty));
+            pack_sprites(inputs.clone(), sheet.clone(), settings).unwrap();
+
+            let json = std::fs::read_to_string(sheet.with_extension("json")).unwrap();
+            let parsed: PackerResult = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed.schema_version, METADATA_SCHEMA_VERSION);
+            assert_eq!(parsed.generator, METADATA_GENERATOR);
+            assert_eq!(parsed.items.len(), 60);
+            sizes.push(json.len());
+        }
+        assert!(sizes[1] < sizes[0], "compact {} vs pretty {}", sizes[1], sizes[0]);
+    }
+
+    #[test]
+    fn test_clips_group_frames_in_order() {
+        assert!(glob_match("walk_*", "walk_10"));
+        assert!(glob_match("*_?", "run_3"));
+        assert!(!glob_match("walk_*", "idle_0"));
+
+        let tmp = tempfile::tempdir().unwrap();
+
+        let dir = tmp.path().to_path_buf();
+        let mut inputs: Vec<PathBuf> = ["walk_2", "walk_0", "idle_0", "walk_3", "walk_1"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(format!("{}.png", name));
+                RgbaImage::from_pixel(4, 4, Rgba([9, 9, 9, 255])).save(&path).unwrap();
+                path
+            })
+            .collect();
+
+        let settings = PackerSettings {
+            clips: vec![Clip { name: "walk".to_string(), frame_pattern: "walk_*".to_string(), fps: 8.0 }],
+            ..PackerSettings::default()
+        };
+        let sheet = dir.join("sheet.png");
+        let result = pack_sprites(inputs.clone(), sheet.clone(), settings.clone()).unwrap();
+        let walk = ClipMetadata {
+            name: "walk".to_string(),
+            frames: vec!["walk_0".to_string(), "walk_1".to_string(), "walk_2".to_string(), "walk_3".to_string()],
+            fps: 8.0,
+            frame_duration_ms: 125,
+        };
+        assert_eq!(result.clips, vec![walk.clone()]);
+
+        let parsed: PackerResult = serde_json::from_str(&std::fs::read_to_string(sheet.with_extension("json")).unwrap()).unwrap();
+        assert_eq!(parsed.clips, vec![walk]);
+
+        inputs.retain(|path| !path.to_string_lossy().contains("walk"));
+        assert!(pack_sprites(inputs, sheet, settings).is_err());
+    }
+
+    #[test]
+    fn test_pack_preview_follows_sort_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let inputs: Vec<PathBuf> = [("b", 8, 20), ("c", 40, 64), ("a", 12, 4)]
+            .iter()
+            .map(|&(name, w, h)| {
+                let path = dir.join(format!("{}.png", name));
+                RgbaImage::from_pixel(w, h, Rgba([3, 3, 3, 255])).save(&path).unwrap();
+                path
+            })
+            .collect();
+
+        let preview = pack_preview(inputs.clone(), &PackerSettings::default()).unwrap();
+        assert_eq!(preview.names, ["c", "b", "a"]);
+        let strip = image::load_from_memory(&preview.png).unwrap().to_rgba8();
+        assert_eq!(strip.height(), PREVIEW_THUMBNAIL_SIZE + 2 * PREVIEW_GAP);
+        assert!(!dir.join("sheet.png").exists());
+
+        for (order, expected) in [(SortOrder::Name, ["a", "b", "c"]), (SortOrder::Width, ["c", "a", "b"])] {
+            let settings = PackerSettings { sort_order: order, ..PackerSettings::default() };
+            assert_eq!(pack_preview(inputs.clone(), &settings).unwrap().names, expected);
+        }
+    }
+
+    #[test]
+    fn test_append_to_sheet_keeps_existing_regions() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let sprite = |name: &str, w, h, v| {
+            let path = dir.join(format!("{}.png", name));
+            RgbaImage::from_pixel(w, h, Rgba([v, v, v, 255])).save(&path).unwrap();
+            path
+        };
+        let inputs = vec![sprite("hero", 8, 12, 10), sprite("slime", 6, 6, 20)];
+        let settings = PackerSettings { max_width: 64, sort_order: SortOrder::None, ..PackerSettings::default() };
+        let sheet = dir.join("sheet.png");
+        let before = pack_sprites(inputs, sheet.clone(), settings.clone()).unwrap();
+
+        let bat = sprite("bat", 5, 4, 30);
+        let after = append_to_sheet(&sheet, &sheet.with_extension("json"), vec![bat.clone()], settings.clone()).unwrap();
+
+        for name in ["hero", "slime"] {
+            let (a, b) = (&before.items[name], &after.items[name]);
+            assert_eq!((a.x, a.y, a.w, a.h), (b.x, b.y, b.w, b.h));
+        }
+        // Placed in the free space to the right of the first row
+        let placed = &after.items["bat"];
+        assert_eq!((placed.y, after.height), (before.items["hero"].y, before.height));
+        assert!(placed.x > before.items["slime"].x);
+
+        let img = image::open(&sheet).unwrap().to_rgba8();
+        assert!(validate_atlas(&img, &after).is_empty(), "{:?}", validate_atlas(&img, &after));
+        assert_eq!(*img.get_pixel(placed.x, placed.y), Rgba([30, 30, 30, 255]));
+
+        assert!(append_to_sheet(&sheet, &sheet.with_extension("json"), vec![bat], settings).is_err());
+    }
+
+    #[test]
+    fn test_append_to_sheet_rejects_out_of_range_metadata() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let hero = dir.join("hero.png");
+        RgbaImage::from_pixel(8, 8, Rgba([10, 10, 10, 255])).save(&hero).unwrap();
+        let settings = PackerSettings { coordinate_origin: Origin::BottomLeft, ..PackerSettings::default() };
+        let sheet = dir.join("sheet.png");
+        let mut result = pack_sprites(vec![hero.clone()], sheet.clone(), settings.clone()).unwrap();
+
+        result.items.get_mut("hero").unwrap().h = u32::MAX;
+        let metadata = sheet.with_extension("json");
+        std::fs::write(&metadata, serde_json::to_string(&result).unwrap()).unwrap();
+
+        let bat = dir.join("bat.png");
+        RgbaImage::from_pixel(4, 4, Rgba([30, 30, 30, 255])).save(&bat).unwrap();
+        let err = append_to_sheet(&sheet, &metadata, vec![bat], settings).unwrap_err();
+        assert!(err.to_string().contains("hero"), "{}", err);
+
+        // Measuring such metadata clips instead of overflowing
+        let measured = measure_packing(result);
+        assert!(measured.used_pixels <= measured.total_pixels);
+    }
+
+    #[test]
+    fn test_packing_metrics_favor_tight_layouts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let inputs: Vec<PathBuf> = (0..4)
+            .map(|i| {
+                let path = dir.join(format!("tile_{}.png", i));
+                RgbaImage::from_pixel(8, 8, Rgba([i as u8, 0, 0, 255])).save(&path).unwrap();
+                path
+            })
+            .collect();
+
+        let tight = PackerSettings {
+            max_width: 16,
+            item_padding: 0,
+            row_padding: 0,
+            border_padding: 0,
+            export_metadata: false,
+            ..PackerSettings::default()
+        };
+        let tight = pack_sprites(inputs.clone(), dir.join("tight.png"), tight).unwrap();
+        assert_eq!((tight.used_pixels, tight.total_pixels), (256, 256));
+        assert!(tight.largest_free_rect.is_none());
+
+        let loose = PackerSettings { max_width: 128, item_padding: 8, export_metadata: false, ..PackerSettings::default() };
+        let loose = pack_sprites(inputs, dir.join("loose.png"), loose).unwrap();
+        let efficiency = |r: &PackerResult| r.used_pixels as f32 / r.total_pixels as f32;
+        assert!(efficiency(&tight) > efficiency(&loose));
+        assert!(loose.fragmentation > 0.0 && loose.fragmentation < 1.0);
+        let free = loose.largest_free_rect.clone().unwrap();
+        assert!(free.w as u64 * free.h as u64 <= loose.total_pixels - loose.used_pixels);
+
+        assert_eq!(largest_empty_rect(&[true, false, false, false, false, true], 3, 2), Some((1, 0, 2, 1)));
+    }
+
+    #[test]
+    fn test_uniform_grid_centers_sprites_in_cells() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        // Visible content of 10x10, 20x8 and 32x32, the first two inside
+        // transparent margins that the grid trims away
+        let mut a = RgbaImage::new(40, 40);
+        image::imageops::replace(&mut a, &RgbaImage::from_pixel(10, 10, Rgba([255, 0, 0, 255])), 3, 25);
+        a.save(dir.join("a.png")).unwrap();
+        let mut b = RgbaImage::new(24, 12);
+        image::imageops::replace(&mut b, &RgbaImage::from_pixel(20, 8, Rgba([0, 255, 0, 255])), 0, 0);
+        b.save(dir.join("b.png")).unwrap();
+        RgbaImage::from_pixel(32, 32, Rgba([0, 0, 255, 255])).save(dir.join("c.png")).unwrap();
+        let inputs: Vec<PathBuf> = ["a", "b", "c"].iter().map(|n| dir.join(format!("{}.png", n))).collect();
+
+        let grid = |max_width| PackerSettings {
+            max_width,
+            item_padding: 0,
+            row_padding: 0,
+            border_padding: 0,
+            sort_order: SortOrder::Name,
+            export_metadata: false,
+            uniform_grid: Some((32, 32)),
+            ..PackerSettings::default()
+        };
+
+        let sheet_path = dir.join("row.png");
+        let row = pack_sprites(inputs.clone(), sheet_path.clone(), grid(96)).unwrap();
+        assert_eq!((row.width, row.height), (96, 32));
+        let cell = |name: &str| {
+            let m = &row.items[name];
+            (m.x, m.y, m.w, m.h, m.row, m.col)
+        };
+        assert_eq!(cell("a"), (0, 0, 32, 32, Some(0), Some(0)));
+        assert_eq!(cell("b"), (32, 0, 32, 32, Some(0), Some(1)));
+        assert_eq!(cell("c"), (64, 0, 32, 32, Some(0), Some(2)));
+
+        let sheet = image::open(&sheet_path).unwrap().to_rgba8();
+        // a: 10x10 centered at (11, 11) in the first cell
+        assert_eq!(sheet.get_pixel(10, 11)[3], 0);
+        assert_eq!(*sheet.get_pixel(11, 11), Rgba([255, 0, 0, 255]));
+        assert_eq!(*sheet.get_pixel(20, 20), Rgba([255, 0, 0, 255]));
+        assert_eq!(sheet.get_pixel(21, 20)[3], 0);
+        // b: 20x8 centered at (6, 12) in the second cell
+        assert_eq!(*sheet.get_pixel(32 + 6, 12), Rgba([0, 255, 0, 255]));
+        assert_eq!(sheet.get_pixel(32 + 5, 12)[3], 0);
+        assert_eq!(sheet.get_pixel(32 + 6, 20)[3], 0);
+        // c fills the third cell
+        assert_eq!(*sheet.get_pixel(64, 0), Rgba([0, 0, 255, 255]));
+        assert_eq!(*sheet.get_pixel(95, 31), Rgba([0, 0, 255, 255]));
+
+        let column = pack_sprites(inputs.clone(), dir.join("column.png"), grid(32)).unwrap();
+        assert_eq!((column.width, column.height), (32, 96));
+        assert_eq!((column.items["c"].y, column.items["c"].row, column.items["c"].col), (64, Some(2), Some(0)));
+
+        let small = PackerSettings { uniform_grid: Some((16, 16)), ..grid(96) };
+        let err = pack_sprites(inputs, dir.join("small.png"), small).unwrap_err().to_string();
+        assert!(err.contains("b (20x8)") && err.contains("c (32x32)") && !err.contains("a ("));
+    }
 }
@@ -0,0 +1,542 @@
+//! Palette Import and Export
+//!
+//! Loads palettes exported from Aseprite for use with palette snapping:
+//! - JSON exports (a list of hex strings or `{r, g, b, a}` objects)
+//! - The palette chunk of `.ase`/`.aseprite` files
+//!
+//! Exports an image's colors as a GIMP `.gpl` or Lospec `.hex` palette,
+//! optionally sorted (see `PaletteSort`).
+//!
+//! `PaletteWatcher` reloads a palette file whenever it changes on disk.
+
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use crate::error::{Result, PixelsError};
+
+pub type PaletteColor = (u8, u8, u8, u8);
+
+/// Aseprite chunk type for the current palette format
+const CHUNK_PALETTE: u16 = 0x2019;
+/// Aseprite chunk type for the legacy palette format (pre-1.2 files)
+const CHUNK_OLD_PALETTE: u16 = 0x0004;
+const FILE_MAGIC: u16 = 0xA5E0;
+const FRAME_MAGIC: u16 = 0xF1FA;
+const HEADER_SIZE: usize = 128;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonColor {
+    Hex(String),
+    Rgba {
+        r: u8,
+        g: u8,
+        b: u8,
+        #[serde(default = "opaque")]
+        a: u8,
+    },
+}
+
+fn opaque() -> u8 {
+    255
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonPalette {
+    List(Vec<JsonColor>),
+    Object { colors: Vec<JsonColor> },
+}
+
+/// Load a palette, choosing the parser from the file extension
+pub fn load_aseprite_palette(path: &Path) -> Result<Vec<PaletteColor>> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    match ext.as_str() {
+        "json" => parse_palette_json(&std::fs::read_to_string(path)?),
+        "ase" | "aseprite" => parse_aseprite_palette(&std::fs::read(path)?),
+        _ => Err(PixelsError::UnsupportedFormat(format!(
+            "Palette must be .json, .ase or .aseprite: {}",
+            path.display()
+        ))),
+    }
+}
+
+/// Parse a JSON palette export
+///
+/// Accepts either a bare array or an object with a `colors` array. Each color
+/// is a hex string (`#rrggbb` or `#rrggbbaa`, `#` optional) or an object with
+/// `r`, `g`, `b` and optional `a` (default 255).
+pub fn parse_palette_json(json: &str) -> Result<Vec<PaletteColor>> {
+    let colors = match serde_json::from_str(json)? {
+        JsonPalette::List(colors) | JsonPalette::Object { colors } => colors,
+    };
+
+    colors
+        .into_iter()
+        .map(|color| match color {
+            JsonColor::Hex(hex) => parse_hex(&hex),
+            JsonColor::Rgba { r, g, b, a } => Ok((r, g, b, a)),
+        })
+        .collect()
+}
+
+fn parse_hex(hex: &str) -> Result<PaletteColor> {
+    let digits = hex.trim().trim_start_matches('#');
+    let channel = |i: usize| {
+        digits
+            .get(i..i + 2)
+            .and_then(|s| u8::from_str_radix(s, 16).ok())
+            .ok_or_else(|| PixelsError::InvalidParameter(format!("Invalid hex color '{}'", hex)))
+    };
+
+    match digits.len() {
+        6 => Ok((channel(0)?, channel(2)?, channel(4)?, 255)),
+        8 => Ok((channel(0)?, channel(2)?, channel(4)?, channel(6)?)),
+        _ => Err(PixelsError::InvalidParameter(format!("Invalid hex color '{}'", hex))),
+    }
+}
+
+/// Little-endian cursor over Aseprite file bytes
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        let slice = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| PixelsError::Decode("Aseprite file is truncated".to_string()))?;
+        self.pos += n;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+/// Parse the palette from the first frame of an `.ase`/`.aseprite` file
+///
+/// Uses the current palette chunk (0x2019) when present, otherwise the
+/// legacy chunk (0x0004). Layers and cels are skipped.
+pub fn parse_aseprite_palette(data: &[u8]) -> Result<Vec<PaletteColor>> {
+    let mut header = Reader { data, pos: 4 };
+    if header.u16()? != FILE_MAGIC {
+        return Err(PixelsError::Decode("Not an Aseprite file".to_string()));
+    }
+
+    let mut frame = Reader { data, pos: HEADER_SIZE };
+    let frame_start = frame.pos;
+    let frame_size = frame.u32()? as usize;
+    if frame.u16()? != FRAME_MAGIC {
+        return Err(PixelsError::Decode("Invalid Aseprite frame header".to_string()));
+    }
+    let old_chunk_count = frame.u16()? as usize;
+    frame.bytes(4)?; // duration + reserved
+    let chunk_count = match frame.u32()? as usize {
+        0 => old_chunk_count,
+        n => n,
+    };
+    let frame_end = frame_start + frame_size;
+
+    let mut legacy = None;
+    for _ in 0..chunk_count {
+        if frame.pos >= frame_end {
+            break;
+        }
+        let chunk_start = frame.pos;
+        let chunk_size = frame.u32()? as usize;
+        let chunk_type = frame.u16()?;
+        let body = frame.bytes(chunk_size.saturating_sub(6))?;
+        frame.pos = chunk_start + chunk_size.max(6);
+
+        match chunk_type {
+            CHUNK_PALETTE => return parse_palette_chunk(body),
+            CHUNK_OLD_PALETTE if legacy.is_none() => legacy = Some(parse_old_palette_chunk(body)?),
+            _ => {}
+        }
+    }
+
+    legacy.ok_or_else(|| PixelsError::Decode("Aseprite file has no palette chunk".to_string()))
+}
+
+fn parse_palette_chunk(body: &[u8]) -> Result<Vec<PaletteColor>> {
+    let mut r = Reader { data: body, pos: 0 };
+    let _size = r.u32()?;
+    let first = r.u32()?;
+    let last = r.u32()?;
+    r.bytes(8)?; // reserved
+
+    let mut colors = Vec::new();
+    for _ in first..=last {
+        let flags = r.u16()?;
+        let rgba = r.bytes(4)?;
+        colors.push((rgba[0], rgba[1], rgba[2], rgba[3]));
+        if flags & 1 != 0 {
+            let name_len = r.u16()? as usize;
+            r.bytes(name_len)?;
+        }
+    }
+    Ok(colors)
+}
+
+fn parse_old_palette_chunk(body: &[u8]) -> Result<Vec<PaletteColor>> {
+    let mut r = Reader { data: body, pos: 0 };
+    let packets = r.u16()?;
+
+    let mut colors = Vec::new();
+    for _ in 0..packets {
+        let skip = r.u8()? as usize;
+        colors.extend(std::iter::repeat_n((0, 0, 0, 255), skip));
+        let count = match r.u8()? {
+            0 => 256,
+            n => n as usize,
+        };
+        for _ in 0..count {
+            let rgb = r.bytes(3)?;
+            colors.push((rgb[0], rgb[1], rgb[2], 255));
+        }
+    }
+    Ok(colors)
+}
+
+// ============================================================================
+// EXPORT
+// ============================================================================
+
+/// Order of colors in an exported palette
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaletteSort {
+    /// Keep the given order (`extract_palette` yields most common first)
+    #[default]
+    Frequency,
+    /// Around the color wheel from red, grays first
+    Hue,
+    /// Darkest to lightest
+    Luminance,
+    /// Grouped into hue families (grays first), each dark to light, the way
+    /// hand-made palettes are usually laid out
+    Populated,
+}
+
+/// Max channel spread at which a color counts as gray for hue sorting
+const GRAY_CHROMA: u8 = 16;
+
+/// Hue families used by `PaletteSort::Populated`, in degrees per family
+const HUE_FAMILY_DEGREES: f32 = 30.0;
+
+/// Hue in degrees (0-360), or None for grays
+fn hue(r: u8, g: u8, b: u8) -> Option<f32> {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let chroma = (max - min) as f32;
+    if max - min <= GRAY_CHROMA {
+        return None;
+    }
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let sector = if max as f32 == r {
+        ((g - b) / chroma).rem_euclid(6.0)
+    } else if max as f32 == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+    Some(sector * 60.0)
+}
+
+/// Sort palette colors in place
+///
+/// Equal keys fall back to RGB order, so the result doesn't depend on the
+/// input order (except for `Frequency`, which leaves it untouched).
+pub fn sort_palette(colors: &mut [(u8, u8, u8)], mode: PaletteSort) {
+    let lightness = |&(r, g, b): &(u8, u8, u8)| crate::color::rgb_to_lab(r, g, b).0;
+    let hue_key = |&(r, g, b): &(u8, u8, u8)| hue(r, g, b).map_or(-1.0, |h| h);
+
+    match mode {
+        PaletteSort::Frequency => {}
+        PaletteSort::Luminance => {
+            colors.sort_by(|a, b| lightness(a).total_cmp(&lightness(b)).then(a.cmp(b)));
+        }
+        PaletteSort::Hue => {
+            colors.sort_by(|a, b| {
+                hue_key(a).total_cmp(&hue_key(b)).then(lightness(a).total_cmp(&lightness(b))).then(a.cmp(b))
+            });
+        }
+        PaletteSort::Populated => {
+            let family = |c: &(u8, u8, u8)| {
+                hue(c.0, c.1, c.2).map_or(-1, |h| ((h / HUE_FAMILY_DEGREES) as i32) % (360.0 / HUE_FAMILY_DEGREES) as i32)
+            };
+            colors.sort_by(|a, b| family(a).cmp(&family(b)).then(lightness(a).total_cmp(&lightness(b))).then(a.cmp(b)));
+        }
+    }
+}
+
+/// Visible colors of an image (alpha ignored), most common first
+pub fn extract_palette(img: &RgbaImage) -> Vec<(u8, u8, u8)> {
+    let mut counts: HashMap<(u8, u8, u8), usize> = HashMap::new();
+    for p in img.pixels().filter(|p| p[3] > 0) {
+        *counts.entry((p[0], p[1], p[2])).or_insert(0) += 1;
+    }
+    let mut colors: Vec<_> = counts.into_iter().collect();
+    colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    colors.into_iter().map(|(color, _)| color).collect()
+}
+
+/// Render colors as a GIMP palette (`.gpl`)
+pub fn format_gpl(colors: &[(u8, u8, u8)], name: &str) -> String {
+    let mut out = format!("GIMP Palette\nName: {}\nColumns: 0\n#\n", name);
+    for &(r, g, b) in colors {
+        out.push_str(&format!("{:3} {:3} {:3}\t#{:02x}{:02x}{:02x}\n", r, g, b, r, g, b));
+    }
+    out
+}
+
+/// Render colors as a Lospec hex palette (`.hex`): one `rrggbb` per line
+pub fn format_hex(colors: &[(u8, u8, u8)]) -> String {
+    colors.iter().map(|&(r, g, b)| format!("{:02x}{:02x}{:02x}\n", r, g, b)).collect()
+}
+
+/// Write an image's palette, choosing the format from the file extension
+/// (`.gpl` or `.hex`). Returns the number of colors written.
+pub fn export_palette(img: &RgbaImage, path: &Path, sort: PaletteSort) -> Result<usize> {
+    let mut colors = extract_palette(img);
+    sort_palette(&mut colors, sort);
+
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    let content = match ext.as_str() {
+        "gpl" => {
+            let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            format_gpl(&colors, &name)
+        }
+        "hex" => format_hex(&colors),
+        _ => {
+            return Err(PixelsError::UnsupportedFormat(format!(
+                "Palette export must be .gpl or .hex: {}",
+                path.display()
+            )))
+        }
+    };
+    std::fs::write(path, content)?;
+    Ok(colors.len())
+}
+
+// ============================================================================
+// FILE WATCHING
+// ============================================================================
+
+/// Quiet period after the last change before reloading, so an editor's
+/// multi-step save triggers one reload
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Whether a file system event can have changed a file's contents (reads
+/// and permission changes can't)
+fn is_content_change(event: &Event) -> bool {
+    match event.kind {
+        EventKind::Create(_) | EventKind::Remove(_) => true,
+        EventKind::Modify(kind) => !matches!(kind, ModifyKind::Metadata(_)),
+        _ => false,
+    }
+}
+
+/// Reloads a palette file when it changes on disk
+///
+/// File system events are debounced: once the file has been quiet for
+/// `WATCH_DEBOUNCE`, the palette is re-parsed and passed to the callback.
+/// Files that fail to parse (e.g. caught mid-write) are skipped until the
+/// next change. The containing folder is watched rather than the file, so
+/// editors that save by replacing the file keep being followed. Watching
+/// stops when the watcher is dropped.
+pub struct PaletteWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl PaletteWatcher {
+    pub fn watch<F>(path: PathBuf, on_change: F) -> Result<Self>
+    where
+        F: Fn(Vec<PaletteColor>) + Send + 'static,
+    {
+        let cannot_watch = |reason: String| {
+            PixelsError::InvalidParameter(format!("Cannot watch {}: {}", path.display(), reason))
+        };
+        // Events report canonical paths
+        let target = path.canonicalize().map_err(|e| cannot_watch(e.to_string()))?;
+        let folder = match target.parent() {
+            Some(folder) if target.is_file() => folder.to_path_buf(),
+            _ => return Err(cannot_watch("not a file".to_string())),
+        };
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let _ = tx.send(event);
+        })
+        .map_err(|e| cannot_watch(e.to_string()))?;
+        watcher
+            .watch(&folder, RecursiveMode::NonRecursive)
+            .map_err(|e| cannot_watch(e.to_string()))?;
+
+        // Ends when the watcher, and with it the sending side, is dropped
+        std::thread::spawn(move || {
+            let mut reload_at: Option<Instant> = None;
+            loop {
+                let received = match reload_at {
+                    Some(at) => rx.recv_timeout(at.saturating_duration_since(Instant::now())),
+                    None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match received {
+                    Ok(Ok(event)) if is_content_change(&event) && event.paths.contains(&target) => {
+                        reload_at = Some(Instant::now() + WATCH_DEBOUNCE);
+                    }
+                    Ok(_) => {}
+                    Err(RecvTimeoutError::Timeout) => {
+                        reload_at = None;
+                        if let Ok(colors) = load_aseprite_palette(&target) {
+                            on_change(colors);
+                        }
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_palette_json() {
+        let json = r##"{ "colors": ["#110602", "ffffff80", { "r": 10, "g": 20, "b": 30 }] }"##;
+        assert_eq!(
+            parse_palette_json(json).unwrap(),
+            vec![(17, 6, 2, 255), (255, 255, 255, 128), (10, 20, 30, 255)]
+        );
+        assert_eq!(parse_palette_json(r##"["#000000"]"##).unwrap(), vec![(0, 0, 0, 255)]);
+        assert!(parse_palette_json(r##"["#12345"]"##).is_err());
+    }
+
+    #[test]
+    fn test_parse_aseprite_palette_chunk() {
+        // Palette chunk: 2 entries, the second named
+        let mut body = Vec::new();
+        body.extend_from_slice(&2u32.to_le_bytes());
+        body.extend_from_slice(&0u32.to_le_bytes());
+        body.extend_from_slice(&1u32.to_le_bytes());
+        body.extend_from_slice(&[0; 8]);
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&[255, 0, 0, 255]);
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&[0, 0, 255, 128]);
+        body.extend_from_slice(&4u16.to_le_bytes());
+        body.extend_from_slice(b"blue");
+
+        let mut chunk = Vec::new();
+        chunk.extend_from_slice(&(body.len() as u32 + 6).to_le_bytes());
+        chunk.extend_from_slice(&CHUNK_PALETTE.to_le_bytes());
+        chunk.extend_from_slice(&body);
+
+        let mut file = vec![0u8; HEADER_SIZE];
+        file[4..6].copy_from_slice(&FILE_MAGIC.to_le_bytes());
+        file.extend_from_slice(&(chunk.len() as u32 + 16).to_le_bytes());
+        file.extend_from_slice(&FRAME_MAGIC.to_le_bytes());
+        file.extend_from_slice(&1u16.to_le_bytes());
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(&1u32.to_le_bytes());
+        file.extend_from_slice(&chunk);
+
+        assert_eq!(parse_aseprite_palette(&file).unwrap(), vec![(255, 0, 0, 255), (0, 0, 255, 128)]);
+        assert!(parse_aseprite_palette(&file[..HEADER_SIZE + 20]).is_err());
+    }
+
+    #[test]
+    fn test_palette_watcher_reports_new_colors() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let path = dir.join("palette.json");
+        std::fs::write(&path, r##"["#000000"]"##).unwrap();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let watcher = PaletteWatcher::watch(path.clone(), move |colors| {
+            let _ = tx.send(colors);
+        })
+        .unwrap();
+
+        // Two quick writes, as an editor might do, debounce to one reload
+        std::fs::write(&path, r##"["#ff0000"]"##).unwrap();
+        std::fs::write(&path, r##"["#ff0000", "#00ff0080"]"##).unwrap();
+
+        let colors = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(colors, vec![(255, 0, 0, 255), (0, 255, 0, 128)]);
+        assert!(rx.recv_timeout(WATCH_DEBOUNCE * 2).is_err());
+
+        // Saving by writing a temp file and renaming it over the palette
+        let temp = dir.join("palette.json.tmp");
+        std::fs::write(&temp, r##"["#0000ff"]"##).unwrap();
+        std::fs::rename(&temp, &path).unwrap();
+        let colors = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(colors, vec![(0, 0, 255, 255)]);
+
+        drop(watcher);
+        assert!(PaletteWatcher::watch(dir.join("missing.json"), |_| {}).is_err());
+    }
+
+    #[test]
+    fn test_sort_palette() {
+        let mut colors = vec![(255, 255, 255), (0, 0, 0), (128, 128, 128)];
+        sort_palette(&mut colors, PaletteSort::Luminance);
+        assert_eq!(colors, vec![(0, 0, 0), (128, 128, 128), (255, 255, 255)]);
+
+        let red = [(255, 0, 0), (140, 10, 10)];
+        let blue = [(0, 0, 255), (60, 60, 200)];
+        let mut colors = vec![red[0], blue[0], (0, 200, 0), red[1], blue[1]];
+        for mode in [PaletteSort::Hue, PaletteSort::Populated] {
+            sort_palette(&mut colors, mode);
+            let at = |c| colors.iter().position(|&x| x == c).unwrap();
+            assert_eq!(at(red[0]).abs_diff(at(red[1])), 1, "{:?}: {:?}", mode, colors);
+            assert_eq!(at(blue[0]).abs_diff(at(blue[1])), 1, "{:?}: {:?}", mode, colors);
+        }
+
+        // Populated orders each hue family dark to light
+        sort_palette(&mut colors, PaletteSort::Populated);
+        assert_eq!(&colors[..2], &[red[1], red[0]]);
+    }
+
+    #[test]
+    fn test_format_palettes() {
+        let colors = [(17, 6, 2), (255, 255, 255)];
+        assert_eq!(format_hex(&colors), "110602\nffffff\n");
+        assert_eq!(
+            format_gpl(&colors, "hero"),
+            "GIMP Palette\nName: hero\nColumns: 0\n#\n 17   6   2\t#110602\n255 255 255\t#ffffff\n"
+        );
+    }
+}
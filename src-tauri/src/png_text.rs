@@ -0,0 +1,188 @@
+//! PNG Text Chunks
+//!
+//! Reads and writes the key-value text chunks a PNG can carry, used to
+//! record provenance such as the upscale factor:
+//! - Reads `tEXt`, `zTXt` and `iTXt` (compressed or not)
+//! - Writes `tEXt`, or `iTXt` when the value isn't Latin-1
+//!
+//! Everything else in the file is passed through byte for byte.
+
+use std::io::Read;
+use flate2::read::ZlibDecoder;
+use crate::error::{Result, PixelsError};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Length, type and CRC around each chunk's data
+const CHUNK_OVERHEAD: usize = 12;
+
+/// Longest keyword the PNG spec allows
+const MAX_KEYWORD_LEN: usize = 79;
+
+struct Chunk<'a> {
+    kind: [u8; 4],
+    data: &'a [u8],
+    /// Offset of the chunk's length field
+    start: usize,
+    /// Offset just past the chunk's CRC
+    end: usize,
+}
+
+/// Split a PNG into its chunks, without checking CRCs
+fn chunks(png: &[u8]) -> Result<Vec<Chunk<'_>>> {
+    if !png.starts_with(&PNG_SIGNATURE) {
+        return Err(PixelsError::UnsupportedFormat("Not a PNG file".to_string()));
+    }
+
+    let mut chunks = Vec::new();
+    let mut at = PNG_SIGNATURE.len();
+    while at < png.len() {
+        let header = png
+            .get(at..at + 8)
+            .ok_or_else(|| PixelsError::Decode("Truncated PNG chunk header".to_string()))?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let kind = [header[4], header[5], header[6], header[7]];
+        let data = png
+            .get(at + 8..at + 8 + len)
+            .ok_or_else(|| PixelsError::Decode("Truncated PNG chunk".to_string()))?;
+        let end = at + len + CHUNK_OVERHEAD;
+        chunks.push(Chunk { kind, data, start: at, end });
+        at = end;
+    }
+    Ok(chunks)
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn inflate(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    ZlibDecoder::new(bytes).read_to_end(&mut out).ok()?;
+    Some(out)
+}
+
+/// Decode one text chunk into (keyword, text); None for other chunks and
+/// for text chunks that don't parse
+fn parse_text(chunk: &Chunk) -> Option<(String, String)> {
+    let split = chunk.data.iter().position(|&b| b == 0);
+    match &chunk.kind {
+        b"tEXt" => {
+            let split = split?;
+            Some((latin1(&chunk.data[..split]), latin1(&chunk.data[split + 1..])))
+        }
+        b"zTXt" => {
+            let split = split?;
+            let text = inflate(chunk.data.get(split + 2..)?)?;
+            Some((latin1(&chunk.data[..split]), latin1(&text)))
+        }
+        b"iTXt" => {
+            let split = split?;
+            let keyword = latin1(&chunk.data[..split]);
+            let compressed = *chunk.data.get(split + 1)? != 0;
+            // Skip the language tag and translated keyword
+            let mut rest = chunk.data.get(split + 3..)?;
+            for _ in 0..2 {
+                let end = rest.iter().position(|&b| b == 0)?;
+                rest = &rest[end + 1..];
+            }
+            let text = if compressed { inflate(rest)? } else { rest.to_vec() };
+            Some((keyword, String::from_utf8(text).ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// Read every text chunk in a PNG as (keyword, text) pairs, in file order
+pub fn read_text_chunks(png: &[u8]) -> Result<Vec<(String, String)>> {
+    Ok(chunks(png)?.iter().filter_map(parse_text).collect())
+}
+
+/// Look up one keyword's text, if the PNG has it
+pub fn read_text(png: &[u8], keyword: &str) -> Result<Option<String>> {
+    Ok(read_text_chunks(png)?
+        .into_iter()
+        .find(|(key, _)| key == keyword)
+        .map(|(_, text)| text))
+}
+
+/// Return a copy of `png` with `keyword` set to `text`
+///
+/// Existing text chunks with the same keyword are dropped; the new chunk
+/// goes right before the first `IDAT`.
+pub fn write_text(png: &[u8], keyword: &str, text: &str) -> Result<Vec<u8>> {
+    let keyword_ok = !keyword.is_empty()
+        && keyword.len() <= MAX_KEYWORD_LEN
+        && keyword.chars().all(|c| (' '..='~').contains(&c));
+    if !keyword_ok {
+        return Err(PixelsError::InvalidParameter(format!(
+            "'{}' is not a valid PNG text keyword (1-{} printable ASCII characters)",
+            keyword, MAX_KEYWORD_LEN
+        )));
+    }
+
+    let (kind, data) = if text.chars().all(|c| (c as u32) < 256 && c != '\0') {
+        let mut data = keyword.as_bytes().to_vec();
+        data.push(0);
+        data.extend(text.chars().map(|c| c as u8));
+        (*b"tEXt", data)
+    } else {
+        // Uncompressed, with empty language tag and translated keyword
+        let mut data = keyword.as_bytes().to_vec();
+        data.extend_from_slice(&[0, 0, 0, 0, 0]);
+        data.extend_from_slice(text.as_bytes());
+        (*b"iTXt", data)
+    };
+    let len = u32::try_from(data.len())
+        .map_err(|_| PixelsError::InvalidParameter("PNG text too long".to_string()))?;
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(&kind);
+    crc.update(&data);
+
+    let mut new_chunk = Vec::with_capacity(data.len() + CHUNK_OVERHEAD);
+    new_chunk.extend_from_slice(&len.to_be_bytes());
+    new_chunk.extend_from_slice(&kind);
+    new_chunk.extend_from_slice(&data);
+    new_chunk.extend_from_slice(&crc.finalize().to_be_bytes());
+
+    let mut out = PNG_SIGNATURE.to_vec();
+    let mut inserted = false;
+    for chunk in chunks(png)? {
+        if parse_text(&chunk).is_some_and(|(key, _)| key == keyword) {
+            continue;
+        }
+        if !inserted && (&chunk.kind == b"IDAT" || &chunk.kind == b"IEND") {
+            out.extend_from_slice(&new_chunk);
+            inserted = true;
+        }
+        out.extend_from_slice(&png[chunk.start..chunk.end]);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn test_text_chunk_roundtrip() {
+        let png = crate::processor::encode_png(&RgbaImage::from_pixel(3, 2, Rgba([9, 8, 7, 255]))).unwrap();
+        assert_eq!(read_text(&png, "upscale_factor").unwrap(), None);
+
+        let tagged = write_text(&png, "upscale_factor", "8").unwrap();
+        let tagged = write_text(&tagged, "Author", "Zoë 🎨").unwrap();
+        let retagged = write_text(&tagged, "upscale_factor", "4").unwrap();
+        assert_eq!(
+            read_text_chunks(&retagged).unwrap(),
+            vec![("Author".to_string(), "Zoë 🎨".to_string()), ("upscale_factor".to_string(), "4".to_string())]
+        );
+
+        // Still a valid image with the same pixels
+        let decoded = image::load_from_memory(&retagged).unwrap().to_rgba8();
+        assert_eq!(*decoded.get_pixel(2, 1), Rgba([9, 8, 7, 255]));
+
+        assert!(write_text(&png, "", "x").is_err());
+        assert!(read_text_chunks(b"GIF89a").is_err());
+    }
+}
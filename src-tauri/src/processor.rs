@@ -15,10 +15,11 @@
 //!
 //! The original `process_image` function remains for backward compatibility.
 
-use image::{RgbaImage, Rgba};
+use image::{DynamicImage, GrayImage, RgbaImage, Rgba};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use crate::color::{delta_e76, lab_to_rgb, luma, rgb_to_lab};
 use crate::error::{Result, PixelsError};
 
 // ============================================================================
@@ -49,9 +50,10 @@ pub struct ProcessorSettings {
     pub outline_thickness: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Connectivity {
+    #[default]
     Four,
     Eight,
 }
@@ -69,6 +71,9 @@ pub struct AlphaSettings {
     pub high_min: u8,
     /// Upper bound of range for making pixels fully opaque (default: 255)
     pub high_max: u8,
+    /// Binary cutoff (default) or multi-level quantization
+    #[serde(default)]
+    pub mode: AlphaMode,
 }
 
 impl Default for AlphaSettings {
@@ -77,20 +82,66 @@ impl Default for AlphaSettings {
             low_cutoff: 200,
             high_min: 200,
             high_max: 255,
+            mode: AlphaMode::Binary,
         }
     }
 }
 
+/// How alpha values are normalized
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlphaMode {
+    /// Threshold to 0 / 255 using the cutoff fields
+    #[default]
+    Binary,
+    /// Snap to the nearest of `levels` evenly spaced steps from 0 to 255
+    /// (e.g. 4 levels = 0/85/170/255). Cutoff fields are ignored.
+    Quantize { levels: u8 },
+}
+
 /// Settings for LAB color space merging
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MergeSettings {
     /// Delta E76 threshold for color clustering - lower = more aggressive merging (default: 3.0)
     pub threshold: f32,
+    /// Include alpha in the clustering distance, scaled so a full 0-255
+    /// alpha difference counts as 100 * weight (same range as LAB L).
+    /// None clusters by RGB only (default: None)
+    #[serde(default)]
+    pub alpha_weight: Option<f32>,
+    /// Zero the RGB of fully transparent pixels after merging (default: false)
+    #[serde(default)]
+    pub clear_transparent_rgb: bool,
+    /// When the image has more unique colors than this, round each channel
+    /// to a 16-step grid before clustering to bound the working set
+    /// (e.g. photographic input). None always clusters exact colors (default: None)
+    #[serde(default)]
+    pub max_unique_colors_before_prequantize: Option<usize>,
+    /// RGB colors that are never merged: each keeps its own cluster and no
+    /// other cluster may average onto it (default: none)
+    #[serde(default)]
+    pub locked_colors: Vec<(u8, u8, u8)>,
+    /// Outline color to protect: pixels within `outline_tolerance` of it on
+    /// every channel are locked like `locked_colors`, so merging after an
+    /// outline can't fold it into neighboring darks (default: None)
+    #[serde(default)]
+    pub protect_outline_color: Option<(u8, u8, u8)>,
+    /// Per-channel tolerance for `protect_outline_color` (default: 0 - exact)
+    #[serde(default)]
+    pub outline_tolerance: u8,
 }
 
 impl Default for MergeSettings {
     fn default() -> Self {
-        Self { threshold: 3.0 }
+        Self {
+            threshold: 3.0,
+            alpha_weight: None,
+            clear_transparent_rgb: false,
+            max_unique_colors_before_prequantize: None,
+            locked_colors: Vec::new(),
+            protect_outline_color: None,
+            outline_tolerance: 0,
+        }
     }
 }
 
@@ -105,6 +156,23 @@ pub struct OutlineSettings {
     pub thickness: u32,
     /// Alpha threshold for edge detection - pixels <= this are transparent (default: 0)
     pub edge_transparent_cutoff: u8,
+    /// Where the painted color comes from (default: fixed `color`)
+    #[serde(default)]
+    pub color_source: OutlineColorSource,
+    /// Whether `thickness` counts pixels of this image or of the final
+    /// downscaled image (default: source)
+    #[serde(default)]
+    pub thickness_unit: ThicknessUnit,
+    /// Soften stair-step corners by blending partial outline color into
+    /// adjacent transparent pixels (default: false - crisp pixel art)
+    #[serde(default)]
+    pub antialias: bool,
+    /// Place the edge where alpha crosses this midpoint instead of at
+    /// `edge_transparent_cutoff`, so soft (anti-aliased) fringes below it
+    /// count as outside and the outline sits on the perceived silhouette
+    /// (default: None - use the cutoff)
+    #[serde(default)]
+    pub alpha_midpoint: Option<u8>,
 }
 
 impl Default for OutlineSettings {
@@ -114,16 +182,197 @@ impl Default for OutlineSettings {
             connectivity: Connectivity::Four,
             thickness: 1,
             edge_transparent_cutoff: 0,
+            color_source: OutlineColorSource::Fixed,
+            thickness_unit: ThicknessUnit::Source,
+            antialias: false,
+            alpha_midpoint: None,
         }
     }
 }
 
+/// Unit of `OutlineSettings::thickness`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThicknessUnit {
+    /// Pixels of the image being outlined
+    #[default]
+    Source,
+    /// Pixels after the pending downscale; multiplied by the scale factor
+    /// when outlining before downscaling
+    Final,
+}
+
+/// How the outline color is chosen
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutlineColorSource {
+    /// Paint exactly `OutlineSettings::color`
+    #[default]
+    Fixed,
+    /// Snap `target` to the closest (Delta E76) color already in the image,
+    /// so the outline never adds a new color to the palette
+    NearestInPalette { target: (u8, u8, u8) },
+    /// Reuse the most common edge color of a reference ("style") image, so
+    /// a new sprite is outlined the same way as an existing one. Falls back
+    /// to `OutlineSettings::color` if the reference can't be loaded or has
+    /// no edges.
+    FromReference { path: String },
+}
+
+/// Blur kernel shape
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlurKind {
+    /// Uniform weights over `2 * round(radius) + 1` pixels (fast)
+    Box,
+    /// Gaussian weights with sigma = radius
+    Gaussian,
+}
+
+/// Mirror line used by `enforce_symmetry`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymmetryAxis {
+    /// Vertical mirror line: left and right halves mirror each other
+    Vertical,
+    /// Horizontal mirror line: top and bottom halves mirror each other
+    Horizontal,
+}
+
+/// Which half is authoritative when enforcing symmetry
+///
+/// `Left`/`Top` both mean the first half along the axis and `Right`/`Bottom`
+/// the second, so either pair works with either axis.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// Which source pixels a color mapping rule applies to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorMatch {
+    /// Exact RGB match (alpha ignored)
+    Exact { color: (u8, u8, u8) },
+    /// Hue in degrees within `[min_hue, max_hue]`; wraps through 0 when
+    /// `min_hue > max_hue`. Grays have no hue and never match.
+    HueRange { min_hue: f32, max_hue: f32 },
+}
+
+/// Color treatment applied by `tint`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TintMode {
+    /// R = G = B = luma
+    Grayscale,
+    /// Luma mapped onto a fixed brown-to-cream ramp
+    Sepia,
+    /// Luma mapped between two colors (black -> `dark`, white -> `light`)
+    Duotone { dark: (u8, u8, u8), light: (u8, u8, u8) },
+}
+
+/// Seam mismatch between opposite edges of a texture
+#[derive(Debug, Clone, Serialize)]
+pub struct TileReport {
+    /// Mean per-channel RGBA difference between the left and right columns (0-255)
+    pub left_right_mismatch: f32,
+    /// Mean per-channel RGBA difference between the top and bottom rows (0-255)
+    pub top_bottom_mismatch: f32,
+    /// True if both mismatches are below 1.0
+    pub is_tileable: bool,
+}
+
+/// Operations for the V2 pipeline, run in order:
+/// downscale -> alpha -> deartifact -> merge -> outline -> bleed (each optional)
+///
+/// The outline can be moved earlier with `outline_before_downscale` or
+/// `outline_before_alpha`; the former wins if both are set.
+#[derive(Debug, Clone, Default)]
+pub struct PipelineConfig {
+    pub downscale: Option<crate::downscaler::PreviewDownscaleSettings>,
+    pub alpha: Option<AlphaSettings>,
+    pub deartifact_strength: Option<f32>,
+    pub merge: Option<MergeSettings>,
+    pub outline: Option<OutlineSettings>,
+    /// Outline before downscaling, passing the detected scale to the outline
+    /// stage (default: false - outline runs last)
+    pub outline_before_downscale: bool,
+    /// Outline right after downscaling, before alpha normalization, so it
+    /// follows the original soft silhouette (every pixel above the outline's
+    /// `edge_transparent_cutoff`) and is hardened along with the sprite.
+    /// By default the outline traces the already-hardened edge instead
+    /// (default: false)
+    pub outline_before_alpha: bool,
+    /// Refuse inputs over this many megapixels before decoding them, rather
+    /// than risk running out of memory (default: 0 - no limit; see
+    /// `DEFAULT_MAX_MEGAPIXELS`)
+    pub max_megapixels: u32,
+    /// Alpha-bleed iterations applied last, for export (see `bleed_edges`)
+    pub bleed_iterations: Option<u32>,
+    /// Crop to the pixels with alpha above this threshold after every other
+    /// stage but bleed, e.g. to drop margins left by an outward outline
+    /// (default: None - no trim)
+    pub trim_alpha_threshold: Option<u8>,
+}
+
+/// Per-stage wall-clock durations; does nothing when disabled
+pub struct StageTimings(Option<Vec<(String, std::time::Duration)>>);
+
+/// Result of processing and saving an image
+#[derive(Debug, Clone, Serialize)]
+pub struct SaveResult {
+    /// Path written (or that would be written, in a dry run), or None if
+    /// skipped by the overwrite policy
+    pub saved_path: Option<String>,
+    /// Size of the processed image
+    pub final_size: (u32, u32),
+    /// Nothing was written to disk
+    pub dry_run: bool,
+    /// (stage, milliseconds) for each stage that ran, if timings were requested
+    pub timings: Option<Vec<(String, f64)>>,
+    /// Backup of the original, if it was backed up before an in-place write
+    pub backup_path: Option<String>,
+}
+
+/// What to do when the output file already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverwritePolicy {
+    /// Replace the existing file
+    #[default]
+    Overwrite,
+    /// Leave the existing file alone and don't write anything
+    Skip,
+    /// Fail with an error
+    Error,
+    /// Write to `name_1.ext`, `name_2.ext`, ... (first free name)
+    RenameWithSuffix,
+}
+
+/// Options controlling how a processed image is written
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SaveOptions {
+    /// Policy when the output already exists (default: overwrite)
+    #[serde(default)]
+    pub overwrite_policy: OverwritePolicy,
+    /// Allow writing over the input file itself (default: false)
+    #[serde(default)]
+    pub allow_in_place: bool,
+}
+
 /// Result from color merge operation
 #[derive(Debug, Clone, Serialize)]
 pub struct MergeResult {
     pub unique_colors_before: usize,
     pub unique_colors_after: usize,
     pub clusters_created: usize,
+    /// Fallbacks and no-ops worth surfacing (e.g. nothing to merge)
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
 /// Result from outline detection
@@ -163,115 +412,9 @@ pub struct ProcessorResult {
     pub unique_colors_before: usize,
     pub unique_colors_after: usize,
     pub clusters_created: usize,
-}
-
-// ============================================================================
-// COLOR SPACE CONVERSIONS (sRGB <-> XYZ <-> LAB)
-// Exact match to Python's rgb_to_lab() and lab_to_rgb()
-// ============================================================================
-
-/// Convert sRGB (0-255) to LAB color space
-fn rgb_to_lab(r: u8, g: u8, b: u8) -> (f32, f32, f32) {
-    // Step 1: sRGB to Linear RGB (gamma correction)
-    fn srgb_to_linear(c: u8) -> f32 {
-        let c = c as f32 / 255.0;
-        if c <= 0.04045 {
-            c / 12.92
-        } else {
-            ((c + 0.055) / 1.055).powf(2.4)
-        }
-    }
-
-    let rl = srgb_to_linear(r);
-    let gl = srgb_to_linear(g);
-    let bl = srgb_to_linear(b);
-
-    // Step 2: Linear RGB to XYZ (standard matrix)
-    let x = rl * 0.4124564 + gl * 0.3575761 + bl * 0.1804375;
-    let y = rl * 0.2126729 + gl * 0.7151522 + bl * 0.0721750;
-    let z = rl * 0.0193339 + gl * 0.1191920 + bl * 0.9503041;
-
-    // Step 3: XYZ to LAB (D65 illuminant)
-    const XN: f32 = 0.95047;
-    const YN: f32 = 1.00000;
-    const ZN: f32 = 1.08883;
-
-    let xr = x / XN;
-    let yr = y / YN;
-    let zr = z / ZN;
-
-    fn f(t: f32) -> f32 {
-        if t > 0.008856 {
-            t.powf(1.0 / 3.0)
-        } else {
-            7.787 * t + 16.0 / 116.0
-        }
-    }
-
-    let fx = f(xr);
-    let fy = f(yr);
-    let fz = f(zr);
-
-    let l = (116.0 * fy - 16.0).max(0.0);
-    let a = 500.0 * (fx - fy);
-    let lab_b = 200.0 * (fy - fz);
-
-    (l, a, lab_b)
-}
-
-/// Convert LAB color space back to sRGB (0-255)
-fn lab_to_rgb(l: f32, a: f32, b: f32) -> (u8, u8, u8) {
-    // Step 1: LAB to XYZ
-    const XN: f32 = 0.95047;
-    const YN: f32 = 1.00000;
-    const ZN: f32 = 1.08883;
-
-    let fy = (l + 16.0) / 116.0;
-    let fx = a / 500.0 + fy;
-    let fz = fy - b / 200.0;
-
-    fn f_inv(t: f32) -> f32 {
-        let t3 = t * t * t;
-        if t3 > 0.008856 {
-            t3
-        } else {
-            (t - 16.0 / 116.0) / 7.787
-        }
-    }
-
-    let xr = f_inv(fx);
-    let yr = f_inv(fy);
-    let zr = f_inv(fz);
-
-    let x = xr * XN;
-    let y = yr * YN;
-    let z = zr * ZN;
-
-    // Step 2: XYZ to Linear RGB (inverse matrix)
-    let rl = x *  3.2404542 + y * -1.5371385 + z * -0.4985314;
-    let gl = x * -0.9692660 + y *  1.8760108 + z *  0.0415560;
-    let bl = x *  0.0556434 + y * -0.2040259 + z *  1.0572252;
-
-    // Step 3: Linear RGB to sRGB (inverse gamma)
-    fn linear_to_srgb(c: f32) -> u8 {
-        let c = c.clamp(0.0, 1.0);
-        let v = if c <= 0.0031308 {
-            12.92 * c
-        } else {
-            1.055 * c.powf(1.0 / 2.4) - 0.055
-        };
-        (v * 255.0).round().clamp(0.0, 255.0) as u8
-    }
-
-    (linear_to_srgb(rl), linear_to_srgb(gl), linear_to_srgb(bl))
-}
-
-/// Calculate Delta E76 color difference in LAB space
-fn delta_e76(lab1: (f32, f32, f32), lab2: (f32, f32, f32)) -> f32 {
-    let dl = lab1.0 - lab2.0;
-    let da = lab1.1 - lab2.1;
-    let db = lab1.2 - lab2.2;
-    (dl * dl + da * da + db * db).sqrt()
+    /// Fallbacks and no-ops worth surfacing (e.g. outline skipped)
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
 // ============================================================================
@@ -303,24 +446,80 @@ fn normalize_opacity_internal(img: &mut RgbaImage, settings: &ProcessorSettings)
 /// - Alpha < low_cutoff → 0 (fully transparent)
 /// - Alpha >= high_min and <= high_max → 255 (fully opaque)
 ///
+/// With `AlphaMode::Quantize`, every alpha instead snaps to the nearest of
+/// `levels` evenly spaced steps (fewer than 2 levels is treated as 2).
+///
 /// Safe to re-apply: idempotent operation (no change on second application)
 pub fn normalize_alpha(img: &mut RgbaImage, settings: &AlphaSettings) {
+    if let AlphaMode::Quantize { levels } = settings.mode {
+        quantize_alpha(img, levels);
+        return;
+    }
+
     let (width, height) = img.dimensions();
 
     for y in 0..height {
         for x in 0..width {
             let pixel = img.get_pixel_mut(x, y);
-            let alpha = pixel[3];
-
-            if alpha < settings.low_cutoff {
-                pixel[3] = 0;
-            } else if alpha >= settings.high_min && alpha <= settings.high_max {
-                pixel[3] = 255;
-            }
+            pixel[3] = binary_alpha(pixel[3], settings);
         }
     }
 }
 
+/// Binary cutoff for a single alpha value (see `normalize_alpha`)
+fn binary_alpha(alpha: u8, settings: &AlphaSettings) -> u8 {
+    if alpha < settings.low_cutoff {
+        0
+    } else if alpha >= settings.high_min && alpha <= settings.high_max {
+        255
+    } else {
+        alpha
+    }
+}
+
+/// Lookup table snapping alpha to the nearest of `levels` evenly spaced steps
+fn quantize_lut(levels: u8) -> Vec<u8> {
+    let steps = levels.max(2) as f32 - 1.0;
+    (0..=255u32)
+        .map(|a| ((a as f32 / 255.0 * steps).round() / steps * 255.0).round() as u8)
+        .collect()
+}
+
+/// Normalize alpha with thresholds chosen by each pixel's color
+///
+/// Each pixel uses the settings of the first rule whose `ColorMatch` matches
+/// its RGB, or `default` if none do - e.g. harden reds while keeping blues
+/// translucent for glass or water.
+pub fn normalize_alpha_by_color(img: &mut RgbaImage, rules: &[(ColorMatch, AlphaSettings)], default: &AlphaSettings) {
+    let luts: Vec<Option<Vec<u8>>> = rules
+        .iter()
+        .map(|(_, settings)| settings)
+        .chain(std::iter::once(default))
+        .map(|settings| match settings.mode {
+            AlphaMode::Quantize { levels } => Some(quantize_lut(levels)),
+            AlphaMode::Binary => None,
+        })
+        .collect();
+
+    for pixel in img.pixels_mut() {
+        let index = rules.iter().position(|(rule, _)| rule.matches(pixel)).unwrap_or(rules.len());
+        let settings = rules.get(index).map(|(_, s)| s).unwrap_or(default);
+        pixel[3] = match &luts[index] {
+            Some(lut) => lut[pixel[3] as usize],
+            None => binary_alpha(pixel[3], settings),
+        };
+    }
+}
+
+/// Snap each alpha value to the nearest of `levels` evenly spaced steps
+fn quantize_alpha(img: &mut RgbaImage, levels: u8) {
+    let lut = quantize_lut(levels);
+
+    for pixel in img.pixels_mut() {
+        pixel[3] = lut[pixel[3] as usize];
+    }
+}
+
 // ============================================================================
 // STEP 2: COLOR SIMPLIFICATION (LAB Clustering)
 // Exact match to Python lines 91-149
@@ -329,30 +528,48 @@ pub fn normalize_alpha(img: &mut RgbaImage, settings: &AlphaSettings) {
 
 struct LabCluster {
     center_lab: (f32, f32, f32),
+    center_alpha: f32,
     sum_l: f32,
     sum_a: f32,
     sum_b: f32,
+    sum_alpha: f32,
     count: u32,
-    members: Vec<((u8, u8, u8), u32)>,
+    members: Vec<((u8, u8, u8, u8), u32)>,
+    /// Locked clusters hold exactly one color and take no members
+    locked: bool,
 }
 
 impl LabCluster {
-    fn new(rgb: (u8, u8, u8), lab: (f32, f32, f32), count: u32) -> Self {
+    fn new(rgba: (u8, u8, u8, u8), lab: (f32, f32, f32), count: u32) -> Self {
         Self {
             center_lab: lab,
+            center_alpha: rgba.3 as f32,
             sum_l: lab.0 * count as f32,
             sum_a: lab.1 * count as f32,
             sum_b: lab.2 * count as f32,
+            sum_alpha: rgba.3 as f32 * count as f32,
             count,
-            members: vec![(rgb, count)],
+            members: vec![(rgba, count)],
+            locked: false,
         }
     }
 
-    fn add(&mut self, rgb: (u8, u8, u8), lab: (f32, f32, f32), count: u32) {
-        self.members.push((rgb, count));
+    /// Center LAB after adding `count` pixels of `lab`
+    fn center_with(&self, lab: (f32, f32, f32), count: u32) -> (f32, f32, f32) {
+        let total = (self.count + count) as f32;
+        (
+            (self.sum_l + lab.0 * count as f32) / total,
+            (self.sum_a + lab.1 * count as f32) / total,
+            (self.sum_b + lab.2 * count as f32) / total,
+        )
+    }
+
+    fn add(&mut self, rgba: (u8, u8, u8, u8), lab: (f32, f32, f32), count: u32) {
+        self.members.push((rgba, count));
         self.sum_l += lab.0 * count as f32;
         self.sum_a += lab.1 * count as f32;
         self.sum_b += lab.2 * count as f32;
+        self.sum_alpha += rgba.3 as f32 * count as f32;
         self.count += count;
         // Update center (weighted average) - Python lines 116-120
         self.center_lab = (
@@ -360,72 +577,127 @@ impl LabCluster {
             self.sum_a / self.count as f32,
             self.sum_b / self.count as f32,
         );
+        self.center_alpha = self.sum_alpha / self.count as f32;
     }
 }
 
-/// Internal color simplification (returns tuple for legacy API)
-fn simplify_colors_internal(img: &mut RgbaImage, threshold: f32) -> (usize, usize, usize) {
-    let result = merge_colors_impl(img, threshold);
-    (result.unique_colors_before, result.unique_colors_after, result.clusters_created)
+/// Round a channel to the nearest multiple of 16 (255 stays 255)
+fn bin16(v: u8) -> u8 {
+    ((v as u16 + 8) / 16 * 16).min(255) as u8
+}
+
+/// Whether a cluster center at `lab` would render as one of the `locked` colors
+fn drifts_onto_locked(lab: (f32, f32, f32), locked: &[(u8, u8, u8)]) -> bool {
+    !locked.is_empty() && locked.contains(&lab_to_rgb(lab.0, lab.1, lab.2))
 }
 
 /// Core implementation of LAB color clustering
-fn merge_colors_impl(img: &mut RgbaImage, threshold: f32) -> MergeResult {
+///
+/// With `alpha_weight` set, colors are keyed and clustered by RGBA and the
+/// merged pixels take the cluster's average alpha; otherwise alpha is ignored.
+/// With `prequantize_above` set and exceeded by the unique color count, RGB
+/// is binned with `bin16` before clustering. `locked` colors are seeded as
+/// their own clusters first and are never binned, joined or averaged onto.
+fn merge_colors_impl(
+    img: &mut RgbaImage,
+    threshold: f32,
+    alpha_weight: Option<f32>,
+    prequantize_above: Option<usize>,
+    locked: &[(u8, u8, u8)],
+) -> MergeResult {
     let (width, height) = img.dimensions();
+    let exact_key = |p: &Rgba<u8>| (p[0], p[1], p[2], if alpha_weight.is_some() { p[3] } else { 0 });
 
     // Collect unique colors with counts (Python lines 96-102)
-    let mut color_counts: HashMap<(u8, u8, u8), u32> = HashMap::new();
+    let mut color_counts: HashMap<(u8, u8, u8, u8), u32> = HashMap::new();
     for y in 0..height {
         for x in 0..width {
             let pixel = img.get_pixel(x, y);
             if pixel[3] >= 1 {
-                let key = (pixel[0], pixel[1], pixel[2]);
+                let key = exact_key(pixel);
                 *color_counts.entry(key).or_insert(0) += 1;
             }
         }
     }
 
     let unique_before = color_counts.len();
+    let prequantize = prequantize_above.is_some_and(|limit| unique_before > limit);
+    let is_locked = |(r, g, b, _): (u8, u8, u8, u8)| locked.contains(&(r, g, b));
+    let bin_key = |(r, g, b, a): (u8, u8, u8, u8)| {
+        if prequantize && !is_locked((r, g, b, a)) { (bin16(r), bin16(g), bin16(b), a) } else { (r, g, b, a) }
+    };
+    let color_key = |p: &Rgba<u8>| bin_key(exact_key(p));
+    if prequantize {
+        let mut binned: HashMap<(u8, u8, u8, u8), u32> = HashMap::new();
+        for (key, count) in color_counts {
+            *binned.entry(bin_key(key)).or_insert(0) += count;
+        }
+        color_counts = binned;
+    }
     if color_counts.is_empty() {
         return MergeResult {
             unique_colors_before: 0,
             unique_colors_after: 0,
             clusters_created: 0,
+            warnings: vec!["No visible pixels to merge; image unchanged".to_string()],
         };
     }
+    let mut warnings = Vec::new();
+    if prequantize {
+        warnings.push(format!(
+            "{} unique colors exceeds the limit of {}; colors were rounded to a 16-step grid before merging",
+            unique_before,
+            prequantize_above.unwrap_or_default()
+        ));
+    }
 
-    // Sort by frequency descending (Python line 107)
+    // Sort by frequency descending (Python line 107), ties broken by RGB so
+    // equal-frequency colors always seed clusters in the same order
     let mut items: Vec<_> = color_counts.into_iter().collect();
-    items.sort_by(|a, b| b.1.cmp(&a.1));
+    items.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
 
-    // Build LAB clusters using greedy assignment (Python lines 109-132)
-    let mut clusters: Vec<LabCluster> = Vec::new();
+    // Locked colors seed their own clusters before anything else is placed
+    let (locked_items, items): (Vec<_>, Vec<_>) = items.into_iter().partition(|&(rgba, _)| is_locked(rgba));
+    let mut clusters: Vec<LabCluster> = locked_items
+        .into_iter()
+        .map(|(rgba, count)| LabCluster { locked: true, ..LabCluster::new(rgba, rgb_to_lab(rgba.0, rgba.1, rgba.2), count) })
+        .collect();
 
-    for ((r, g, b), count) in items {
-        let lab = rgb_to_lab(r, g, b);
+    // Build LAB clusters using greedy assignment (Python lines 109-132)
+    for (rgba, count) in items {
+        let lab = rgb_to_lab(rgba.0, rgba.1, rgba.2);
         let mut assigned = false;
 
-        for cluster in &mut clusters {
-            if delta_e76(lab, cluster.center_lab) <= threshold {
-                cluster.add((r, g, b), lab, count);
+        for cluster in clusters.iter_mut().filter(|c| !c.locked) {
+            let distance = match alpha_weight {
+                Some(weight) => {
+                    let de = delta_e76(lab, cluster.center_lab);
+                    let da = (rgba.3 as f32 - cluster.center_alpha) / 255.0 * 100.0 * weight;
+                    (de * de + da * da).sqrt()
+                }
+                None => delta_e76(lab, cluster.center_lab),
+            };
+            if distance <= threshold && !drifts_onto_locked(cluster.center_with(lab, count), locked) {
+                cluster.add(rgba, lab, count);
                 assigned = true;
                 break;
             }
         }
 
         if !assigned {
-            clusters.push(LabCluster::new((r, g, b), lab, count));
+            clusters.push(LabCluster::new(rgba, lab, count));
         }
     }
 
     let clusters_created = clusters.len();
 
     // Build color mapping (Python lines 135-139)
-    let mut colormap: HashMap<(u8, u8, u8), (u8, u8, u8)> = HashMap::new();
+    let mut colormap = HashMap::new();
     for cluster in &clusters {
-        let rep = lab_to_rgb(cluster.center_lab.0, cluster.center_lab.1, cluster.center_lab.2);
-        for &(rgb, _) in &cluster.members {
-            colormap.insert(rgb, rep);
+        let (r, g, b) = lab_to_rgb(cluster.center_lab.0, cluster.center_lab.1, cluster.center_lab.2);
+        let a = if alpha_weight.is_some() { cluster.center_alpha.round() as u8 } else { 0 };
+        for &(rgba, _) in &cluster.members {
+            colormap.insert(rgba, (r, g, b, a));
         }
     }
 
@@ -436,20 +708,27 @@ fn merge_colors_impl(img: &mut RgbaImage, threshold: f32) -> MergeResult {
         for x in 0..width {
             let pixel = img.get_pixel_mut(x, y);
             if pixel[3] >= 1 {
-                let key = (pixel[0], pixel[1], pixel[2]);
-                if let Some(&(r, g, b)) = colormap.get(&key) {
+                if let Some(&(r, g, b, a)) = colormap.get(&color_key(pixel)) {
                     pixel[0] = r;
                     pixel[1] = g;
                     pixel[2] = b;
+                    if alpha_weight.is_some() {
+                        pixel[3] = a;
+                    }
                 }
             }
         }
     }
 
+    if clusters_created == unique_before {
+        warnings.push(format!("No colors were within threshold {}; nothing merged", threshold));
+    }
+
     MergeResult {
         unique_colors_before: unique_before,
         unique_colors_after: unique_after,
         clusters_created,
+        warnings,
     }
 }
 
@@ -458,9 +737,338 @@ fn merge_colors_impl(img: &mut RgbaImage, threshold: f32) -> MergeResult {
 /// Uses greedy first-fit assignment with Delta E76 distance metric.
 /// Colors within `threshold` distance are merged to their weighted average.
 ///
+/// With `alpha_weight` set, alpha joins the distance so same-RGB colors with
+/// different opacity stay distinct. `clear_transparent_rgb` zeroes the stale
+/// RGB left in fully transparent pixels. `locked_colors`, and pixels near
+/// `protect_outline_color`, are left exactly as they are.
+///
 /// Safe to re-apply: progressive simplification (may reduce colors further each time)
 pub fn merge_colors(img: &mut RgbaImage, settings: &MergeSettings) -> MergeResult {
-    merge_colors_impl(img, settings.threshold)
+    if is_empty_or_tiny(img) {
+        return MergeResult {
+            unique_colors_before: 0,
+            unique_colors_after: 0,
+            clusters_created: 0,
+            warnings: vec!["Image is empty or fully transparent; nothing to merge".to_string()],
+        };
+    }
+    let mut locked = settings.locked_colors.clone();
+    if let Some(outline) = settings.protect_outline_color {
+        let tolerance = settings.outline_tolerance;
+        let near = |p: &Rgba<u8>| {
+            p[0].abs_diff(outline.0) <= tolerance
+                && p[1].abs_diff(outline.1) <= tolerance
+                && p[2].abs_diff(outline.2) <= tolerance
+        };
+        for p in img.pixels().filter(|p| p[3] > 0 && near(p)) {
+            if !locked.contains(&(p[0], p[1], p[2])) {
+                locked.push((p[0], p[1], p[2]));
+            }
+        }
+    }
+    let result = merge_colors_impl(
+        img,
+        settings.threshold,
+        settings.alpha_weight,
+        settings.max_unique_colors_before_prequantize,
+        &locked,
+    );
+    if settings.clear_transparent_rgb {
+        for pixel in img.pixels_mut() {
+            if pixel[3] == 0 {
+                *pixel = Rgba([0, 0, 0, 0]);
+            }
+        }
+    }
+    result
+}
+
+/// Largest Delta E threshold tried when searching for a color count
+const MAX_SEARCH_THRESHOLD: f32 = 100.0;
+
+/// Bisection steps when searching for a color count
+const THRESHOLD_SEARCH_STEPS: u32 = 16;
+
+/// Find the merge threshold whose result has the unique color count closest
+/// to `target_colors`, returning the merged image, its result and the threshold
+///
+/// Bisects on the threshold (a larger threshold never leaves more colors,
+/// give or take greedy clustering order) and keeps the closest attempt.
+fn search_merge_threshold(img: &RgbaImage, target_colors: usize) -> (RgbaImage, MergeResult, f32) {
+    let attempt = |threshold: f32| {
+        let mut candidate = img.clone();
+        let settings = MergeSettings { threshold, ..MergeSettings::default() };
+        let result = merge_colors(&mut candidate, &settings);
+        (candidate, result, threshold)
+    };
+    let miss = |result: &MergeResult| result.unique_colors_after.abs_diff(target_colors);
+
+    let mut best = attempt(0.0);
+    let (mut low, mut high) = (0.0f32, MAX_SEARCH_THRESHOLD);
+    for _ in 0..THRESHOLD_SEARCH_STEPS {
+        if miss(&best.1) == 0 {
+            break;
+        }
+        let mid = (low + high) / 2.0;
+        let candidate = attempt(mid);
+        if candidate.1.unique_colors_after > target_colors {
+            low = mid;
+        } else {
+            high = mid;
+        }
+        if miss(&candidate.1) < miss(&best.1) {
+            best = candidate;
+        }
+    }
+    best
+}
+
+/// Merge colors until about `target_fraction` of the unique colors remain
+///
+/// A friendlier knob than a raw Delta E threshold: 0.5 means "halve the
+/// palette". The threshold is searched for, so the result lands as close to
+/// the target as the image's colors allow; a note with the threshold used
+/// (and the shortfall, if any) is added to the warnings. An image already at
+/// or below the target (including one with no visible pixels) is left as is.
+pub fn merge_to_reduction(img: &mut RgbaImage, target_fraction: f32) -> MergeResult {
+    let unique_before = img
+        .pixels()
+        .filter(|p| p[3] >= 1)
+        .map(|p| (p[0], p[1], p[2]))
+        .collect::<HashSet<_>>()
+        .len();
+    let target_colors = ((unique_before as f32 * target_fraction.clamp(0.0, 1.0)).round() as usize).max(1);
+    if unique_before <= target_colors {
+        let note = if unique_before == 0 {
+            "No visible pixels to merge; image unchanged".to_string()
+        } else {
+            format!("{} colors already within the target of {}; image unchanged", unique_before, target_colors)
+        };
+        return MergeResult {
+            unique_colors_before: unique_before,
+            unique_colors_after: unique_before,
+            clusters_created: 0,
+            warnings: vec![note],
+        };
+    }
+
+    let (merged, mut result, threshold) = search_merge_threshold(img, target_colors);
+    *img = merged;
+    result.warnings.push(format!(
+        "Merged at threshold {:.2}: {} of {} colors remain (target {})",
+        threshold, result.unique_colors_after, result.unique_colors_before, target_colors
+    ));
+    result
+}
+
+// ============================================================================
+// COMPRESSION ARTIFACT CLEANUP
+// ============================================================================
+
+/// Snap low-amplitude color noise to the dominant color of its neighborhood
+///
+/// A visible pixel is replaced by the most common color among its 8 neighbors
+/// only when that color holds a strict majority of the visible neighbors and
+/// lies within `strength` Delta E76 of the pixel. Real detail (a distinct
+/// color, or a pixel with no clear local majority) is left alone, so edges
+/// keep their position. Runs a single pass over an unmodified copy.
+///
+/// Returns the number of pixels changed.
+pub fn deartifact(img: &mut RgbaImage, strength: f32) -> usize {
+    let (width, height) = img.dimensions();
+    if strength <= 0.0 {
+        return 0;
+    }
+
+    let source = img.clone();
+    let mut changed = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = *source.get_pixel(x, y);
+            if pixel[3] == 0 {
+                continue;
+            }
+
+            let mut counts: HashMap<Rgba<u8>, usize> = HashMap::new();
+            let mut visible = 0;
+            for (nx, ny) in get_neighbors(x, y, width, height, &Connectivity::Eight) {
+                let n = *source.get_pixel(nx, ny);
+                if n[3] > 0 {
+                    visible += 1;
+                    *counts.entry(n).or_insert(0) += 1;
+                }
+            }
+
+            let (mode, count) = match counts.into_iter().max_by_key(|&(p, count)| (count, p.0)) {
+                Some(m) => m,
+                None => continue,
+            };
+            if mode == pixel || count * 2 <= visible {
+                continue;
+            }
+
+            let distance = delta_e76(
+                rgb_to_lab(pixel[0], pixel[1], pixel[2]),
+                rgb_to_lab(mode[0], mode[1], mode[2]),
+            );
+            if distance <= strength {
+                img.put_pixel(x, y, Rgba([mode[0], mode[1], mode[2], pixel[3]]));
+                changed += 1;
+            }
+        }
+    }
+
+    changed
+}
+
+// ============================================================================
+// REGION FLATTENING
+// ============================================================================
+
+/// Repaint each connected region of similar color with its average color
+///
+/// Regions grow by flood fill over `connectivity` neighbors: a visible
+/// neighbor joins when it is within `delta_e` (Delta E76) of the pixel it was
+/// reached from. With `Eight`, a thin diagonal line stays one region. Unlike
+/// `merge_colors`, two separate areas of the same noisy color are flattened
+/// independently. Alpha is preserved. Returns the number of regions found.
+pub fn flatten_regions(img: &mut RgbaImage, delta_e: f32, connectivity: &Connectivity) -> usize {
+    let (width, height) = img.dimensions();
+    let labs: Vec<(f32, f32, f32)> = img.pixels().map(|p| rgb_to_lab(p[0], p[1], p[2])).collect();
+    let mut visited = vec![false; labs.len()];
+    let mut regions = 0;
+
+    for start in 0..labs.len() {
+        let (sx, sy) = (start as u32 % width, start as u32 / width);
+        if visited[start] || img.get_pixel(sx, sy)[3] == 0 {
+            continue;
+        }
+        visited[start] = true;
+        regions += 1;
+
+        let mut members = vec![start];
+        let mut stack = vec![start];
+        while let Some(idx) = stack.pop() {
+            let (x, y) = (idx as u32 % width, idx as u32 / width);
+            for (nx, ny) in get_neighbors(x, y, width, height, connectivity) {
+                let n = (ny * width + nx) as usize;
+                if !visited[n] && img.get_pixel(nx, ny)[3] > 0 && delta_e76(labs[idx], labs[n]) <= delta_e {
+                    visited[n] = true;
+                    members.push(n);
+                    stack.push(n);
+                }
+            }
+        }
+
+        let count = members.len() as f32;
+        let (sum_l, sum_a, sum_b) = members.iter().fold((0.0, 0.0, 0.0), |acc, &i| {
+            (acc.0 + labs[i].0, acc.1 + labs[i].1, acc.2 + labs[i].2)
+        });
+        let (r, g, b) = lab_to_rgb(sum_l / count, sum_a / count, sum_b / count);
+        for &i in &members {
+            let pixel = img.get_pixel_mut(i as u32 % width, i as u32 / width);
+            *pixel = Rgba([r, g, b, pixel[3]]);
+        }
+    }
+
+    regions
+}
+
+// ============================================================================
+// CONNECTED COMPONENTS
+// ============================================================================
+
+/// Label the connected components of a mask
+///
+/// Pixels where `mask` is non-zero are grouped by `connectivity`; with
+/// `Eight`, diagonal neighbors join, so a thin diagonal line stays one
+/// component. Returns row-major labels (0 outside the mask, components
+/// numbered from 1 in scan order of their first pixel) and the number of
+/// components.
+pub fn label_components(mask: &GrayImage, connectivity: &Connectivity) -> (Vec<u32>, u32) {
+    let (width, height) = mask.dimensions();
+    let inside: Vec<bool> = mask.pixels().map(|p| p[0] > 0).collect();
+    let mut labels = vec![0u32; inside.len()];
+    let mut count = 0;
+
+    for start in 0..inside.len() {
+        if !inside[start] || labels[start] != 0 {
+            continue;
+        }
+        count += 1;
+        labels[start] = count;
+
+        let mut stack = vec![start];
+        while let Some(idx) = stack.pop() {
+            let (x, y) = (idx as u32 % width, idx as u32 / width);
+            for (nx, ny) in get_neighbors(x, y, width, height, connectivity) {
+                let n = (ny * width + nx) as usize;
+                if inside[n] && labels[n] == 0 {
+                    labels[n] = count;
+                    stack.push(n);
+                }
+            }
+        }
+    }
+
+    (labels, count)
+}
+
+// ============================================================================
+// PALETTE REMAP
+// ============================================================================
+
+/// Snap every visible pixel to the nearest (Delta E76) color in `reference`
+///
+/// The reference palette is its set of unique visible RGB colors. Alpha is
+/// preserved. Does nothing if the reference has no visible pixels.
+pub fn remap_to_reference(img: &mut RgbaImage, reference: &RgbaImage) {
+    let palette: Vec<_> = reference
+        .pixels()
+        .filter(|p| p[3] > 0)
+        .map(|p| (p[0], p[1], p[2], p[3]))
+        .collect();
+    remap_to_palette(img, &palette);
+}
+
+/// Snap every visible pixel to the nearest (Delta E76) color in `palette`
+///
+/// Palette entries are matched by RGB; fully transparent entries are ignored
+/// and the image's alpha is preserved. Does nothing for an empty palette.
+pub fn remap_to_palette(img: &mut RgbaImage, palette: &[(u8, u8, u8, u8)]) {
+    let palette: Vec<_> = palette
+        .iter()
+        .filter(|c| c.3 > 0)
+        .map(|c| (c.0, c.1, c.2))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .map(|c| (c, rgb_to_lab(c.0, c.1, c.2)))
+        .collect();
+
+    if palette.is_empty() {
+        return;
+    }
+
+    let mut cache: HashMap<(u8, u8, u8), (u8, u8, u8)> = HashMap::new();
+    for pixel in img.pixels_mut() {
+        if pixel[3] == 0 {
+            continue;
+        }
+        let key = (pixel[0], pixel[1], pixel[2]);
+        let (r, g, b) = *cache.entry(key).or_insert_with(|| {
+            let lab = rgb_to_lab(key.0, key.1, key.2);
+            palette
+                .iter()
+                .map(|(c, c_lab)| (delta_e76(lab, *c_lab), *c))
+                .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap().then(a.1.cmp(&b.1)))
+                .map(|(_, c)| c)
+                .unwrap()
+        });
+        pixel[0] = r;
+        pixel[1] = g;
+        pixel[2] = b;
+    }
 }
 
 // ============================================================================
@@ -468,7 +1076,7 @@ pub fn merge_colors(img: &mut RgbaImage, settings: &MergeSettings) -> MergeResul
 // Exact match to Python lines 151-202 (frontier queue, grows inward)
 // ============================================================================
 
-fn get_neighbors(x: u32, y: u32, width: u32, height: u32, connectivity: &Connectivity) -> Vec<(u32, u32)> {
+pub(crate) fn get_neighbors(x: u32, y: u32, width: u32, height: u32, connectivity: &Connectivity) -> Vec<(u32, u32)> {
     let mut neighbors = Vec::new();
 
     match connectivity {
@@ -501,10 +1109,61 @@ fn generate_outline_internal(img: &mut RgbaImage, settings: &ProcessorSettings)
         connectivity: settings.outline_connectivity.clone(),
         thickness: settings.outline_thickness,
         edge_transparent_cutoff: settings.edge_transparent_cutoff,
+        color_source: OutlineColorSource::Fixed,
+        thickness_unit: ThicknessUnit::Source,
+        antialias: false,
+        alpha_midpoint: None,
     };
     add_outline(img, &outline_settings);
 }
 
+/// Resolve the RGBA color `add_outline` will paint
+///
+/// For `NearestInPalette`, picks the visible image color closest to the target
+/// (falling back to the target itself for an empty image). For
+/// `FromReference`, loads the reference and takes its dominant edge color.
+/// Alpha always comes from `settings.color`.
+pub fn resolve_outline_color(img: &RgbaImage, settings: &OutlineSettings) -> Rgba<u8> {
+    let alpha = settings.color.3;
+    match &settings.color_source {
+        OutlineColorSource::Fixed => Rgba([settings.color.0, settings.color.1, settings.color.2, alpha]),
+        OutlineColorSource::NearestInPalette { target } => {
+            let palette: HashSet<(u8, u8, u8)> = img
+                .pixels()
+                .filter(|p| p[3] > settings.edge_transparent_cutoff)
+                .map(|p| (p[0], p[1], p[2]))
+                .collect();
+            let target_lab = rgb_to_lab(target.0, target.1, target.2);
+            let nearest = palette
+                .into_iter()
+                .map(|c| (delta_e76(rgb_to_lab(c.0, c.1, c.2), target_lab), c))
+                .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap().then(a.1.cmp(&b.1)))
+                .map(|(_, c)| c)
+                .unwrap_or(*target);
+            Rgba([nearest.0, nearest.1, nearest.2, alpha])
+        }
+        OutlineColorSource::FromReference { path } => {
+            let (r, g, b, _) = load_image(Path::new(path))
+                .ok()
+                .and_then(|reference| dominant_edge_color(&reference))
+                .map(|(color, _)| color)
+                .unwrap_or(settings.color);
+            Rgba([r, g, b, alpha])
+        }
+    }
+}
+
+/// Outline color of a reference ("style") image: its most common edge color
+///
+/// Uses the same edge analysis as `detect_outline`, but returns the dominant
+/// color even when it falls short of the confidence needed to call it an
+/// outline. A reference with no visible edge yields the default outline color.
+pub fn outline_color_from_reference(reference: &RgbaImage) -> (u8, u8, u8, u8) {
+    dominant_edge_color(reference)
+        .map(|(color, _)| color)
+        .unwrap_or(OutlineSettings::default().color)
+}
+
 /// Add outline/border around sprite (grows inward from edges)
 ///
 /// Uses frontier queue algorithm:
@@ -515,35 +1174,111 @@ fn generate_outline_internal(img: &mut RgbaImage, settings: &ProcessorSettings)
 /// **Warning**: Applying outline to an already-outlined image creates double-outline artifacts.
 /// Use `detect_outline()` first to check if image already has an outline.
 pub fn add_outline(img: &mut RgbaImage, settings: &OutlineSettings) {
-    let (width, height) = img.dimensions();
-    let edge_cutoff = settings.edge_transparent_cutoff;
-    let connectivity = &settings.connectivity;
-    let thickness = settings.thickness;
+    add_outline_scaled(img, settings, 1);
+}
 
-    if thickness == 0 {
+/// Add outline to an image that will later be downscaled by `pending_scale`
+///
+/// With `ThicknessUnit::Final` the thickness is multiplied by `pending_scale`
+/// so a 1px final outline survives the downscale as exactly 1px.
+pub fn add_outline_scaled(img: &mut RgbaImage, settings: &OutlineSettings, pending_scale: u32) {
+    if is_empty_or_tiny(img) {
         return;
     }
+    match settings.thickness_unit {
+        ThicknessUnit::Source => outline_with_background(img, settings, None, None),
+        ThicknessUnit::Final => {
+            let scaled = OutlineSettings {
+                thickness: settings.thickness * pending_scale.max(1),
+                ..settings.clone()
+            };
+            outline_with_background(img, &scaled, None, None);
+        }
+    }
+}
 
-    // Extract alpha channel (Python line 158)
-    let alpha: Vec<Vec<u8>> = (0..height)
-        .map(|y| (0..width).map(|x| img.get_pixel(x, y)[3]).collect())
-        .collect();
-
-    // Build outline mask (Python line 161)
-    let mut mask: Vec<Vec<bool>> = vec![vec![false; width as usize]; height as usize];
+/// Add outline only along edges that border removed background
+///
+/// `background` is a mask the same size as `img` where non-zero marks pixels
+/// cleared by background removal. Transparent pixels that were already
+/// transparent in the original art (internal cutouts) don't start an outline,
+/// so the outline stays on the sprite's silhouette.
+pub fn add_outline_masked(img: &mut RgbaImage, settings: &OutlineSettings, background: &GrayImage) -> Result<()> {
+    if background.dimensions() != img.dimensions() {
+        return Err(PixelsError::InvalidParameter(format!(
+            "Background mask is {}x{} but image is {}x{}",
+            background.width(),
+            background.height(),
+            img.width(),
+            img.height()
+        )));
+    }
+    outline_with_background(img, settings, Some(background), None);
+    Ok(())
+}
 
-    // Find border pixels (Python lines 177-186)
+/// Add outline along the boundary of a separate silhouette mask
+///
+/// `mask` is the same size as `img`; its values stand in for the image's
+/// alpha (compared against `edge_transparent_cutoff`), so the outline traces
+/// the mask's inside/outside boundary - e.g. just an armor region - while
+/// the color is painted into `img`. The image's own alpha is ignored.
+pub fn add_outline_from_mask(img: &mut RgbaImage, mask: &GrayImage, settings: &OutlineSettings) -> Result<()> {
+    if mask.dimensions() != img.dimensions() {
+        return Err(PixelsError::InvalidParameter(format!(
+            "Silhouette mask is {}x{} but image is {}x{}",
+            mask.width(),
+            mask.height(),
+            img.width(),
+            img.height()
+        )));
+    }
+    outline_with_background(img, settings, None, Some(mask));
+    Ok(())
+}
+
+/// Shared outline implementation; `silhouette` replaces the image's alpha
+/// as the shape being outlined
+fn outline_with_background(
+    img: &mut RgbaImage,
+    settings: &OutlineSettings,
+    background: Option<&GrayImage>,
+    silhouette: Option<&GrayImage>,
+) {
+    let (width, height) = img.dimensions();
+    // Alpha at or below the cutoff is outside; a midpoint of 128 means 127
+    let edge_cutoff = settings.alpha_midpoint.map_or(settings.edge_transparent_cutoff, |m| m.saturating_sub(1));
+    let connectivity = &settings.connectivity;
+    let thickness = settings.thickness;
+
+    if thickness == 0 {
+        return;
+    }
+
+    // Extract alpha channel (Python line 158)
+    let alpha: Vec<u8> = match silhouette {
+        Some(mask) => mask.pixels().map(|p| p[0]).collect(),
+        None => img.pixels().map(|p| p[3]).collect(),
+    };
+
+    // Build outline mask (Python line 161), row-major like `alpha`
+    let mut mask = vec![false; alpha.len()];
+
+    // Find border pixels (Python lines 177-186)
     let mut frontier: Vec<(u32, u32)> = Vec::new();
 
     for y in 0..height {
         for x in 0..width {
-            if alpha[y as usize][x as usize] > edge_cutoff {
+            if alpha[(y * width + x) as usize] > edge_cutoff {
                 let is_border = get_neighbors(x, y, width, height, connectivity)
                     .iter()
-                    .any(|&(nx, ny)| alpha[ny as usize][nx as usize] <= edge_cutoff);
+                    .any(|&(nx, ny)| {
+                        alpha[(ny * width + nx) as usize] <= edge_cutoff
+                            && background.is_none_or(|m| m.get_pixel(nx, ny)[0] > 0)
+                    });
 
                 if is_border {
-                    mask[y as usize][x as usize] = true;
+                    mask[(y * width + x) as usize] = true;
                     frontier.push((x, y));
                 }
             }
@@ -556,10 +1291,10 @@ pub fn add_outline(img: &mut RgbaImage, settings: &OutlineSettings) {
 
         for &(x, y) in &frontier {
             for (nx, ny) in get_neighbors(x, y, width, height, connectivity) {
-                if alpha[ny as usize][nx as usize] > edge_cutoff
-                    && !mask[ny as usize][nx as usize]
+                if alpha[(ny * width + nx) as usize] > edge_cutoff
+                    && !mask[(ny * width + nx) as usize]
                 {
-                    mask[ny as usize][nx as usize] = true;
+                    mask[(ny * width + nx) as usize] = true;
                     new_frontier.push((nx, ny));
                 }
             }
@@ -569,26 +1304,55 @@ pub fn add_outline(img: &mut RgbaImage, settings: &OutlineSettings) {
     }
 
     // Apply outline color (Python lines 199-202)
-    let outline_rgba = Rgba([
-        settings.color.0,
-        settings.color.1,
-        settings.color.2,
-        settings.color.3,
-    ]);
+    let outline_rgba = resolve_outline_color(img, settings);
 
     for y in 0..height {
         for x in 0..width {
-            if mask[y as usize][x as usize] {
+            if mask[(y * width + x) as usize] {
                 img.put_pixel(x, y, outline_rgba);
             }
         }
     }
+
+    if settings.antialias {
+        antialias_outline(img, &mask, &alpha, edge_cutoff, outline_rgba);
+    }
+}
+
+/// Blend partial outline color into transparent pixels sitting in the
+/// notches of a stair-stepped outline
+///
+/// A transparent pixel with at least two outline pixels among its four
+/// orthogonal neighbors (an inner corner of a diagonal) gets the outline
+/// color at `count / 4` of the outline's alpha. Pixels along straight edges
+/// have only one such neighbor and stay transparent.
+fn antialias_outline(img: &mut RgbaImage, mask: &[bool], alpha: &[u8], edge_cutoff: u8, color: Rgba<u8>) {
+    let (width, height) = img.dimensions();
+
+    for y in 0..height {
+        for x in 0..width {
+            if alpha[(y * width + x) as usize] > edge_cutoff {
+                continue;
+            }
+            let count = get_neighbors(x, y, width, height, &Connectivity::Four)
+                .iter()
+                .filter(|&&(nx, ny)| mask[(ny * width + nx) as usize])
+                .count() as u32;
+            if count >= 2 {
+                let a = (color[3] as u32 * count / 4) as u8;
+                img.put_pixel(x, y, Rgba([color[0], color[1], color[2], a]));
+            }
+        }
+    }
 }
 
 // ============================================================================
 // OUTLINE DETECTION (V2)
 // ============================================================================
 
+/// Delta E76 within which an edge pixel counts as the "same" outline color
+const OUTLINE_MATCH_DELTA_E: f32 = 5.0;
+
 /// Detect if an image already has an outline
 ///
 /// Scans edge pixels (opaque pixels adjacent to transparent) and checks
@@ -598,16 +1362,53 @@ pub fn add_outline(img: &mut RgbaImage, settings: &OutlineSettings) {
 /// Returns detection result with confidence score. Use this before `add_outline`
 /// to warn users about potential double-outline artifacts.
 pub fn detect_outline(img: &RgbaImage) -> OutlineDetectionResult {
-    let (width, height) = img.dimensions();
+    let edge_colors = edge_pixel_colors(img);
+    let edge_count = edge_colors.len();
 
-    if width == 0 || height == 0 {
+    let Some((most_common_color, most_common_count)) = most_common_color(&edge_colors) else {
         return OutlineDetectionResult {
             has_outline: false,
             outline_color: None,
             confidence: 0.0,
             edge_pixel_count: 0,
         };
+    };
+
+    // Calculate confidence: what percentage of edge pixels match the most common color?
+    let confidence = most_common_count as f32 / edge_count as f32;
+
+    // Also count colors within small Delta E distance (allow slight variations)
+    let most_common_lab = rgb_to_lab(most_common_color.0, most_common_color.1, most_common_color.2);
+    let similar_count: usize = edge_colors
+        .iter()
+        .filter(|c| {
+            let lab = rgb_to_lab(c.0, c.1, c.2);
+            delta_e76(lab, most_common_lab) <= OUTLINE_MATCH_DELTA_E
+        })
+        .count();
+
+    let similar_confidence = similar_count as f32 / edge_count as f32;
+    let final_confidence = similar_confidence.max(confidence);
+
+    // Consider it an outline if >80% of edge pixels are the same/similar color
+    let has_outline = final_confidence >= 0.80;
+
+    OutlineDetectionResult {
+        has_outline,
+        outline_color: if has_outline { Some(most_common_color) } else { None },
+        confidence: final_confidence,
+        edge_pixel_count: edge_count,
     }
+}
+
+/// Most common edge color and how many edge pixels have it
+fn dominant_edge_color(img: &RgbaImage) -> Option<((u8, u8, u8, u8), usize)> {
+    most_common_color(&edge_pixel_colors(img))
+}
+
+/// Colors of the visible pixels touching transparency or the image border
+fn edge_pixel_colors(img: &RgbaImage) -> Vec<(u8, u8, u8, u8)> {
+    let (width, height) = img.dimensions();
 
     // Collect edge pixels (opaque pixels adjacent to transparent)
     let mut edge_colors: Vec<(u8, u8, u8, u8)> = Vec::new();
@@ -641,186 +1442,3168 @@ pub fn detect_outline(img: &RgbaImage) -> OutlineDetectionResult {
         }
     }
 
-    let edge_count = edge_colors.len();
-
-    if edge_count == 0 {
-        return OutlineDetectionResult {
-            has_outline: false,
-            outline_color: None,
-            confidence: 0.0,
-            edge_pixel_count: 0,
-        };
-    }
+    edge_colors
+}
 
-    // Count color occurrences
+/// Most frequent color and its count (None if `colors` is empty)
+///
+/// Ties are broken by RGBA so the result doesn't depend on HashMap
+/// iteration order.
+fn most_common_color(colors: &[(u8, u8, u8, u8)]) -> Option<((u8, u8, u8, u8), usize)> {
     let mut color_counts: HashMap<(u8, u8, u8, u8), usize> = HashMap::new();
-    for color in &edge_colors {
+    for color in colors {
         *color_counts.entry(*color).or_insert(0) += 1;
     }
 
-    // Find most common edge color
-    let (most_common_color, most_common_count) = color_counts
-        .iter()
-        .max_by_key(|(_, count)| *count)
-        .map(|(color, count)| (*color, *count))
-        .unwrap();
-
-    // Calculate confidence: what percentage of edge pixels match the most common color?
-    let confidence = most_common_count as f32 / edge_count as f32;
-
-    // Also count colors within small Delta E distance (allow slight variations)
-    let most_common_lab = rgb_to_lab(most_common_color.0, most_common_color.1, most_common_color.2);
-    let similar_count: usize = edge_colors
-        .iter()
-        .filter(|c| {
-            let lab = rgb_to_lab(c.0, c.1, c.2);
-            delta_e76(lab, most_common_lab) <= 5.0 // Tight threshold for "same" color
-        })
-        .count();
-
-    let similar_confidence = similar_count as f32 / edge_count as f32;
-    let final_confidence = similar_confidence.max(confidence);
-
-    // Consider it an outline if >80% of edge pixels are the same/similar color
-    let has_outline = final_confidence >= 0.80;
-
-    OutlineDetectionResult {
-        has_outline,
-        outline_color: if has_outline { Some(most_common_color) } else { None },
-        confidence: final_confidence,
-        edge_pixel_count: edge_count,
-    }
+    color_counts
+        .into_iter()
+        .max_by_key(|&(color, count)| (count, color))
 }
 
 // ============================================================================
-// MAIN ENTRY POINT
+// OUTLINE THINNING
 // ============================================================================
 
-/// Legacy entry point - processes image file with all operations
+/// Compute each pixel's ring depth measured inward from transparency
 ///
-/// This function is retained for backward compatibility with existing v1 UI.
-/// For v2, use the individual operations: `normalize_alpha`, `merge_colors`, `add_outline`
-pub fn process_image(
-    input_path: PathBuf,
-    output_path: PathBuf,
-    settings: ProcessorSettings,
-) -> Result<ProcessorResult> {
-    // Load image
-    let img = image::open(&input_path)
-        .map_err(|e| PixelsError::Processing(format!("Failed to load {}: {}", input_path.display(), e)))?;
+/// Transparent pixels (alpha <= edge_cutoff) have depth 0, border pixels
+/// depth 1, the next ring inward depth 2, and so on. Row-major, indexed by
+/// `y * width + x`.
+fn ring_depths(img: &RgbaImage, edge_cutoff: u8, connectivity: &Connectivity) -> Vec<u32> {
+    let (width, height) = img.dimensions();
+    let mut depth = vec![u32::MAX; width as usize * height as usize];
+    let mut queue: VecDeque<(u32, u32)> = VecDeque::new();
 
-    let mut rgba = img.to_rgba8();
-    let original_size = rgba.dimensions();
+    for y in 0..height {
+        for x in 0..width {
+            if img.get_pixel(x, y)[3] <= edge_cutoff {
+                depth[(y * width + x) as usize] = 0;
+                queue.push_back((x, y));
+            }
+        }
+    }
 
-    // Step 1: Opacity normalization (always runs)
-    normalize_opacity_internal(&mut rgba, &settings);
+    while let Some((x, y)) = queue.pop_front() {
+        let d = depth[(y * width + x) as usize];
+        for (nx, ny) in get_neighbors(x, y, width, height, connectivity) {
+            if depth[(ny * width + nx) as usize] == u32::MAX {
+                depth[(ny * width + nx) as usize] = d + 1;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
 
-    // Step 2: Color simplification (if enabled)
-    let (colors_before, colors_after, clusters) = if settings.enable_color_simplify {
-        simplify_colors_internal(&mut rgba, settings.lab_merge_threshold)
-    } else {
-        (0, 0, 0)
+    depth
+}
+
+/// Reduce a thick uniform outline band to `target_thickness` rings
+///
+/// Detects the outline color with `detect_outline()`, measures how many rings
+/// (counted inward from transparency) are mostly that color, then recolors the
+/// inner rings beyond `target_thickness` from the adjacent interior pixels.
+/// Only pixels matching the detected outline color are touched, so interior
+/// art that happens to sit inside the band is left alone.
+///
+/// Returns the number of pixels changed (0 if no outline was detected or the
+/// band is already thin enough).
+pub fn thin_outline(img: &mut RgbaImage, target_thickness: u32, settings: &OutlineSettings) -> u32 {
+    let (width, height) = img.dimensions();
+    let detection = detect_outline(img);
+    let outline_color = match detection.outline_color {
+        Some(color) => color,
+        None => return 0,
+    };
+    let outline_lab = rgb_to_lab(outline_color.0, outline_color.1, outline_color.2);
+    let is_outline = |p: &Rgba<u8>| {
+        p[3] > settings.edge_transparent_cutoff
+            && delta_e76(rgb_to_lab(p[0], p[1], p[2]), outline_lab) <= OUTLINE_MATCH_DELTA_E
     };
 
-    // Step 3: Outline generation (if enabled and thickness > 0)
-    if settings.enable_outline && settings.outline_thickness > 0 {
-        generate_outline_internal(&mut rgba, &settings);
+    let depth = ring_depths(img, settings.edge_transparent_cutoff, &settings.connectivity);
+
+    // Group pixels by ring
+    let mut rings: Vec<Vec<(u32, u32)>> = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let d = depth[(y * width + x) as usize];
+            if d == 0 || d == u32::MAX {
+                continue;
+            }
+            let idx = (d - 1) as usize;
+            if rings.len() <= idx {
+                rings.resize(idx + 1, Vec::new());
+            }
+            rings[idx].push((x, y));
+        }
     }
 
-    // Ensure output directory exists
-    if let Some(parent) = output_path.parent() {
-        std::fs::create_dir_all(parent)?;
+    // Band thickness = consecutive rings that are mostly outline-colored
+    let mut band = 0u32;
+    for ring in &rings {
+        let matching = ring.iter().filter(|&&(x, y)| is_outline(img.get_pixel(x, y))).count();
+        if ring.is_empty() || (matching as f32 / ring.len() as f32) < 0.80 {
+            break;
+        }
+        band += 1;
     }
 
-    // Save result
-    rgba.save(&output_path)?;
+    if band <= target_thickness {
+        return 0;
+    }
 
-    Ok(ProcessorResult {
-        original_size,
-        unique_colors_before: colors_before,
-        unique_colors_after: colors_after,
-        clusters_created: clusters,
-    })
+    // Recolor innermost rings first so each ring can borrow from the one inside it
+    let mut changed = 0u32;
+    for d in ((target_thickness + 1)..=band).rev() {
+        let mut pending: Vec<(u32, u32)> = rings[(d - 1) as usize]
+            .iter()
+            .copied()
+            .filter(|&(x, y)| is_outline(img.get_pixel(x, y)))
+            .collect();
+
+        // Repeat so pixels without a deeper neighbor (ring corners) pick up
+        // colors from ring-mates recolored in the previous pass
+        loop {
+            let mut remaining = Vec::new();
+            let mut updates = Vec::new();
+
+            for &(x, y) in &pending {
+                let mut counts: HashMap<Rgba<u8>, usize> = HashMap::new();
+                for (nx, ny) in get_neighbors(x, y, width, height, &Connectivity::Eight) {
+                    let p = img.get_pixel(nx, ny);
+                    if depth[(ny * width + nx) as usize] >= d && !is_outline(p) {
+                        *counts.entry(*p).or_insert(0) += 1;
+                    }
+                }
+                match counts.into_iter().max_by_key(|&(p, count)| (count, p.0)) {
+                    Some((fill, _)) => updates.push((x, y, fill)),
+                    None => remaining.push((x, y)),
+                }
+            }
+
+            if updates.is_empty() {
+                break;
+            }
+            for (x, y, fill) in updates {
+                img.put_pixel(x, y, fill);
+                changed += 1;
+            }
+            pending = remaining;
+        }
+    }
+
+    changed
 }
 
 // ============================================================================
-// V2 IN-MEMORY PROCESSING
+// BLUR
 // ============================================================================
 
-/// Load an image from disk into memory
-pub fn load_image(path: &PathBuf) -> Result<RgbaImage> {
-    let img = image::open(path)
-        .map_err(|e| PixelsError::Processing(format!("Failed to load {}: {}", path.display(), e)))?;
-    Ok(img.to_rgba8())
+/// Build a normalized 1D kernel for the given blur kind and radius
+fn blur_kernel(radius: f32, kind: BlurKind) -> Vec<f32> {
+    let weights: Vec<f32> = match kind {
+        BlurKind::Box => {
+            let r = radius.round().max(0.0) as usize;
+            vec![1.0; 2 * r + 1]
+        }
+        BlurKind::Gaussian => {
+            let sigma = radius.max(0.01);
+            let r = (sigma * 3.0).ceil() as i32;
+            (-r..=r)
+                .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+                .collect()
+        }
+    };
+    let total: f32 = weights.iter().sum();
+    weights.into_iter().map(|w| w / total).collect()
 }
 
-/// Save an in-memory image to disk
-pub fn save_image(img: &RgbaImage, path: &PathBuf) -> Result<()> {
-    // Ensure output directory exists
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent)?;
+/// Convolve a premultiplied RGBA buffer along one axis (edges clamped)
+fn convolve_axis(src: &[[f32; 4]], width: usize, height: usize, kernel: &[f32], horizontal: bool) -> Vec<[f32; 4]> {
+    let half = (kernel.len() / 2) as isize;
+    let mut out = vec![[0.0f32; 4]; src.len()];
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut acc = [0.0f32; 4];
+            for (k, &w) in kernel.iter().enumerate() {
+                let offset = k as isize - half;
+                let (sx, sy) = if horizontal {
+                    ((x as isize + offset).clamp(0, width as isize - 1) as usize, y)
+                } else {
+                    (x, (y as isize + offset).clamp(0, height as isize - 1) as usize)
+                };
+                let p = &src[sy * width + sx];
+                for c in 0..4 {
+                    acc[c] += p[c] * w;
+                }
+            }
+            out[y * width + x] = acc;
+        }
     }
-    img.save(path)?;
-    Ok(())
+
+    out
 }
 
-/// Encode image as PNG bytes (for preview/transfer without file I/O)
-pub fn encode_png(img: &RgbaImage) -> Result<Vec<u8>> {
-    use std::io::Cursor;
-    let mut buffer = Cursor::new(Vec::new());
-    img.write_to(&mut buffer, image::ImageFormat::Png)
-        .map_err(|e| PixelsError::Processing(format!("Failed to encode PNG: {}", e)))?;
-    Ok(buffer.into_inner())
+/// Blur the image, respecting the alpha channel
+///
+/// Color is premultiplied by alpha before blurring and divided back out
+/// afterwards, so transparent pixels don't bleed black into soft edges.
+/// Both kinds are applied as two separable 1D passes. A radius <= 0 is a no-op.
+pub fn blur(img: &mut RgbaImage, radius: f32, kind: BlurKind) {
+    let (width, height) = img.dimensions();
+    if radius <= 0.0 || width == 0 || height == 0 {
+        return;
+    }
+    let (w, h) = (width as usize, height as usize);
+
+    let premultiplied: Vec<[f32; 4]> = img
+        .pixels()
+        .map(|p| {
+            let a = p[3] as f32 / 255.0;
+            [p[0] as f32 * a, p[1] as f32 * a, p[2] as f32 * a, p[3] as f32]
+        })
+        .collect();
+
+    let kernel = blur_kernel(radius, kind);
+    let pass = convolve_axis(&premultiplied, w, h, &kernel, true);
+    let blurred = convolve_axis(&pass, w, h, &kernel, false);
+
+    for (pixel, v) in img.pixels_mut().zip(blurred) {
+        let alpha = v[3].round().clamp(0.0, 255.0);
+        if alpha <= 0.0 {
+            *pixel = Rgba([0, 0, 0, 0]);
+            continue;
+        }
+        let a = v[3] / 255.0;
+        *pixel = Rgba([
+            (v[0] / a).round().clamp(0.0, 255.0) as u8,
+            (v[1] / a).round().clamp(0.0, 255.0) as u8,
+            (v[2] / a).round().clamp(0.0, 255.0) as u8,
+            alpha as u8,
+        ]);
+    }
 }
 
 // ============================================================================
-// TESTS
+// SYMMETRY
 // ============================================================================
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Mirror the `source_side` half of the image onto the other half
+///
+/// For odd sizes the center column/row is left untouched.
+pub fn enforce_symmetry(img: &mut RgbaImage, axis: SymmetryAxis, source_side: Side) {
+    let (width, height) = img.dimensions();
+    let from_first = matches!(source_side, Side::Left | Side::Top);
 
-    #[test]
-    fn test_rgb_lab_roundtrip() {
-        let test_colors = [
-            (255, 0, 0), (0, 255, 0), (0, 0, 255),
-            (255, 255, 255), (0, 0, 0), (128, 128, 128),
-            (17, 6, 2), // Default outline color
-        ];
+    match axis {
+        SymmetryAxis::Vertical => {
+            for y in 0..height {
+                for x in 0..width / 2 {
+                    let mirror = width - 1 - x;
+                    let (src, dst) = if from_first { (x, mirror) } else { (mirror, x) };
+                    let pixel = *img.get_pixel(src, y);
+                    img.put_pixel(dst, y, pixel);
+                }
+            }
+        }
+        SymmetryAxis::Horizontal => {
+            for y in 0..height / 2 {
+                let mirror = height - 1 - y;
+                let (src, dst) = if from_first { (y, mirror) } else { (mirror, y) };
+                for x in 0..width {
+                    let pixel = *img.get_pixel(x, src);
+                    img.put_pixel(x, dst, pixel);
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// TILEABLE TEXTURES
+// ============================================================================
 
-        for (r, g, b) in test_colors {
-            let lab = rgb_to_lab(r, g, b);
-            let (r2, g2, b2) = lab_to_rgb(lab.0, lab.1, lab.2);
-            assert!((r as i16 - r2 as i16).abs() <= 1, "Red mismatch for ({}, {}, {})", r, g, b);
-            assert!((g as i16 - g2 as i16).abs() <= 1, "Green mismatch for ({}, {}, {})", r, g, b);
-            assert!((b as i16 - b2 as i16).abs() <= 1, "Blue mismatch for ({}, {}, {})", r, g, b);
+/// Mean per-channel absolute difference between two pixel sequences
+fn mean_pixel_difference(a: impl Iterator<Item = Rgba<u8>>, b: impl Iterator<Item = Rgba<u8>>) -> f32 {
+    let mut total = 0u64;
+    let mut count = 0u64;
+    for (pa, pb) in a.zip(b) {
+        for c in 0..4 {
+            total += (pa[c] as i32 - pb[c] as i32).unsigned_abs() as u64;
         }
+        count += 4;
+    }
+    if count == 0 {
+        0.0
+    } else {
+        total as f32 / count as f32
     }
+}
 
-    #[test]
-    fn test_delta_e76_same_color() {
-        let lab = rgb_to_lab(100, 100, 100);
-        assert!(delta_e76(lab, lab) < 0.001);
+/// Measure how well opposite edges match when the image is tiled
+pub fn check_tileable(img: &RgbaImage) -> TileReport {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return TileReport { left_right_mismatch: 0.0, top_bottom_mismatch: 0.0, is_tileable: true };
     }
 
-    #[test]
-    fn test_neighbors_4way() {
-        let neighbors = get_neighbors(5, 5, 10, 10, &Connectivity::Four);
-        assert_eq!(neighbors.len(), 4);
+    let left_right = mean_pixel_difference(
+        (0..height).map(|y| *img.get_pixel(0, y)),
+        (0..height).map(|y| *img.get_pixel(width - 1, y)),
+    );
+    let top_bottom = mean_pixel_difference(
+        (0..width).map(|x| *img.get_pixel(x, 0)),
+        (0..width).map(|x| *img.get_pixel(x, height - 1)),
+    );
+
+    TileReport {
+        left_right_mismatch: left_right,
+        top_bottom_mismatch: top_bottom,
+        is_tileable: left_right < 1.0 && top_bottom < 1.0,
     }
+}
 
-    #[test]
-    fn test_neighbors_8way() {
-        let neighbors = get_neighbors(5, 5, 10, 10, &Connectivity::Eight);
-        assert_eq!(neighbors.len(), 8);
+/// Blend two pixels: `a * (1 - t) + b * t`
+fn lerp_pixel(a: &Rgba<u8>, b: &Rgba<u8>, t: f32) -> Rgba<u8> {
+    let mix = |c: usize| (a[c] as f32 * (1.0 - t) + b[c] as f32 * t).round() as u8;
+    Rgba([mix(0), mix(1), mix(2), mix(3)])
+}
+
+/// Cross-blend opposite edges over `blend` pixels so the image wraps seamlessly
+///
+/// Pixels at distance `i` from an edge move toward their mirror at the same
+/// distance from the opposite edge, fully averaged at the seam (i = 0) and
+/// fading out at `blend`. `blend` is clamped to half the image size (min 1).
+pub fn make_tileable(img: &mut RgbaImage, blend: u32) {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return;
     }
 
-    #[test]
-    fn test_neighbors_corner() {
-        let n4 = get_neighbors(0, 0, 10, 10, &Connectivity::Four);
-        assert_eq!(n4.len(), 2);
-        let n8 = get_neighbors(0, 0, 10, 10, &Connectivity::Eight);
-        assert_eq!(n8.len(), 3);
+    let blend_x = blend.clamp(1, (width / 2).max(1));
+    for i in 0..blend_x.min(width / 2) {
+        let t = 0.5 * (1.0 - i as f32 / blend_x as f32);
+        for y in 0..height {
+            let left = *img.get_pixel(i, y);
+            let right = *img.get_pixel(width - 1 - i, y);
+            img.put_pixel(i, y, lerp_pixel(&left, &right, t));
+            img.put_pixel(width - 1 - i, y, lerp_pixel(&right, &left, t));
+        }
+    }
+
+    let blend_y = blend.clamp(1, (height / 2).max(1));
+    for i in 0..blend_y.min(height / 2) {
+        let t = 0.5 * (1.0 - i as f32 / blend_y as f32);
+        for x in 0..width {
+            let top = *img.get_pixel(x, i);
+            let bottom = *img.get_pixel(x, height - 1 - i);
+            img.put_pixel(x, i, lerp_pixel(&top, &bottom, t));
+            img.put_pixel(x, height - 1 - i, lerp_pixel(&bottom, &top, t));
+        }
+    }
+}
+
+// ============================================================================
+// REGION-LIMITED OPERATIONS
+// ============================================================================
+
+/// Run `op` on a rectangular sub-region `(x, y, w, h)` of the image
+///
+/// The region is cropped out (clamped to the image bounds), `op` runs on the
+/// crop, and the result is written back. Pixels outside the region are never
+/// touched. With `None`, `op` runs on the whole image.
+pub fn apply_in_region<T>(
+    img: &mut RgbaImage,
+    region: Option<(u32, u32, u32, u32)>,
+    op: impl FnOnce(&mut RgbaImage) -> T,
+) -> T {
+    let (x, y, w, h) = match region {
+        Some(r) => r,
+        None => return op(img),
+    };
+
+    let (width, height) = img.dimensions();
+    let x = x.min(width);
+    let y = y.min(height);
+    let w = w.min(width - x);
+    let h = h.min(height - y);
+
+    let mut sub = image::imageops::crop_imm(img, x, y, w, h).to_image();
+    let result = op(&mut sub);
+    image::imageops::replace(img, &sub, x as i64, y as i64);
+    result
+}
+
+/// Outline within an optional region `(x, y, w, h)`
+///
+/// With `background_mask` (a full-size mask file as written by
+/// `downscaler::remove_background_with_mask`), only edges that border
+/// removed background are outlined; the mask is cropped to the region.
+pub fn add_outline_in_region(
+    img: &mut RgbaImage,
+    settings: &OutlineSettings,
+    region: Option<(u32, u32, u32, u32)>,
+    background_mask: Option<&Path>,
+) -> Result<()> {
+    match background_mask {
+        Some(mask_path) => {
+            let mask = decode_image(mask_path)?.to_luma8();
+            let mask = match region {
+                Some((x, y, w, h)) => image::imageops::crop_imm(&mask, x, y, w, h).to_image(),
+                None => mask,
+            };
+            apply_in_region(img, region, |sub| add_outline_masked(sub, settings, &mask))
+        }
+        None => {
+            apply_in_region(img, region, |sub| add_outline(sub, settings));
+            Ok(())
+        }
+    }
+}
+
+// ============================================================================
+// COLOR MAPPING
+// ============================================================================
+
+/// Hue of an RGB color in degrees [0, 360), or None for grays
+fn rgb_hue(r: u8, g: u8, b: u8) -> Option<f32> {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    if delta == 0.0 {
+        return None;
+    }
+    let hue = if max == r {
+        60.0 * ((g - b) / delta)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    Some(hue.rem_euclid(360.0))
+}
+
+impl ColorMatch {
+    fn matches(&self, p: &Rgba<u8>) -> bool {
+        match *self {
+            ColorMatch::Exact { color } => (p[0], p[1], p[2]) == color,
+            ColorMatch::HueRange { min_hue, max_hue } => match rgb_hue(p[0], p[1], p[2]) {
+                Some(h) if min_hue <= max_hue => h >= min_hue && h <= max_hue,
+                Some(h) => h >= min_hue || h <= max_hue,
+                None => false,
+            },
+        }
+    }
+}
+
+/// Recolor pixels using an ordered list of (match, replacement RGB) rules
+///
+/// Every rule is tested against the pixel's original color and the last
+/// matching rule wins, so later rules override earlier ones where they
+/// overlap. Alpha is preserved and transparent pixels are left alone.
+/// Returns how many pixels each rule ended up changing.
+pub fn apply_color_mappings(img: &mut RgbaImage, mappings: &[(ColorMatch, (u8, u8, u8))]) -> Vec<usize> {
+    let mut counts = vec![0usize; mappings.len()];
+
+    for pixel in img.pixels_mut() {
+        if pixel[3] == 0 {
+            continue;
+        }
+        let winner = mappings.iter().rposition(|(rule, _)| rule.matches(pixel));
+        if let Some(i) = winner {
+            let (r, g, b) = mappings[i].1;
+            if (pixel[0], pixel[1], pixel[2]) != (r, g, b) {
+                *pixel = Rgba([r, g, b, pixel[3]]);
+                counts[i] += 1;
+            }
+        }
+    }
+
+    counts
+}
+
+// ============================================================================
+// V2 PIPELINE
+// ============================================================================
+
+impl StageTimings {
+    pub fn new(enabled: bool) -> Self {
+        Self(if enabled { Some(Vec::new()) } else { None })
+    }
+
+    /// Run `op`, recording its duration under `stage` if enabled
+    /// Repeated stages accumulate into one entry
+    pub fn time<T>(&mut self, stage: &str, op: impl FnOnce() -> T) -> T {
+        match &mut self.0 {
+            Some(entries) => {
+                let start = std::time::Instant::now();
+                let result = op();
+                let elapsed = start.elapsed();
+                match entries.iter_mut().find(|(name, _)| name == stage) {
+                    Some(entry) => entry.1 += elapsed,
+                    None => entries.push((stage.to_string(), elapsed)),
+                }
+                result
+            }
+            None => op(),
+        }
+    }
+
+    /// Recorded (stage, milliseconds) entries, or None if disabled
+    /// Durations keep sub-millisecond precision.
+    pub fn into_entries(self) -> Option<Vec<(String, f64)>> {
+        self.0.map(|entries| {
+            entries
+                .into_iter()
+                .map(|(stage, elapsed)| (stage, elapsed.as_secs_f64() * 1000.0))
+                .collect()
+        })
+    }
+}
+
+/// Run the configured operations on an image in pipeline order
+pub fn run_pipeline(mut img: RgbaImage, config: &PipelineConfig, timings: &mut StageTimings) -> RgbaImage {
+    use crate::downscaler;
+
+    let downscale = config.downscale.as_ref().filter(|ds| ds.enabled);
+    let outline_first = config.outline_before_downscale && downscale.is_some();
+
+    // Downscale first (if enabled)
+    if let Some(ds_settings) = downscale {
+        // Trim and work out the scale before touching pixels
+        let manual = match (ds_settings.target_width, ds_settings.target_height) {
+            (Some(w), Some(h)) => Some((w, h)),
+            _ => None,
+        };
+        let (scale, phase_x, phase_y) = timings.time("downscale", || {
+            if ds_settings.auto_trim {
+                img = downscaler::auto_trim_image(&img);
+            }
+            match manual {
+                // Check if manual dimensions are provided
+                Some((target_w, _)) => {
+                    (((img.width() as f32 / target_w.max(1) as f32).round() as u32).max(1), 0, 0)
+                }
+                // Use auto-detection
+                None => {
+                    let grid_hint = downscaler::detect_grid_for_image(&img);
+                    downscaler::find_optimal_scale_for_image(&img, grid_hint)
+                }
+            }
+        });
+
+        if outline_first {
+            if let Some(settings) = &config.outline {
+                timings.time("outline", || add_outline_scaled(&mut img, settings, scale));
+            }
+        }
+
+        img = timings.time("downscale", || match manual {
+            Some((target_w, target_h)) => downscaler::downscale_to_dimensions(&img, target_w, target_h),
+            None if scale > 1 => downscaler::downsample_image(&img, scale, phase_x, phase_y),
+            None => img,
+        });
+    }
+
+    // Apply post-processing operations in order (if settings provided)
+    let outline_before_alpha = !outline_first && config.outline_before_alpha;
+    if let Some(settings) = config.outline.as_ref().filter(|_| outline_before_alpha) {
+        timings.time("outline", || add_outline(&mut img, settings));
+    }
+    if let Some(settings) = &config.alpha {
+        timings.time("alpha", || normalize_alpha(&mut img, settings));
+    }
+    if let Some(strength) = config.deartifact_strength {
+        timings.time("deartifact", || deartifact(&mut img, strength));
+    }
+    if let Some(settings) = &config.merge {
+        timings.time("merge", || merge_colors(&mut img, settings));
+    }
+    if let Some(settings) = config.outline.as_ref().filter(|_| !outline_first && !outline_before_alpha) {
+        timings.time("outline", || add_outline(&mut img, settings));
+    }
+    if let Some(threshold) = config.trim_alpha_threshold {
+        img = timings.time("trim", || downscaler::auto_trim_above(&img, threshold));
+    }
+    if let Some(iterations) = config.bleed_iterations.filter(|&n| n > 0) {
+        timings.time("bleed", || bleed_edges(&mut img, iterations));
+    }
+
+    img
+}
+
+/// Load a pipeline input, enforcing `config.max_megapixels`
+fn load_pipeline_input(path: &Path, config: &PipelineConfig) -> Result<RgbaImage> {
+    Ok(decode_image_limited(path, config.max_megapixels)?.to_rgba8())
+}
+
+/// Run the pipeline on an image file and encode the result as PNG, for
+/// previews that are never written to disk
+pub fn preview_pipeline(input: &Path, config: &PipelineConfig) -> Result<Vec<u8>> {
+    let img = load_pipeline_input(input, config)?;
+    let img = run_pipeline(img, config, &mut StageTimings::new(false));
+    encode_png(&img)
+}
+
+/// Run the pipeline on `input` and save the result to `output` under
+/// `options`
+///
+/// A dry run reports where the result would go without writing anything.
+/// With `auto_backup`, an in-place write first backs up the original into
+/// the enclosing workspace (see `state::backup_before_overwrite`). Stages
+/// (and the encode) are recorded in `timings`, whose entries are returned
+/// in the result.
+pub fn process_and_save(
+    input: &Path,
+    output: &Path,
+    config: &PipelineConfig,
+    options: &SaveOptions,
+    auto_backup: bool,
+    dry_run: bool,
+    mut timings: StageTimings,
+) -> Result<SaveResult> {
+    let img = load_pipeline_input(input, config)?;
+    let img = run_pipeline(img, config, &mut timings);
+
+    let in_place = options.allow_in_place && same_file(input, output);
+    let backup = if !dry_run && auto_backup && in_place {
+        Some(crate::state::backup_before_overwrite(input)?)
+    } else {
+        None
+    };
+    let written = if dry_run {
+        resolve_output_path(input, output, options)?
+    } else {
+        timings.time("encode", || save_image_with_options(&img, input, output, options))?
+    };
+    Ok(SaveResult {
+        saved_path: written.map(|p| p.to_string_lossy().to_string()),
+        final_size: img.dimensions(),
+        dry_run,
+        timings: timings.into_entries(),
+        backup_path: backup.map(|p| p.to_string_lossy().to_string()),
+    })
+}
+
+/// Per-file outcome of `process_files`
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessFileEntry {
+    /// Input path as given
+    pub input_path: String,
+    /// Output written for this input
+    pub output_path: String,
+    /// Size of the processed image (None if this file failed)
+    pub final_size: Option<(u32, u32)>,
+    /// Error message if this file failed
+    pub error: Option<String>,
+    /// Already completed by an interrupted run and not redone
+    pub skipped: bool,
+}
+
+/// Run the V2 pipeline over an explicit list of files
+///
+/// Each output is written to `output_dir` as `<file stem>.png`, under
+/// `options`. Inputs that would land on the same output name are rejected up
+/// front; a failure on one file (including a refused overwrite) is recorded
+/// in its entry rather than aborting the rest. Entries are returned in input
+/// order. Files run on the same bounded pool as `downscale_folder`, and
+/// progress is checkpointed in `output_dir` the same way; with `resume`,
+/// inputs an interrupted run finished are skipped.
+pub fn process_files(
+    inputs: &[PathBuf],
+    output_dir: &Path,
+    config: &PipelineConfig,
+    options: &SaveOptions,
+    resume: bool,
+) -> Result<Vec<ProcessFileEntry>> {
+    use crate::downscaler::{batch_pool, checkpoint_path, CheckpointTracker};
+    use rayon::prelude::*;
+
+    if inputs.is_empty() {
+        return Err(PixelsError::InvalidParameter("No input files provided".to_string()));
+    }
+
+    let mut outputs: HashMap<PathBuf, &PathBuf> = HashMap::new();
+    for input in inputs {
+        let stem = input.file_stem().ok_or_else(|| {
+            PixelsError::InvalidParameter(format!("Not a file path: {}", input.display()))
+        })?;
+        let output = output_dir.join(format!("{}.png", stem.to_string_lossy()));
+        if let Some(previous) = outputs.insert(output.clone(), input) {
+            return Err(PixelsError::InvalidParameter(format!(
+                "{} and {} would both be written to {}",
+                previous.display(),
+                input.display(),
+                output.display()
+            )));
+        }
+    }
+    std::fs::create_dir_all(output_dir)?;
+    let tracker = CheckpointTracker::start(checkpoint_path(output_dir), "process_files", output_dir, resume);
+
+    let entries: Vec<ProcessFileEntry> = batch_pool()?.install(|| {
+        inputs
+            .par_iter()
+            .map(|input| {
+                let input_path = input.to_string_lossy().to_string();
+                let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+                let output_path = output_dir.join(format!("{}.png", stem));
+                if tracker.was_done(&input_path) {
+                    let output_path = output_path.to_string_lossy().to_string();
+                    return ProcessFileEntry { input_path, output_path, final_size: None, error: None, skipped: true };
+                }
+
+                let outcome = resolve_output_path(input, &output_path, options).and_then(|target| {
+                    let img = run_pipeline(load_pipeline_input(input, config)?, config, &mut StageTimings::new(false));
+                    if let Some(path) = target {
+                        save_image(&img, &path)?;
+                    }
+                    Ok(img.dimensions())
+                });
+                if outcome.is_ok() {
+                    tracker.complete(input_path.clone());
+                }
+                ProcessFileEntry {
+                    input_path,
+                    output_path: output_path.to_string_lossy().to_string(),
+                    final_size: outcome.as_ref().ok().copied(),
+                    error: outcome.err().map(|e| e.to_string()),
+                    skipped: false,
+                }
+            })
+            .collect()
+    });
+
+    tracker.finish(entries.iter().all(|e| e.error.is_none()))?;
+    Ok(entries)
+}
+
+/// Folder beside `output` where `process_zip` keeps finished images and its
+/// checkpoint until every image has succeeded
+pub fn zip_staging_dir(output: &Path) -> PathBuf {
+    let name = output.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    output.with_file_name(format!("{}.partial", name))
+}
+
+/// Run the V2 pipeline over every image inside a ZIP archive, without
+/// extracting it, and write the results to a new archive
+///
+/// Processed images keep their path inside the archive, with the extension
+/// changed to `.png`; other files are copied through unchanged. As with
+/// `process_files`, a failure on one image is recorded in its entry and that
+/// image is left out of the output, and images run on the bounded batch pool. Entries are returned in archive order.
+/// The archive is written under `options`.
+///
+/// Finished images are staged in `zip_staging_dir` with a checkpoint, which
+/// is removed once every image has succeeded. With `resume`, images an
+/// interrupted run finished are taken from there instead of being redone.
+pub fn process_zip(
+    input: &Path,
+    output: &Path,
+    config: &PipelineConfig,
+    options: &SaveOptions,
+    resume: bool,
+) -> Result<Vec<ProcessFileEntry>> {
+    use crate::archive::{read_zip, write_zip, ZipEntry};
+    use crate::downscaler::{batch_pool, checkpoint_path, CheckpointTracker};
+    use rayon::prelude::*;
+
+    let target = resolve_output_path(input, output, options)?;
+    let bytes = std::fs::read(input)
+        .map_err(|e| PixelsError::Io(std::io::Error::new(e.kind(), format!("{}: {}", input.display(), e))))?;
+    let entries = read_zip(&bytes)?;
+
+    let output_name = |name: &str| match name.rfind('.') {
+        Some(dot) if !name[dot..].contains('/') => format!("{}.png", &name[..dot]),
+        _ => format!("{}.png", name),
+    };
+    let mut names: HashMap<String, &str> = HashMap::new();
+    for entry in &entries {
+        let name = if is_image_file(Path::new(&entry.name)) { output_name(&entry.name) } else { entry.name.clone() };
+        if let Some(previous) = names.insert(name.clone(), &entry.name) {
+            return Err(PixelsError::InvalidParameter(format!(
+                "{} and {} would both be written to {}",
+                previous, entry.name, name
+            )));
+        }
+    }
+
+    // Nothing is staged when the overwrite policy skips the output
+    let staging = target.as_ref().map(|output| zip_staging_dir(output));
+    if let Some(dir) = &staging {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tracker = staging
+        .as_ref()
+        .map(|dir| CheckpointTracker::start(checkpoint_path(dir), "process_zip", output, resume));
+    // Staged files are named by hash, since entry names come from the archive
+    let staged_path = |dir: &Path, name: &str| dir.join(format!("{}.png", crate::state::hash_bytes(name.as_bytes())));
+
+    let results: Vec<(Option<ProcessFileEntry>, Option<ZipEntry>)> = batch_pool()?.install(|| {
+        entries
+            .into_par_iter()
+            .map(|entry| {
+                if !is_image_file(Path::new(&entry.name)) {
+                    return (None, Some(entry));
+                }
+                let name = output_name(&entry.name);
+                let staged = staging.as_ref().map(|dir| staged_path(dir, &entry.name));
+
+                let done_before = tracker.as_ref().is_some_and(|t| t.was_done(&entry.name));
+                if let Some(data) = staged.as_ref().filter(|_| done_before).and_then(|path| std::fs::read(path).ok()) {
+                    let report = ProcessFileEntry {
+                        input_path: entry.name,
+                        output_path: name.clone(),
+                        final_size: None,
+                        error: None,
+                        skipped: true,
+                    };
+                    return (Some(report), Some(ZipEntry { name, data }));
+                }
+
+                let outcome = decode_bytes_limited(&entry.data, Path::new(&entry.name), config.max_megapixels).and_then(|img| {
+                    let img = run_pipeline(img.to_rgba8(), config, &mut StageTimings::new(false));
+                    Ok((img.dimensions(), encode_png(&img)?))
+                });
+                if let (Ok((_, data)), Some(path), Some(tracker)) = (&outcome, &staged, &tracker) {
+                    // Best effort: an unstaged image is just redone on resume
+                    if std::fs::write(path, data).is_ok() {
+                        tracker.complete(entry.name.clone());
+                    }
+                }
+                let report = ProcessFileEntry {
+                    input_path: entry.name,
+                    output_path: name.clone(),
+                    final_size: outcome.as_ref().ok().map(|(size, _)| *size),
+                    error: outcome.as_ref().err().map(|e| e.to_string()),
+                    skipped: false,
+                };
+                (Some(report), outcome.ok().map(|(_, data)| ZipEntry { name, data }))
+            })
+            .collect()
+    });
+
+    let (reports, written): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+    let reports: Vec<ProcessFileEntry> = reports.into_iter().flatten().collect();
+    let written: Vec<ZipEntry> = written.into_iter().flatten().collect();
+    if let Some(output) = target {
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(output, write_zip(&written)?)?;
+    }
+
+    if let (Some(dir), Some(tracker)) = (&staging, &tracker) {
+        if reports.iter().all(|e| e.error.is_none()) {
+            std::fs::remove_dir_all(dir)?;
+        } else {
+            tracker.finish(false)?;
+        }
+    }
+
+    Ok(reports)
+}
+
+// ============================================================================
+// RECIPES
+// ============================================================================
+
+/// Bumped whenever the recipe layout changes incompatibly
+pub const RECIPE_SCHEMA_VERSION: u32 = 1;
+
+/// One stage of a recipe, with its settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum RecipeStage {
+    Downscale(crate::downscaler::PreviewDownscaleSettings),
+    Alpha(AlphaSettings),
+    Deartifact { strength: f32 },
+    Merge(MergeSettings),
+    Outline(OutlineSettings),
+    Trim { alpha_threshold: u8 },
+    Bleed { iterations: u32 },
+}
+
+impl RecipeStage {
+    fn name(&self) -> &'static str {
+        match self {
+            RecipeStage::Downscale(_) => "downscale",
+            RecipeStage::Alpha(_) => "alpha",
+            RecipeStage::Deartifact { .. } => "deartifact",
+            RecipeStage::Merge(_) => "merge",
+            RecipeStage::Outline(_) => "outline",
+            RecipeStage::Trim { .. } => "trim",
+            RecipeStage::Bleed { .. } => "bleed",
+        }
+    }
+}
+
+/// A shareable `recipe.json`: the pipeline stages to run, in order
+///
+/// Stages follow `run_pipeline`'s order, except that outline may come
+/// first (`PipelineConfig::outline_before_downscale`) or right after
+/// downscale (`PipelineConfig::outline_before_alpha`). Each stage appears
+/// at most once; stages left out don't run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recipe {
+    pub schema_version: u32,
+    pub stages: Vec<RecipeStage>,
+}
+
+impl Recipe {
+    /// Capture the stages a pipeline config would run
+    pub fn from_config(config: &PipelineConfig) -> Self {
+        let outline = config.outline.clone().map(RecipeStage::Outline);
+        let outline_first = config.outline_before_downscale && config.downscale.as_ref().is_some_and(|ds| ds.enabled);
+        let outline_before_alpha = !outline_first && config.outline_before_alpha;
+
+        let mut stages = Vec::new();
+        if outline_first {
+            stages.extend(outline.clone());
+        }
+        stages.extend(config.downscale.clone().map(RecipeStage::Downscale));
+        if outline_before_alpha {
+            stages.extend(outline.clone());
+        }
+        stages.extend(config.alpha.clone().map(RecipeStage::Alpha));
+        stages.extend(config.deartifact_strength.map(|strength| RecipeStage::Deartifact { strength }));
+        stages.extend(config.merge.clone().map(RecipeStage::Merge));
+        if !outline_first && !outline_before_alpha {
+            stages.extend(outline);
+        }
+        stages.extend(config.trim_alpha_threshold.map(|alpha_threshold| RecipeStage::Trim { alpha_threshold }));
+        stages.extend(config.bleed_iterations.map(|iterations| RecipeStage::Bleed { iterations }));
+
+        Self { schema_version: RECIPE_SCHEMA_VERSION, stages }
+    }
+
+    /// Rebuild the pipeline config, rejecting repeated or out-of-order stages
+    pub fn to_config(&self) -> Result<PipelineConfig> {
+        if self.schema_version > RECIPE_SCHEMA_VERSION {
+            return Err(PixelsError::UnsupportedFormat(format!(
+                "Recipe schema version {} is newer than supported version {}",
+                self.schema_version, RECIPE_SCHEMA_VERSION
+            )));
+        }
+
+        // Fixed order of every stage but outline, which is placed below
+        const ORDER: [&str; 6] = ["downscale", "alpha", "deartifact", "merge", "trim", "bleed"];
+        let misplaced = |name: &str| {
+            PixelsError::InvalidParameter(format!("Recipe stage '{}' is repeated or out of order", name))
+        };
+
+        let mut config = PipelineConfig::default();
+        let mut seen = HashSet::new();
+        let mut last_rank = None;
+        for stage in &self.stages {
+            if !seen.insert(stage.name()) {
+                return Err(misplaced(stage.name()));
+            }
+            if let Some(rank) = ORDER.iter().position(|&name| name == stage.name()) {
+                if last_rank.is_some_and(|last| rank <= last) {
+                    return Err(misplaced(stage.name()));
+                }
+                last_rank = Some(rank);
+            }
+
+            match stage {
+                RecipeStage::Downscale(settings) => config.downscale = Some(settings.clone()),
+                RecipeStage::Alpha(settings) => config.alpha = Some(settings.clone()),
+                RecipeStage::Deartifact { strength } => config.deartifact_strength = Some(*strength),
+                RecipeStage::Merge(settings) => config.merge = Some(settings.clone()),
+                RecipeStage::Outline(settings) => config.outline = Some(settings.clone()),
+                RecipeStage::Trim { alpha_threshold } => config.trim_alpha_threshold = Some(*alpha_threshold),
+                RecipeStage::Bleed { iterations } => config.bleed_iterations = Some(*iterations),
+            }
+        }
+
+        // Outline goes last (before trim and bleed), first, or right after downscale
+        if let Some(at) = self.stages.iter().position(|s| matches!(s, RecipeStage::Outline(_))) {
+            let before: Vec<&str> = self.stages[..at].iter().map(RecipeStage::name).collect();
+            let after: Vec<&str> = self.stages[at + 1..].iter().map(RecipeStage::name).collect();
+            let last = after.iter().all(|&name| name == "trim" || name == "bleed");
+            if !last && before.is_empty() && after.contains(&"downscale") {
+                config.outline_before_downscale = true;
+            } else if !last && before.iter().all(|&name| name == "downscale") {
+                config.outline_before_alpha = true;
+            } else if !last {
+                return Err(misplaced("outline"));
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Write a pipeline config to `path` as a recipe (pretty-printed JSON)
+pub fn save_recipe(path: &Path, config: &PipelineConfig) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&Recipe::from_config(config))?)?;
+    Ok(())
+}
+
+/// Read a recipe file back into a pipeline config
+pub fn load_recipe(path: &Path) -> Result<PipelineConfig> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| PixelsError::Io(std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e))))?;
+    let recipe: Recipe = serde_json::from_str(&json)?;
+    recipe.to_config()
+}
+
+/// Replay a recipe on one image, saving the result under `options`
+/// Returns the size of the output image
+pub fn apply_recipe(recipe_path: &Path, input: &Path, output: &Path, options: &SaveOptions) -> Result<(u32, u32)> {
+    let config = load_recipe(recipe_path)?;
+    edit_file(input, output, options, |img| {
+        *img = run_pipeline(std::mem::take(img), &config, &mut StageTimings::new(false));
+        Ok(img.dimensions())
+    })
+}
+
+// ============================================================================
+// TINT
+// ============================================================================
+
+/// Sepia ramp endpoints (shadow, highlight)
+const SEPIA_DARK: (u8, u8, u8) = (38, 22, 9);
+const SEPIA_LIGHT: (u8, u8, u8) = (255, 240, 200);
+
+/// Linear blend between two colors at `t` / 255
+fn lerp_rgb(a: (u8, u8, u8), b: (u8, u8, u8), t: u8) -> (u8, u8, u8) {
+    let mix = |x: u8, y: u8| ((x as u32 * (255 - t as u32) + y as u32 * t as u32 + 127) / 255) as u8;
+    (mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+}
+
+/// Recolor an image by luma (alpha preserved)
+///
+/// Each color maps to exactly one output color, so the result never has more
+/// colors than the input and edges stay crisp.
+pub fn tint(img: &mut RgbaImage, mode: TintMode) {
+    let (dark, light) = match mode {
+        TintMode::Grayscale => ((0, 0, 0), (255, 255, 255)),
+        TintMode::Sepia => (SEPIA_DARK, SEPIA_LIGHT),
+        TintMode::Duotone { dark, light } => (dark, light),
+    };
+
+    for pixel in img.pixels_mut() {
+        if pixel[3] == 0 {
+            continue;
+        }
+        let (r, g, b) = lerp_rgb(dark, light, luma(pixel[0], pixel[1], pixel[2]));
+        *pixel = Rgba([r, g, b, pixel[3]]);
+    }
+}
+
+// ============================================================================
+// TILED PROCESSING
+// ============================================================================
+
+/// Run `op` over overlapping tiles in parallel and stitch the results
+///
+/// Each `tile_size` square is processed together with `overlap` pixels of
+/// surrounding context, and only the tile's own pixels are copied back, so
+/// neighborhood operations whose reach is at most `overlap` (e.g. outline
+/// with `thickness <= overlap`) match a whole-image run with no seams.
+/// Operations that look at global statistics, like `merge_colors`, see only
+/// their tile plus overlap and may cluster differently per tile.
+pub fn process_tiled<F>(img: &RgbaImage, tile_size: u32, overlap: u32, op: F) -> Result<RgbaImage>
+where
+    F: Fn(&mut RgbaImage) + Sync,
+{
+    use rayon::prelude::*;
+
+    if tile_size == 0 {
+        return Err(PixelsError::InvalidParameter("Tile size must be greater than 0".to_string()));
+    }
+
+    let (width, height) = img.dimensions();
+    let tiles: Vec<(u32, u32)> = (0..height)
+        .step_by(tile_size as usize)
+        .flat_map(|y| (0..width).step_by(tile_size as usize).map(move |x| (x, y)))
+        .collect();
+
+    let processed: Vec<(u32, u32, RgbaImage)> = tiles
+        .par_iter()
+        .map(|&(x, y)| {
+            let x0 = x.saturating_sub(overlap);
+            let y0 = y.saturating_sub(overlap);
+            let x1 = (x + tile_size + overlap).min(width);
+            let y1 = (y + tile_size + overlap).min(height);
+            let mut region = image::imageops::crop_imm(img, x0, y0, x1 - x0, y1 - y0).to_image();
+            op(&mut region);
+
+            let w = tile_size.min(width - x);
+            let h = tile_size.min(height - y);
+            let core = image::imageops::crop_imm(&region, x - x0, y - y0, w, h).to_image();
+            (x, y, core)
+        })
+        .collect();
+
+    let mut out = RgbaImage::new(width, height);
+    for (x, y, core) in processed {
+        image::imageops::replace(&mut out, &core, x as i64, y as i64);
+    }
+    Ok(out)
+}
+
+// ============================================================================
+// SIZE BUDGET
+// ============================================================================
+
+/// Merge thresholds tried in order by `quantize_to_size`
+const SIZE_BUDGET_THRESHOLDS: [f32; 8] = [3.0, 6.0, 10.0, 15.0, 22.0, 32.0, 45.0, 64.0];
+
+/// Outcome of `quantize_to_size_report`
+#[derive(Debug, Clone, Serialize)]
+pub struct SizeBudgetResult {
+    /// Encoded PNG size of the returned image in bytes
+    pub size: usize,
+    /// Whether `size` is within the requested budget
+    pub fits: bool,
+    /// Merge threshold that produced the image (None if unmodified)
+    pub threshold: Option<f32>,
+    pub warnings: Vec<String>,
+}
+
+/// Merge colors ever more aggressively until the PNG fits in `max_bytes`
+///
+/// Returns the image and its encoded size. If no threshold gets under the
+/// budget, the smallest result is returned.
+pub fn quantize_to_size(img: &RgbaImage, max_bytes: usize) -> Result<(RgbaImage, usize)> {
+    let (out, report) = quantize_to_size_report(img, max_bytes)?;
+    Ok((out, report.size))
+}
+
+/// `quantize_to_size` with details on the threshold used and a warning when
+/// the budget could not be met
+pub fn quantize_to_size_report(img: &RgbaImage, max_bytes: usize) -> Result<(RgbaImage, SizeBudgetResult)> {
+    let mut best = (img.clone(), encode_png(img)?.len(), None);
+
+    for threshold in SIZE_BUDGET_THRESHOLDS {
+        if best.1 <= max_bytes {
+            break;
+        }
+        let mut candidate = img.clone();
+        let settings = MergeSettings {
+            threshold,
+            max_unique_colors_before_prequantize: Some(4096),
+            ..MergeSettings::default()
+        };
+        merge_colors(&mut candidate, &settings);
+        let size = encode_png(&candidate)?.len();
+        if size < best.1 {
+            best = (candidate, size, Some(threshold));
+        }
+    }
+
+    let (out, size, threshold) = best;
+    let fits = size <= max_bytes;
+    let warnings = if fits {
+        Vec::new()
+    } else {
+        vec![format!(
+            "Could not reach {} bytes; smallest result is {} bytes",
+            max_bytes, size
+        )]
+    };
+    Ok((out, SizeBudgetResult { size, fits, threshold, warnings }))
+}
+
+// ============================================================================
+// ALPHA BLEED
+// ============================================================================
+
+/// Spread edge colors into the RGB of neighboring transparent pixels
+///
+/// Each iteration gives every fully transparent pixel that touches a visible
+/// (or already bled) pixel the average RGB of those neighbors, growing one
+/// pixel outward per pass. Alpha stays 0, so nothing visible changes, but
+/// mipmapping and bilinear filtering no longer pull stale (often black) RGB
+/// into the sprite's edges.
+pub fn bleed_edges(img: &mut RgbaImage, iterations: u32) {
+    let (width, height) = img.dimensions();
+    let mut filled: Vec<bool> = img.pixels().map(|p| p[3] > 0).collect();
+
+    for _ in 0..iterations {
+        let mut updates = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                if filled[(y * width + x) as usize] {
+                    continue;
+                }
+                let (mut sum, mut count) = ([0u32; 3], 0u32);
+                for (nx, ny) in get_neighbors(x, y, width, height, &Connectivity::Eight) {
+                    if filled[(ny * width + nx) as usize] {
+                        let n = img.get_pixel(nx, ny);
+                        (0..3).for_each(|c| sum[c] += n[c] as u32);
+                        count += 1;
+                    }
+                }
+                if count > 0 {
+                    let avg = sum.map(|v| ((v + count / 2) / count) as u8);
+                    updates.push((x, y, avg));
+                }
+            }
+        }
+        if updates.is_empty() {
+            break;
+        }
+        for (x, y, [r, g, b]) in updates {
+            img.put_pixel(x, y, Rgba([r, g, b, 0]));
+            filled[(y * width + x) as usize] = true;
+        }
+    }
+}
+
+// ============================================================================
+// BENCHMARK
+// ============================================================================
+
+/// Timing of one operation in a `benchmark` run
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkEntry {
+    /// Operation name (e.g. "downscale", "merge")
+    pub operation: String,
+    /// Size of the image the operation ran on
+    pub input_size: (u32, u32),
+    /// Wall-clock time of a single run, in milliseconds
+    pub duration_ms: f64,
+    /// Input megapixels processed per second
+    pub megapixels_per_second: f64,
+}
+
+/// Time each core operation once on a sample image, with default settings
+///
+/// Operations run in pipeline order on the previous one's output: the
+/// downscale sees the sample, merge and outline see the downscaled image,
+/// and the pack (of the sample alone) includes reading and writing files.
+pub fn benchmark(sample_path: &Path) -> Result<Vec<BenchmarkEntry>> {
+    use crate::{downscaler, packer};
+
+    let mut timings = StageTimings::new(true);
+    let sample_size = image::image_dimensions(sample_path)?;
+
+    let (mut img, _) = timings.time("downscale", || {
+        downscaler::downscale_file(sample_path, &downscaler::DownscalerSettings::default())
+    })?;
+    let downscaled_size = img.dimensions();
+    timings.time("merge", || merge_colors(&mut img, &MergeSettings::default()));
+    timings.time("outline", || add_outline(&mut img, &OutlineSettings::default()));
+
+    let scratch = std::env::temp_dir().join(format!("pixels_benchmark_{}", std::process::id()));
+    std::fs::create_dir_all(&scratch)?;
+    let settings = packer::PackerSettings { export_metadata: false, ..Default::default() };
+    let packed = timings.time("pack", || {
+        packer::pack_sprites(vec![sample_path.to_path_buf()], scratch.join("sheet.png"), settings)
+    });
+    let _ = std::fs::remove_dir_all(&scratch);
+    packed?;
+
+    // Input size of each stage, in the order they ran
+    let sizes = [sample_size, downscaled_size, downscaled_size, sample_size];
+    let entries = timings.into_entries().unwrap_or_default().into_iter().zip(sizes);
+    Ok(entries
+        .map(|((operation, duration_ms), size)| {
+            let megapixels = size.0 as f64 * size.1 as f64 / 1_000_000.0;
+            BenchmarkEntry {
+                operation,
+                input_size: size,
+                duration_ms,
+                megapixels_per_second: if duration_ms > 0.0 { megapixels / (duration_ms / 1000.0) } else { 0.0 },
+            }
+        })
+        .collect())
+}
+
+// ============================================================================
+// FOLDER COMPARISON
+// ============================================================================
+
+/// Similarity at or above which a compared pair passes (see `compare_folders`)
+pub const DEFAULT_MIN_SIMILARITY: f32 = 0.99;
+
+/// Pixel-level comparison of an output image against a reference
+#[derive(Debug, Clone, Serialize)]
+pub struct ComparisonReport {
+    pub size: (u32, u32),
+    pub reference_size: (u32, u32),
+    /// Fraction of pixels with identical RGBA (0.0 if the sizes differ)
+    pub similarity: f32,
+    /// Mean absolute per-channel difference (0-255) over all pixels
+    pub mean_error: f32,
+    /// Number of pixels whose RGBA differs
+    pub differing_pixels: u64,
+}
+
+/// Compare two images pixel by pixel
+pub fn compare_images(img: &RgbaImage, reference: &RgbaImage) -> ComparisonReport {
+    let size = img.dimensions();
+    let reference_size = reference.dimensions();
+    let total = size.0 as u64 * size.1 as u64;
+
+    if size != reference_size {
+        return ComparisonReport {
+            size,
+            reference_size,
+            similarity: 0.0,
+            mean_error: 255.0,
+            differing_pixels: total.max(reference_size.0 as u64 * reference_size.1 as u64),
+        };
+    }
+
+    let mut differing = 0u64;
+    let mut error_sum = 0u64;
+    for (a, b) in img.pixels().zip(reference.pixels()) {
+        if a != b {
+            differing += 1;
+        }
+        error_sum += (0..4).map(|c| (a[c] as i32 - b[c] as i32).unsigned_abs() as u64).sum::<u64>();
+    }
+
+    ComparisonReport {
+        size,
+        reference_size,
+        similarity: if total == 0 { 1.0 } else { (total - differing) as f32 / total as f32 },
+        mean_error: if total == 0 { 0.0 } else { error_sum as f32 / (total * 4) as f32 },
+        differing_pixels: differing,
+    }
+}
+
+/// One file name from either side of a folder comparison
+#[derive(Debug, Clone, Serialize)]
+pub struct FolderComparisonEntry {
+    pub file: String,
+    /// Pixel comparison, if both sides exist and decoded
+    pub report: Option<ComparisonReport>,
+    /// Why there is no report: a missing counterpart or a decode failure
+    pub problem: Option<String>,
+    /// Report exists and its similarity meets the threshold
+    pub passed: bool,
+}
+
+/// Aggregate result of `compare_folders`
+#[derive(Debug, Clone, Serialize)]
+pub struct FolderComparison {
+    /// True when every file is paired and every pair passed
+    pub passed: bool,
+    pub min_similarity: f32,
+    /// Mean similarity over the pairs that were compared
+    pub mean_similarity: f32,
+    /// Sorted by file name
+    pub entries: Vec<FolderComparisonEntry>,
+}
+
+/// Compare every image in `output_dir` with the same-named image in
+/// `reference_dir`
+///
+/// Files present on only one side, or that fail to decode, are reported in
+/// their entry (and fail the comparison) rather than aborting it.
+pub fn compare_folders(output_dir: &Path, reference_dir: &Path, min_similarity: f32) -> Result<FolderComparison> {
+    let names = |dir: &Path| -> Result<std::collections::BTreeSet<String>> {
+        Ok(list_image_files(dir)?
+            .iter()
+            .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+            .collect())
+    };
+    let outputs = names(output_dir)?;
+    let references = names(reference_dir)?;
+
+    let entries: Vec<FolderComparisonEntry> = outputs
+        .union(&references)
+        .map(|file| {
+            let outcome = match (outputs.contains(file), references.contains(file)) {
+                (true, false) => Err("No reference image".to_string()),
+                (false, _) => Err("No output image".to_string()),
+                (true, true) => load_image(&output_dir.join(file))
+                    .and_then(|img| Ok(compare_images(&img, &load_image(&reference_dir.join(file))?)))
+                    .map_err(|e| e.to_string()),
+            };
+            match outcome {
+                Ok(report) => FolderComparisonEntry {
+                    file: file.clone(),
+                    passed: report.similarity >= min_similarity,
+                    report: Some(report),
+                    problem: None,
+                },
+                Err(problem) => FolderComparisonEntry { file: file.clone(), report: None, problem: Some(problem), passed: false },
+            }
+        })
+        .collect();
+
+    let similarities: Vec<f32> = entries.iter().filter_map(|e| e.report.as_ref().map(|r| r.similarity)).collect();
+    Ok(FolderComparison {
+        passed: !entries.is_empty() && entries.iter().all(|e| e.passed),
+        min_similarity,
+        mean_similarity: if similarities.is_empty() { 0.0 } else { similarities.iter().sum::<f32>() / similarities.len() as f32 },
+        entries,
+    })
+}
+
+// ============================================================================
+// TEXT LABELS
+// ============================================================================
+
+/// Glyph size of the built-in label font, in font pixels
+pub const GLYPH_WIDTH: u32 = 3;
+pub const GLYPH_HEIGHT: u32 = 5;
+
+/// Rows of a 3x5 glyph, most significant of the three bits on the left.
+/// Letters are uppercase only; anything unknown draws as '?'.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010], // '?'
+    }
+}
+
+/// Width in pixels of `text` drawn by `draw_text` at `scale`
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    let chars = text.chars().count() as u32;
+    (chars * (GLYPH_WIDTH + 1)).saturating_sub(1) * scale
+}
+
+/// Draw a short label with the built-in 3x5 font, each font pixel `scale`
+/// image pixels wide; anything outside the image is clipped
+pub fn draw_text(img: &mut RgbaImage, x: u32, y: u32, text: &str, scale: u32, color: Rgba<u8>) {
+    let (width, height) = img.dimensions();
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i as u32 * (GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let (px, py) = (left + col * scale + dx, y + row as u32 * scale + dy);
+                        if px < width && py < height {
+                            img.put_pixel(px, py, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// MAIN ENTRY POINT
+// ============================================================================
+
+/// Legacy entry point - processes image file with all operations
+///
+/// This function is retained for backward compatibility with existing v1 UI.
+/// For v2, use the individual operations: `normalize_alpha`, `merge_colors`, `add_outline`
+pub fn process_image(
+    input_path: PathBuf,
+    output_path: PathBuf,
+    settings: ProcessorSettings,
+    options: &SaveOptions,
+) -> Result<ProcessorResult> {
+    let target = resolve_output_path(&input_path, &output_path, options)?;
+
+    // Load image
+    let mut rgba = load_image(&input_path)?;
+    let original_size = rgba.dimensions();
+
+    // Step 1: Opacity normalization (always runs)
+    normalize_opacity_internal(&mut rgba, &settings);
+
+    // Step 2: Color simplification (if enabled)
+    let mut warnings = Vec::new();
+    let (colors_before, colors_after, clusters) = if settings.enable_color_simplify {
+        let result = merge_colors_impl(&mut rgba, settings.lab_merge_threshold, None, None, &[]);
+        warnings.extend(result.warnings);
+        (result.unique_colors_before, result.unique_colors_after, result.clusters_created)
+    } else {
+        (0, 0, 0)
+    };
+
+    // Step 3: Outline generation (if enabled and thickness > 0)
+    if settings.enable_outline && settings.outline_thickness > 0 {
+        generate_outline_internal(&mut rgba, &settings);
+    } else if settings.enable_outline {
+        warnings.push("Outline enabled but thickness is 0; outline skipped".to_string());
+    }
+
+    // Save result
+    if let Some(path) = target {
+        save_image(&rgba, &path)?;
+    }
+
+    Ok(ProcessorResult {
+        original_size,
+        unique_colors_before: colors_before,
+        unique_colors_after: colors_after,
+        clusters_created: clusters,
+        warnings,
+    })
+}
+
+// ============================================================================
+// V2 IN-MEMORY PROCESSING
+// ============================================================================
+
+/// File extensions treated as images when scanning folders
+pub const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "bmp", "webp"];
+
+/// Check whether a path has a supported image extension (case-insensitive)
+pub fn is_image_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| IMAGE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// List image files directly inside a folder (non-recursive), sorted by name
+pub fn list_image_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_image_file(path))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Image formats this build can decode (by primary extension)
+pub fn supported_formats() -> Vec<&'static str> {
+    image::ImageFormat::all()
+        .filter(|f| f.reading_enabled())
+        .filter_map(|f| f.extensions_str().first().copied())
+        .collect()
+}
+
+/// Map an `image` crate error onto the matching `PixelsError` kind
+fn classify_image_error(path: &Path, err: image::ImageError) -> PixelsError {
+    match err {
+        image::ImageError::IoError(e) => {
+            PixelsError::Io(std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+        }
+        image::ImageError::Unsupported(e) => PixelsError::UnsupportedFormat(format!(
+            "{}: {} (supported: {})",
+            path.display(),
+            e,
+            supported_formats().join(", ")
+        )),
+        image::ImageError::Decoding(e) => PixelsError::Decode(format!("{}: {}", path.display(), e)),
+        image::ImageError::Limits(e) => PixelsError::Decode(format!("{}: {}", path.display(), e)),
+        other => PixelsError::Processing(format!("Failed to load {}: {}", path.display(), other)),
+    }
+}
+
+/// Suggested `PipelineConfig::max_megapixels` for interactive use (8000x8000)
+pub const DEFAULT_MAX_MEGAPIXELS: u32 = 64;
+
+/// Rough peak bytes per pixel across the heavy operations: the RGBA buffer
+/// plus a working copy, flat alpha/outline masks and the downscaler's
+/// grayscale and FFT buffers
+const ESTIMATED_BYTES_PER_PIXEL: u64 = 24;
+
+/// Approximate peak memory needed to process a `width` x `height` image
+pub fn estimate_memory_bytes(width: u32, height: u32) -> u64 {
+    width as u64 * height as u64 * ESTIMATED_BYTES_PER_PIXEL
+}
+
+/// Refuse images over `limit` megapixels (0 = no limit) before anything is
+/// allocated for them
+pub fn check_image_size(width: u32, height: u32, limit: u32) -> Result<()> {
+    let pixels = width as u64 * height as u64;
+    if limit > 0 && pixels > limit as u64 * 1_000_000 {
+        return Err(PixelsError::InvalidParameter(format!(
+            "Image is {}x{} ({:.1} MP, ~{} MB to process), over the max_megapixels limit of {} MP",
+            width,
+            height,
+            pixels as f64 / 1_000_000.0,
+            estimate_memory_bytes(width, height) / (1024 * 1024),
+            limit
+        )));
+    }
+    Ok(())
+}
+
+/// Decode an image file, reporting why it failed
+///
+/// Distinguishes I/O failures (missing file, permissions), unrecognized or
+/// unsupported formats (format sniffed from content, not the extension), and
+/// decode failures such as empty or truncated files.
+pub fn decode_image(path: &Path) -> Result<DynamicImage> {
+    decode_image_limited(path, 0)
+}
+
+/// `decode_image`, refusing images over `max_megapixels` (0 = no limit)
+/// before their pixels are allocated
+pub fn decode_image_limited(path: &Path, max_megapixels: u32) -> Result<DynamicImage> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| PixelsError::Io(std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e))))?;
+    decode_bytes_limited(&bytes, path, max_megapixels)
+}
+
+/// Decode an in-memory image file (e.g. an archive entry) the same way as
+/// `decode_image`; `path` names it in error messages
+pub fn decode_image_bytes(bytes: &[u8], path: &Path) -> Result<DynamicImage> {
+    decode_bytes_limited(bytes, path, 0)
+}
+
+fn decode_bytes_limited(bytes: &[u8], path: &Path, max_megapixels: u32) -> Result<DynamicImage> {
+    if bytes.is_empty() {
+        return Err(PixelsError::Decode(format!("{}: file is empty", path.display())));
+    }
+
+    let format = image::guess_format(bytes).map_err(|_| {
+        PixelsError::UnsupportedFormat(format!(
+            "{}: content is not a recognized image (supported: {})",
+            path.display(),
+            supported_formats().join(", ")
+        ))
+    })?;
+
+    // Size check from the header, before the pixel buffer is allocated
+    let (width, height) = image::ImageReader::with_format(std::io::Cursor::new(bytes), format)
+        .into_dimensions()
+        .map_err(|e| classify_image_error(path, e))?;
+    check_image_size(width, height, max_megapixels)?;
+
+    image::load_from_memory_with_format(bytes, format).map_err(|e| classify_image_error(path, e))
+}
+
+/// Original encoding of a source image, read from its header
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceInfo {
+    /// Detected container format (e.g. "Png")
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    /// Color type as stored in the file (e.g. "Rgba16", "La8", "L1")
+    pub color_type: String,
+    /// Bits per channel
+    pub bit_depth: u8,
+    pub channels: u8,
+    pub has_alpha: bool,
+    pub is_grayscale: bool,
+    /// Approximate peak memory needed to process the image
+    #[serde(default)]
+    pub estimated_memory_bytes: u64,
+    /// Information lost when the image is loaded as 8-bit RGBA
+    pub warnings: Vec<String>,
+}
+
+/// Inspect a source image's color type and bit depth without decoding pixels
+pub fn inspect_image(path: &Path) -> Result<SourceInfo> {
+    use image::{ExtendedColorType, ImageDecoder};
+
+    let reader = image::ImageReader::open(path)
+        .map_err(|e| PixelsError::Io(std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e))))?
+        .with_guessed_format()?;
+    let format = reader.format().ok_or_else(|| {
+        PixelsError::UnsupportedFormat(format!(
+            "{}: content is not a recognized image (supported: {})",
+            path.display(),
+            supported_formats().join(", ")
+        ))
+    })?;
+    let decoder = reader.into_decoder().map_err(|e| classify_image_error(path, e))?;
+    let (width, height) = decoder.dimensions();
+    let color = decoder.original_color_type();
+
+    let channels = color.channel_count();
+    let bit_depth = if channels > 0 { (color.bits_per_pixel() / channels as u16) as u8 } else { 0 };
+    let is_grayscale = matches!(
+        color,
+        ExtendedColorType::L1
+            | ExtendedColorType::L2
+            | ExtendedColorType::L4
+            | ExtendedColorType::L8
+            | ExtendedColorType::L16
+            | ExtendedColorType::La1
+            | ExtendedColorType::La2
+            | ExtendedColorType::La4
+            | ExtendedColorType::La8
+            | ExtendedColorType::La16
+    );
+    let has_alpha = matches!(channels, 2 | 4);
+
+    let mut warnings = Vec::new();
+    if bit_depth > 8 {
+        warnings.push(format!(
+            "Source is {}-bit per channel; precision is reduced to 8-bit when loaded",
+            bit_depth
+        ));
+    }
+
+    Ok(SourceInfo {
+        format: format!("{:?}", format),
+        width,
+        height,
+        color_type: format!("{:?}", color),
+        bit_depth,
+        channels,
+        has_alpha,
+        is_grayscale,
+        estimated_memory_bytes: estimate_memory_bytes(width, height),
+        warnings,
+    })
+}
+
+/// Load an image from disk into memory
+pub fn load_image(path: &Path) -> Result<RgbaImage> {
+    Ok(decode_image(path)?.to_rgba8())
+}
+
+/// Save an in-memory image to disk
+pub fn save_image(img: &RgbaImage, path: &PathBuf) -> Result<()> {
+    // Ensure output directory exists
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    img.save(path)?;
+    Ok(())
+}
+
+/// True for images with nothing worth processing: zero area, a single
+/// pixel, or no visible pixels. Operations treat these as a no-op.
+pub fn is_empty_or_tiny(img: &RgbaImage) -> bool {
+    let (width, height) = img.dimensions();
+    width as u64 * height as u64 <= 1 || img.pixels().all(|p| p[3] == 0)
+}
+
+/// Shrink an image to fit within `max_size` x `max_size` (nearest-neighbor,
+/// so pixel art stays crisp). Images already small enough are returned as-is.
+pub fn make_thumbnail(img: &RgbaImage, max_size: u32) -> RgbaImage {
+    let (width, height) = img.dimensions();
+    if max_size == 0 || (width <= max_size && height <= max_size) {
+        return img.clone();
+    }
+    let scale = max_size as f32 / width.max(height) as f32;
+    let thumb_w = ((width as f32 * scale).round() as u32).max(1);
+    let thumb_h = ((height as f32 * scale).round() as u32).max(1);
+    image::imageops::resize(img, thumb_w, thumb_h, image::imageops::FilterType::Nearest)
+}
+
+/// Check whether two paths refer to the same file
+pub(crate) fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(ca), Ok(cb)) => ca == cb,
+        _ => a == b,
+    }
+}
+
+/// Decide where an output should be written given the save options
+///
+/// Returns `Ok(None)` when the write should be skipped. Errors if `output`
+/// is the input file and `allow_in_place` is not set, or if the file exists
+/// under `OverwritePolicy::Error`.
+pub fn resolve_output_path(input: &Path, output: &Path, options: &SaveOptions) -> Result<Option<PathBuf>> {
+    if !options.allow_in_place && same_file(input, output) {
+        return Err(PixelsError::InvalidParameter(format!(
+            "Output path {} is the input file; set allow_in_place to overwrite the original",
+            output.display()
+        )));
+    }
+
+    if !output.exists() {
+        return Ok(Some(output.to_path_buf()));
+    }
+
+    match options.overwrite_policy {
+        OverwritePolicy::Overwrite => Ok(Some(output.to_path_buf())),
+        OverwritePolicy::Skip => Ok(None),
+        OverwritePolicy::Error => Err(PixelsError::InvalidParameter(format!(
+            "Output file {} already exists",
+            output.display()
+        ))),
+        OverwritePolicy::RenameWithSuffix => {
+            let stem = output.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            let ext = output.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+            let mut n = 1u32;
+            loop {
+                let candidate = output.with_file_name(format!("{}_{}{}", stem, n, ext));
+                if !candidate.exists() {
+                    return Ok(Some(candidate));
+                }
+                n += 1;
+            }
+        }
+    }
+}
+
+/// Save an image produced from `input`, honoring the save options
+/// Returns the path written, or None if the write was skipped
+pub fn save_image_with_options(
+    img: &RgbaImage,
+    input: &Path,
+    output: &Path,
+    options: &SaveOptions,
+) -> Result<Option<PathBuf>> {
+    match resolve_output_path(input, output, options)? {
+        Some(path) => {
+            save_image(img, &path)?;
+            Ok(Some(path))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Load `input`, edit it with `op`, and save it to `output` honoring the
+/// save options
+///
+/// The output path is checked before any work is done, so a refused
+/// in-place write fails fast. A write skipped by the overwrite policy still
+/// runs `op` and returns its result.
+pub fn edit_file<T>(
+    input: &Path,
+    output: &Path,
+    options: &SaveOptions,
+    op: impl FnOnce(&mut RgbaImage) -> Result<T>,
+) -> Result<T> {
+    let target = resolve_output_path(input, output, options)?;
+    let mut img = load_image(input)?;
+    let result = op(&mut img)?;
+    if let Some(path) = target {
+        save_image(&img, &path)?;
+    }
+    Ok(result)
+}
+
+/// Encode image as PNG bytes (for preview/transfer without file I/O)
+pub fn encode_png(img: &RgbaImage) -> Result<Vec<u8>> {
+    use std::io::Cursor;
+    let mut buffer = Cursor::new(Vec::new());
+    img.write_to(&mut buffer, image::ImageFormat::Png)
+        .map_err(|e| PixelsError::Processing(format!("Failed to encode PNG: {}", e)))?;
+    Ok(buffer.into_inner())
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_neighbors_4way() {
+        let neighbors = get_neighbors(5, 5, 10, 10, &Connectivity::Four);
+        assert_eq!(neighbors.len(), 4);
+    }
+
+    #[test]
+    fn test_neighbors_8way() {
+        let neighbors = get_neighbors(5, 5, 10, 10, &Connectivity::Eight);
+        assert_eq!(neighbors.len(), 8);
+    }
+
+    #[test]
+    fn test_neighbors_corner() {
+        let n4 = get_neighbors(0, 0, 10, 10, &Connectivity::Four);
+        assert_eq!(n4.len(), 2);
+        let n8 = get_neighbors(0, 0, 10, 10, &Connectivity::Eight);
+        assert_eq!(n8.len(), 3);
+    }
+
+    #[test]
+    fn test_thin_outline_3px_to_1px() {
+        let outline = Rgba([17, 6, 2, 255]);
+        let fill = Rgba([200, 40, 40, 255]);
+        let mut img = RgbaImage::from_pixel(16, 16, Rgba([0, 0, 0, 0]));
+        for y in 3..13 {
+            for x in 3..13 {
+                let ring = (x - 3).min(12 - x).min(y - 3).min(12 - y);
+                img.put_pixel(x, y, if ring < 3 { outline } else { fill });
+            }
+        }
+
+        let changed = thin_outline(&mut img, 1, &OutlineSettings::default());
+
+        // Rings 2 and 3 of a 10x10 square: 28 + 20 pixels
+        assert_eq!(changed, 48);
+        assert_eq!(*img.get_pixel(3, 3), outline);
+        assert_eq!(*img.get_pixel(12, 7), outline);
+        assert_eq!(*img.get_pixel(4, 4), fill);
+        assert_eq!(*img.get_pixel(5, 8), fill);
+        assert_eq!(*img.get_pixel(0, 0), Rgba([0, 0, 0, 0]));
+
+        // Already at target thickness: nothing left to do
+        assert_eq!(thin_outline(&mut img, 1, &OutlineSettings::default()), 0);
+    }
+
+    #[test]
+    fn test_blur_spreads_single_pixel() {
+        let mut img = RgbaImage::from_pixel(7, 7, Rgba([0, 0, 0, 0]));
+        img.put_pixel(3, 3, Rgba([255, 0, 0, 255]));
+
+        blur(&mut img, 1.0, BlurKind::Gaussian);
+
+        let center = img.get_pixel(3, 3)[3];
+        let side = img.get_pixel(4, 3)[3];
+        let diagonal = img.get_pixel(4, 4)[3];
+        let far = img.get_pixel(5, 3)[3];
+        assert!(center > side && side > diagonal && diagonal > 0);
+        assert!(side > far);
+        // Premultiplied blur keeps the color, no darkening toward black
+        assert_eq!(*img.get_pixel(4, 3), Rgba([255, 0, 0, side]));
+    }
+
+    #[test]
+    fn test_blur_uniform_unchanged() {
+        let color = Rgba([40, 120, 200, 255]);
+        for kind in [BlurKind::Box, BlurKind::Gaussian] {
+            let mut img = RgbaImage::from_pixel(6, 5, color);
+            blur(&mut img, 2.0, kind);
+            assert!(img.pixels().all(|p| *p == color), "{:?} changed a uniform image", kind);
+        }
+    }
+
+    #[test]
+    fn test_outline_snaps_to_nearest_palette_color() {
+        let mut img = RgbaImage::from_pixel(8, 8, Rgba([0, 0, 0, 0]));
+        for y in 1..7 {
+            for x in 1..7 {
+                img.put_pixel(x, y, Rgba([240, 240, 240, 255]));
+            }
+        }
+        img.put_pixel(3, 3, Rgba([17, 6, 2, 255]));
+        img.put_pixel(4, 4, Rgba([200, 40, 40, 255]));
+
+        let settings = OutlineSettings {
+            color_source: OutlineColorSource::NearestInPalette { target: (0, 0, 0) },
+            ..OutlineSettings::default()
+        };
+        add_outline(&mut img, &settings);
+
+        assert_eq!(*img.get_pixel(1, 1), Rgba([17, 6, 2, 255]));
+        assert_eq!(*img.get_pixel(6, 3), Rgba([17, 6, 2, 255]));
+        let palette: HashSet<_> = img.pixels().filter(|p| p[3] > 0).map(|p| (p[0], p[1], p[2])).collect();
+        assert_eq!(palette.len(), 3, "outline must not add a color");
+    }
+
+    #[test]
+    fn test_load_image_reports_distinct_error_kinds() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+
+        let empty = dir.join("empty.png");
+        std::fs::write(&empty, b"").unwrap();
+        let text = dir.join("notes.png");
+        std::fs::write(&text, b"just some text, not pixels").unwrap();
+        let truncated = dir.join("truncated.png");
+        let mut png = encode_png(&RgbaImage::from_pixel(8, 8, Rgba([1, 2, 3, 255]))).unwrap();
+        png.truncate(png.len() / 2);
+        std::fs::write(&truncated, &png).unwrap();
+
+        assert!(matches!(load_image(&empty), Err(PixelsError::Decode(_))));
+        match load_image(&text) {
+            Err(PixelsError::UnsupportedFormat(msg)) => assert!(msg.contains("png"), "{}", msg),
+            other => panic!("expected UnsupportedFormat, got {:?}", other.map(|_| ())),
+        }
+        assert!(!matches!(load_image(&truncated), Err(PixelsError::UnsupportedFormat(_)) | Ok(_)));
+        assert!(matches!(load_image(&dir.join("missing.png")), Err(PixelsError::Io(_))));
+    }
+
+    #[test]
+    fn test_quantize_alpha_four_levels() {
+        let mut img = RgbaImage::new(6, 1);
+        for (x, a) in [0u8, 40, 43, 127, 200, 250].iter().enumerate() {
+            img.put_pixel(x as u32, 0, Rgba([10, 20, 30, *a]));
+        }
+        let settings = AlphaSettings {
+            mode: AlphaMode::Quantize { levels: 4 },
+            ..AlphaSettings::default()
+        };
+
+        normalize_alpha(&mut img, &settings);
+        let alphas: Vec<u8> = img.pixels().map(|p| p[3]).collect();
+        assert_eq!(alphas, vec![0, 0, 85, 85, 170, 255]);
+
+        // Idempotent
+        normalize_alpha(&mut img, &settings);
+        assert_eq!(img.pixels().map(|p| p[3]).collect::<Vec<_>>(), alphas);
+    }
+
+    #[test]
+    fn test_enforce_symmetry_left_onto_right() {
+        let mut img = RgbaImage::from_fn(5, 4, |x, y| Rgba([(x * 40) as u8, (y * 60) as u8, 7, 255]));
+        let original = img.clone();
+
+        enforce_symmetry(&mut img, SymmetryAxis::Vertical, Side::Left);
+
+        for y in 0..4 {
+            for x in 0..5 {
+                assert_eq!(img.get_pixel(x, y), img.get_pixel(4 - x, y));
+            }
+            // Source half and center column are preserved
+            for x in 0..3 {
+                assert_eq!(img.get_pixel(x, y), original.get_pixel(x, y));
+            }
+        }
+
+        let mut img = original.clone();
+        enforce_symmetry(&mut img, SymmetryAxis::Horizontal, Side::Bottom);
+        for x in 0..5 {
+            assert_eq!(img.get_pixel(x, 0), original.get_pixel(x, 3));
+            assert_eq!(img.get_pixel(x, 3), original.get_pixel(x, 3));
+            assert_eq!(img.get_pixel(x, 1), original.get_pixel(x, 2));
+        }
+    }
+
+    #[test]
+    fn test_merge_tiebreak_is_deterministic() {
+        // Three equal-frequency grays where clustering depends on seed order:
+        // ascending RGB order groups {100, 103} and leaves 106 on its own
+        let grays = [106u8, 100, 103];
+        for rotation in 0..3 {
+            let mut img = RgbaImage::new(3, 1);
+            for i in 0..3 {
+                let g = grays[(i + rotation) % 3];
+                img.put_pixel(i as u32, 0, Rgba([g, g, g, 255]));
+            }
+            let find = |img: &RgbaImage, original: u8| {
+                let x = (0..3).find(|&i| grays[(i + rotation) % 3] == original).unwrap();
+                *img.get_pixel(x as u32, 0)
+            };
+
+            let result = merge_colors(&mut img, &MergeSettings { threshold: 1.5, ..MergeSettings::default() });
+
+            assert_eq!(result.clusters_created, 2);
+            assert_eq!(find(&img, 100), find(&img, 103));
+            assert_ne!(find(&img, 103), find(&img, 106));
+            assert_eq!(find(&img, 106), Rgba([106, 106, 106, 255]));
+        }
+    }
+
+    #[test]
+    fn test_apply_in_region_leaves_outside_untouched() {
+        let img = RgbaImage::from_fn(8, 4, |x, y| Rgba([100 + (x % 2) as u8, 100 + (y % 2) as u8, 100, 255]));
+        let mut edited = img.clone();
+
+        let result = apply_in_region(&mut edited, Some((0, 0, 4, 4)), |sub| {
+            merge_colors(sub, &MergeSettings { threshold: 10.0, ..MergeSettings::default() })
+        });
+
+        assert_eq!(result.unique_colors_after, 1);
+        for y in 0..4 {
+            for x in 4..8 {
+                assert_eq!(edited.get_pixel(x, y), img.get_pixel(x, y));
+            }
+        }
+        assert_eq!(edited.get_pixel(0, 0), edited.get_pixel(1, 1));
+    }
+
+    #[test]
+    fn test_deartifact_cleans_noise_keeps_edge() {
+        let left = Rgba([50, 100, 200, 255]);
+        let right = Rgba([200, 60, 40, 255]);
+        let mut img = RgbaImage::from_fn(10, 6, |x, _| if x < 5 { left } else { right });
+        let clean = img.clone();
+
+        // Low-amplitude ringing near the edge and in flat areas
+        img.put_pixel(4, 2, Rgba([53, 98, 203, 255]));
+        img.put_pixel(5, 3, Rgba([197, 62, 42, 255]));
+        img.put_pixel(1, 1, Rgba([48, 102, 199, 255]));
+
+        let changed = deartifact(&mut img, 6.0);
+
+        assert_eq!(changed, 3);
+        assert_eq!(img, clean, "noise removed, edge column unchanged");
+
+        // A genuinely different detail pixel survives
+        let mut detail = clean.clone();
+        detail.put_pixel(2, 2, Rgba([255, 255, 0, 255]));
+        assert_eq!(deartifact(&mut detail, 6.0), 0);
+    }
+
+    #[test]
+    fn test_remap_to_reference_palette() {
+        let mut img = RgbaImage::from_fn(32, 2, |x, _| {
+            let v = (x * 8) as u8;
+            Rgba([v, v, (v / 2).wrapping_add(10), 255])
+        });
+        img.put_pixel(0, 1, Rgba([9, 9, 9, 0]));
+
+        let palette = [(0, 0, 0), (85, 85, 85), (170, 170, 170), (255, 255, 255)];
+        let reference = RgbaImage::from_fn(4, 1, |x, _| {
+            let (r, g, b) = palette[x as usize];
+            Rgba([r, g, b, 255])
+        });
+
+        remap_to_reference(&mut img, &reference);
+
+        let remaining: HashSet<(u8, u8, u8)> = img.pixels().filter(|p| p[3] > 0).map(|p| (p[0], p[1], p[2])).collect();
+        assert!(remaining.iter().all(|c| palette.contains(c)), "{:?}", remaining);
+        assert_eq!(remaining.len(), 4);
+        assert_eq!(*img.get_pixel(0, 1), Rgba([9, 9, 9, 0]), "transparent pixels untouched");
+    }
+
+    #[test]
+    fn test_make_tileable_fixes_gradient() {
+        let mut img = RgbaImage::from_fn(16, 12, |x, y| Rgba([(x * 15) as u8, (y * 20) as u8, 128, 255]));
+
+        let before = check_tileable(&img);
+        assert!(before.left_right_mismatch > 10.0);
+        assert!(before.top_bottom_mismatch > 10.0);
+        assert!(!before.is_tileable);
+
+        make_tileable(&mut img, 4);
+
+        let after = check_tileable(&img);
+        assert!(after.left_right_mismatch < 1.0, "{:?}", after);
+        assert!(after.top_bottom_mismatch < 1.0, "{:?}", after);
+        assert!(after.is_tileable);
+    }
+
+    #[test]
+    fn test_overwrite_policies() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let input = dir.join("in.png");
+        let output = dir.join("out.png");
+        let img = RgbaImage::from_pixel(2, 2, Rgba([1, 2, 3, 255]));
+        save_image(&img, &input).unwrap();
+        std::fs::write(&output, b"existing").unwrap();
+
+        let skip = SaveOptions { overwrite_policy: OverwritePolicy::Skip, allow_in_place: false };
+        assert_eq!(save_image_with_options(&img, &input, &output, &skip).unwrap(), None);
+        assert_eq!(std::fs::read(&output).unwrap(), b"existing");
+
+        let error = SaveOptions { overwrite_policy: OverwritePolicy::Error, allow_in_place: false };
+        let err = save_image_with_options(&img, &input, &output, &error).unwrap_err();
+        assert!(err.to_string().contains("already exists"), "{}", err);
+        assert_eq!(std::fs::read(&output).unwrap(), b"existing");
+
+        let rename = SaveOptions { overwrite_policy: OverwritePolicy::RenameWithSuffix, allow_in_place: false };
+        let written = save_image_with_options(&img, &input, &output, &rename).unwrap();
+        assert_eq!(written, Some(dir.join("out_1.png")));
+        let written = save_image_with_options(&img, &input, &output, &rename).unwrap();
+        assert_eq!(written, Some(dir.join("out_2.png")));
+
+        // Writing over the input needs an explicit opt-in
+        assert!(save_image_with_options(&img, &input, &input, &SaveOptions::default()).is_err());
+        let in_place = SaveOptions { allow_in_place: true, ..SaveOptions::default() };
+        assert!(save_image_with_options(&img, &input, &input, &in_place).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_inspect_16bit_and_grayscale_sources() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+
+        let deep_path = dir.join("deep.png");
+        let deep: image::ImageBuffer<Rgba<u16>, Vec<u16>> =
+            image::ImageBuffer::from_pixel(3, 2, Rgba([1000, 20000, 65535, 65535]));
+        deep.save(&deep_path).unwrap();
+        let info = inspect_image(&deep_path).unwrap();
+        assert_eq!(info.bit_depth, 16);
+        assert_eq!(info.channels, 4);
+        assert_eq!((info.width, info.height), (3, 2));
+        assert!(!info.warnings.is_empty());
+
+        let gray_path = dir.join("gray.png");
+        let gray = image::GrayAlphaImage::from_pixel(2, 2, image::LumaA([77, 128]));
+        gray.save(&gray_path).unwrap();
+        let info = inspect_image(&gray_path).unwrap();
+        assert!(info.is_grayscale && info.has_alpha);
+        assert_eq!(info.bit_depth, 8);
+        assert!(info.warnings.is_empty());
+        let rgba = load_image(&gray_path).unwrap();
+        assert_eq!(rgba.get_pixel(1, 1), &Rgba([77, 77, 77, 128]));
+    }
+
+    #[test]
+    fn test_outline_masked_skips_internal_transparency() {
+        // 8x8 image: transparent border ring (removed background), opaque body,
+        // and a transparent 2x2 hole in the middle that belongs to the art
+        let mut img = RgbaImage::from_pixel(8, 8, Rgba([200, 100, 50, 255]));
+        let mut background = GrayImage::new(8, 8);
+        for y in 0..8 {
+            for x in 0..8 {
+                if x == 0 || y == 0 || x == 7 || y == 7 {
+                    img.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                    background.put_pixel(x, y, image::Luma([255]));
+                }
+            }
+        }
+        for (x, y) in [(3, 3), (4, 3), (3, 4), (4, 4)] {
+            img.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+        }
+
+        let settings = OutlineSettings::default();
+        let outline = Rgba([17, 6, 2, 255]);
+
+        let mut unmasked = img.clone();
+        add_outline(&mut unmasked, &settings);
+        assert_eq!(unmasked.get_pixel(2, 3), &outline);
+
+        add_outline_masked(&mut img, &settings, &background).unwrap();
+        assert_eq!(img.get_pixel(1, 3), &outline);
+        assert_eq!(img.get_pixel(2, 3), &Rgba([200, 100, 50, 255]));
+        assert_eq!(img.get_pixel(3, 3)[3], 0);
+
+        assert!(add_outline_masked(&mut img, &settings, &GrayImage::new(4, 4)).is_err());
+    }
+
+    #[test]
+    fn test_outline_from_mask_follows_mask_not_alpha() {
+        // Fully opaque 10x10 sprite; the mask marks only a 4x4 region
+        let body = Rgba([200, 100, 50, 255]);
+        let mut img = RgbaImage::from_pixel(10, 10, body);
+        let mut mask = GrayImage::new(10, 10);
+        for y in 3..7 {
+            for x in 3..7 {
+                mask.put_pixel(x, y, image::Luma([255]));
+            }
+        }
+
+        let settings = OutlineSettings::default();
+        let outline = Rgba([17, 6, 2, 255]);
+        add_outline_from_mask(&mut img, &mask, &settings).unwrap();
+
+        // Ring on the inside of the mask boundary
+        assert_eq!(img.get_pixel(3, 3), &outline);
+        assert_eq!(img.get_pixel(6, 4), &outline);
+        assert_eq!(img.get_pixel(4, 4), &body);
+        // The image's own (alpha) edge is untouched
+        assert_eq!(img.get_pixel(0, 0), &body);
+        assert_eq!(img.pixels().filter(|p| **p == outline).count(), 12);
+
+        assert!(add_outline_from_mask(&mut img, &GrayImage::new(4, 4), &settings).is_err());
+    }
+
+    #[test]
+    fn test_apply_color_mappings_swaps_in_one_pass() {
+        let red = (200, 30, 30);
+        let blue = (30, 30, 200);
+        let green = (30, 200, 30);
+        let mut img = RgbaImage::new(3, 2);
+        for (x, c) in [red, blue, green].iter().enumerate() {
+            img.put_pixel(x as u32, 0, Rgba([c.0, c.1, c.2, 255]));
+            img.put_pixel(x as u32, 1, Rgba([c.0, c.1, c.2, 128]));
+        }
+
+        // Rotate red -> blue -> green -> red; each rule sees original colors
+        let mappings = vec![
+            (ColorMatch::Exact { color: red }, blue),
+            (ColorMatch::Exact { color: blue }, green),
+            (ColorMatch::Exact { color: green }, red),
+        ];
+        let counts = apply_color_mappings(&mut img, &mappings);
+
+        assert_eq!(counts, vec![2, 2, 2]);
+        assert_eq!(img.get_pixel(0, 0), &Rgba([30, 30, 200, 255]));
+        assert_eq!(img.get_pixel(1, 0), &Rgba([30, 200, 30, 255]));
+        assert_eq!(img.get_pixel(2, 1), &Rgba([200, 30, 30, 128]));
+
+        // A later hue rule overrides an earlier exact rule where both match
+        let mut img = RgbaImage::from_pixel(1, 1, Rgba([200, 30, 30, 255]));
+        let mappings = vec![
+            (ColorMatch::Exact { color: red }, (1, 1, 1)),
+            (ColorMatch::HueRange { min_hue: 340.0, max_hue: 20.0 }, (9, 9, 9)),
+        ];
+        assert_eq!(apply_color_mappings(&mut img, &mappings), vec![0, 1]);
+        assert_eq!(img.get_pixel(0, 0), &Rgba([9, 9, 9, 255]));
+    }
+
+    #[test]
+    fn test_pipeline_timings_only_when_requested() {
+        let img = RgbaImage::from_pixel(8, 8, Rgba([120, 60, 30, 255]));
+        let config = PipelineConfig {
+            alpha: Some(AlphaSettings::default()),
+            merge: Some(MergeSettings::default()),
+            ..PipelineConfig::default()
+        };
+
+        let mut timings = StageTimings::new(true);
+        run_pipeline(img.clone(), &config, &mut timings);
+        let stages: Vec<String> = timings.into_entries().unwrap().into_iter().map(|(s, _)| s).collect();
+        assert_eq!(stages, vec!["alpha", "merge"]);
+
+        let mut timings = StageTimings::new(false);
+        run_pipeline(img, &config, &mut timings);
+        assert!(timings.into_entries().is_none());
+    }
+
+    #[test]
+    fn test_stage_timings_keep_sub_millisecond_precision() {
+        let mut timings = StageTimings::new(true);
+        timings.time("sleep", || std::thread::sleep(std::time::Duration::from_micros(1500)));
+        timings.time("sleep", || std::thread::sleep(std::time::Duration::from_micros(1500)));
+        let entries = timings.into_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        // Whole milliseconds would have recorded 1 + 1
+        assert!(entries[0].1 >= 3.0, "{:?}", entries);
+    }
+
+    #[test]
+    fn test_alpha_aware_merge_keeps_opacity_levels_apart() {
+        let mut img = RgbaImage::new(4, 1);
+        img.put_pixel(0, 0, Rgba([100, 150, 200, 255]));
+        img.put_pixel(1, 0, Rgba([100, 150, 200, 255]));
+        img.put_pixel(2, 0, Rgba([100, 150, 200, 96]));
+        img.put_pixel(3, 0, Rgba([55, 66, 77, 0]));
+
+        let mut blind = img.clone();
+        let result = merge_colors(&mut blind, &MergeSettings::default());
+        assert_eq!(result.unique_colors_before, 1);
+
+        let settings = MergeSettings {
+            alpha_weight: Some(1.0),
+            clear_transparent_rgb: true,
+            ..MergeSettings::default()
+        };
+        let result = merge_colors(&mut img, &settings);
+        assert_eq!(result.unique_colors_before, 2);
+        assert_eq!(result.clusters_created, 2);
+        assert_eq!(img.get_pixel(0, 0)[3], 255);
+        assert_eq!(img.get_pixel(2, 0)[3], 96);
+        assert_eq!(img.get_pixel(3, 0), &Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_tint_grayscale_and_duotone() {
+        let mut img = RgbaImage::new(4, 1);
+        img.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        img.put_pixel(1, 0, Rgba([10, 200, 90, 128]));
+        img.put_pixel(2, 0, Rgba([0, 0, 0, 255]));
+        img.put_pixel(3, 0, Rgba([255, 255, 255, 255]));
+
+        let mut gray = img.clone();
+        tint(&mut gray, TintMode::Grayscale);
+        for p in gray.pixels() {
+            assert!(p[0] == p[1] && p[1] == p[2], "{:?}", p);
+        }
+        assert_eq!(gray.get_pixel(1, 0)[3], 128);
+
+        let dark = (20, 10, 60);
+        let light = (240, 220, 100);
+        tint(&mut img, TintMode::Duotone { dark, light });
+        assert_eq!(img.get_pixel(2, 0), &Rgba([20, 10, 60, 255]));
+        assert_eq!(img.get_pixel(3, 0), &Rgba([240, 220, 100, 255]));
+        for p in img.pixels() {
+            // Blends stay on the segment between the endpoints
+            assert!(p[0] >= dark.0 && p[0] <= light.0);
+            assert!(p[1] >= dark.1 && p[1] <= light.1);
+            assert!(p[2] >= dark.2.min(light.2) && p[2] <= dark.2.max(light.2));
+        }
+    }
+
+    #[test]
+    fn test_degenerate_images_are_no_ops() {
+        for (w, h) in [(0, 0), (1, 1), (1, 6), (6, 1)] {
+            let mut img = RgbaImage::new(w, h);
+            assert!(is_empty_or_tiny(&img));
+
+            let result = merge_colors(&mut img, &MergeSettings::default());
+            assert_eq!(result.clusters_created, 0);
+            add_outline(&mut img, &OutlineSettings::default());
+            assert_eq!(img, RgbaImage::new(w, h));
+        }
+
+        // A single opaque row or column is still processed, without panicking
+        let mut column = RgbaImage::from_pixel(1, 5, Rgba([9, 9, 9, 255]));
+        assert_eq!(merge_colors(&mut column, &MergeSettings::default()).clusters_created, 1);
+        add_outline(&mut column, &OutlineSettings::default());
+
+        assert!(!is_empty_or_tiny(&RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 255]))));
+    }
+
+    #[test]
+    fn test_final_thickness_outline_survives_downscale() {
+        // 6x6 native sprite centered on a 10x10 native canvas, upscaled 10x
+        let mut img = RgbaImage::new(100, 100);
+        for y in 20..80 {
+            for x in 20..80 {
+                img.put_pixel(x, y, Rgba([200, 120, 40, 255]));
+            }
+        }
+        let settings = OutlineSettings {
+            thickness_unit: ThicknessUnit::Final,
+            ..OutlineSettings::default()
+        };
+        add_outline_scaled(&mut img, &settings, 10);
+
+        let outline = Rgba([17, 6, 2, 255]);
+        assert_eq!(img.get_pixel(29, 50), &outline);
+        assert_eq!(img.get_pixel(30, 50), &Rgba([200, 120, 40, 255]));
+
+        let small = crate::downscaler::downsample_image(&img, 10, 0, 0);
+        assert_eq!(small.get_pixel(2, 5), &outline);
+        assert_eq!(small.get_pixel(3, 5), &Rgba([200, 120, 40, 255]));
+        assert_eq!(small.get_pixel(7, 5), &outline);
+        assert_eq!(small.get_pixel(6, 5), &Rgba([200, 120, 40, 255]));
+    }
+
+    #[test]
+    fn test_flatten_regions_treats_blobs_independently() {
+        // Two noisy orange blobs separated by transparency; one is slightly
+        // darker overall so its average differs from the other
+        let mut img = RgbaImage::new(12, 5);
+        for y in 0..5 {
+            for x in 0..5 {
+                let n = ((x * 3 + y * 5) % 4) as u8;
+                img.put_pixel(x, y, Rgba([200 + n, 120 + n, 40, 255]));
+                img.put_pixel(x + 7, y, Rgba([188 + n, 112 + n, 36, 255]));
+            }
+        }
+
+        let regions = flatten_regions(&mut img, 6.0, &Connectivity::Four);
+        assert_eq!(regions, 2);
+
+        let left: HashSet<_> = (0..5).flat_map(|y| (0..5).map(move |x| (x, y))).map(|(x, y)| *img.get_pixel(x, y)).collect();
+        let right: HashSet<_> = (0..5).flat_map(|y| (7..12).map(move |x| (x, y))).map(|(x, y)| *img.get_pixel(x, y)).collect();
+        assert_eq!(left.len(), 1);
+        assert_eq!(right.len(), 1);
+        assert_ne!(left, right);
+        assert_eq!(img.get_pixel(6, 2)[3], 0);
+    }
+
+    #[test]
+    fn test_normalize_alpha_by_color_uses_matching_rule() {
+        let mut img = RgbaImage::new(3, 1);
+        img.put_pixel(0, 0, Rgba([30, 60, 220, 150]));
+        img.put_pixel(1, 0, Rgba([220, 30, 30, 150]));
+        img.put_pixel(2, 0, Rgba([128, 128, 128, 150]));
+
+        let keep_translucent = AlphaSettings { low_cutoff: 50, high_min: 240, ..AlphaSettings::default() };
+        let harden = AlphaSettings { low_cutoff: 50, high_min: 100, ..AlphaSettings::default() };
+        let rules = vec![
+            (ColorMatch::HueRange { min_hue: 200.0, max_hue: 260.0 }, keep_translucent),
+            (ColorMatch::HueRange { min_hue: 340.0, max_hue: 20.0 }, harden),
+        ];
+        normalize_alpha_by_color(&mut img, &rules, &AlphaSettings::default());
+
+        assert_eq!(img.get_pixel(0, 0)[3], 150);
+        assert_eq!(img.get_pixel(1, 0)[3], 255);
+        // Gray matches no rule: default cutoff of 200 clears it
+        assert_eq!(img.get_pixel(2, 0)[3], 0);
+    }
+
+    #[test]
+    fn test_outline_antialias_softens_diagonals() {
+        // Staircase triangle: opaque where x <= y
+        let mut img = RgbaImage::new(8, 8);
+        for y in 0..8 {
+            for x in 0..=y {
+                img.put_pixel(x, y, Rgba([220, 180, 60, 255]));
+            }
+        }
+
+        let mut crisp = img.clone();
+        add_outline(&mut crisp, &OutlineSettings::default());
+        assert!(crisp.pixels().all(|p| p[3] == 0 || p[3] == 255));
+
+        let settings = OutlineSettings { antialias: true, ..OutlineSettings::default() };
+        add_outline(&mut img, &settings);
+        let partial: Vec<_> = img.pixels().filter(|p| p[3] > 0 && p[3] < 255).collect();
+        assert!(!partial.is_empty());
+        assert!(partial.iter().all(|p| (p[0], p[1], p[2]) == (17, 6, 2)));
+    }
+
+    #[test]
+    fn test_merge_prequantize_bounds_clusters() {
+        // 256x256 gradient: 65536 unique colors
+        let make = || RgbaImage::from_fn(256, 256, |x, y| Rgba([x as u8, y as u8, ((x + y) / 2) as u8, 255]));
+
+        let mut img = make();
+        let settings = MergeSettings {
+            threshold: 1.0,
+            max_unique_colors_before_prequantize: Some(4096),
+            ..MergeSettings::default()
+        };
+        let result = merge_colors(&mut img, &settings);
+        assert_eq!(result.unique_colors_before, 65536);
+        // 17 levels per channel at most
+        assert!(result.clusters_created <= 17 * 17 * 17, "{}", result.clusters_created);
+        assert!(result.unique_colors_after <= result.clusters_created);
+
+        // Guard above the color count leaves behavior unchanged
+        let mut small = RgbaImage::from_fn(4, 4, |x, y| Rgba([x as u8 * 40, y as u8 * 40, 0, 255]));
+        let mut exact = small.clone();
+        let guarded = MergeSettings { max_unique_colors_before_prequantize: Some(1000), ..MergeSettings::default() };
+        let a = merge_colors(&mut small, &guarded);
+        let b = merge_colors(&mut exact, &MergeSettings::default());
+        assert_eq!((a.clusters_created, a.unique_colors_after), (b.clusters_created, b.unique_colors_after));
+        assert_eq!(small, exact);
+    }
+
+    #[test]
+    fn test_process_tiled_outline_has_no_seams() {
+        // Two blobs straddling tile boundaries
+        let img = RgbaImage::from_fn(50, 40, |x, y| {
+            let d1 = (x as i32 - 15).pow(2) + (y as i32 - 15).pow(2);
+            let d2 = (x as i32 - 33).pow(2) + (y as i32 - 24).pow(2);
+            if d1 < 100 || d2 < 64 { Rgba([200, 120, 40, 255]) } else { Rgba([0, 0, 0, 0]) }
+        });
+        let settings = OutlineSettings { thickness: 2, ..OutlineSettings::default() };
+
+        let mut whole = img.clone();
+        add_outline(&mut whole, &settings);
+        let tiled = process_tiled(&img, 16, 2, |tile| add_outline(tile, &settings)).unwrap();
+        assert_eq!(tiled, whole);
+
+        // Without enough overlap, tile edges see false borders
+        let seamed = process_tiled(&img, 16, 0, |tile| add_outline(tile, &settings)).unwrap();
+        assert_ne!(seamed, whole);
+
+        assert!(process_tiled(&img, 0, 2, |_| {}).is_err());
+    }
+
+    #[test]
+    fn test_fallbacks_report_warnings() {
+        let mut empty = RgbaImage::new(4, 4);
+        let result = merge_colors(&mut empty, &MergeSettings::default());
+        assert!(result.warnings[0].contains("nothing to merge"));
+
+        let mut distinct = RgbaImage::from_fn(2, 2, |x, y| Rgba([x as u8 * 200, y as u8 * 200, 0, 255]));
+        let result = merge_colors(&mut distinct, &MergeSettings::default());
+        assert_eq!(result.clusters_created, 4);
+        assert!(result.warnings[0].contains("nothing merged"), "{:?}", result.warnings);
+
+        let mut noisy = RgbaImage::from_fn(64, 64, |x, y| Rgba([x as u8 * 4, y as u8 * 4, 0, 255]));
+        let settings = MergeSettings { max_unique_colors_before_prequantize: Some(100), ..MergeSettings::default() };
+        let result = merge_colors(&mut noisy, &settings);
+        assert!(result.warnings.iter().any(|w| w.contains("16-step grid")), "{:?}", result.warnings);
+
+        let tmp = tempfile::tempdir().unwrap();
+
+        let dir = tmp.path().to_path_buf();
+        RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255])).save(dir.join("in.png")).unwrap();
+        let settings = ProcessorSettings { outline_thickness: 0, ..ProcessorSettings::default() };
+        let result = process_image(dir.join("in.png"), dir.join("out.png"), settings, &SaveOptions::default()).unwrap();
+        assert!(result.warnings.iter().any(|w| w.contains("outline skipped")), "{:?}", result.warnings);
+    }
+
+    #[test]
+    fn test_quantize_to_size_budget() {
+        // Noisy gradient: many near-identical colors that compress poorly
+        let mut state = 12345u32;
+        let img = RgbaImage::from_fn(64, 64, |x, y| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            let n = (state >> 16) as u8 % 12;
+            Rgba([(x * 3) as u8 + n, (y * 3) as u8 + n, 100 + n, 255])
+        });
+        let original = encode_png(&img).unwrap().len();
+
+        let budget = original / 2;
+        let (out, size) = quantize_to_size(&img, budget).unwrap();
+        assert!(size <= budget, "{} > {}", size, budget);
+        assert_eq!(encode_png(&out).unwrap().len(), size);
+
+        let (_, report) = quantize_to_size_report(&img, 10).unwrap();
+        assert!(!report.fits);
+        assert!(report.size < original);
+        assert!(report.warnings[0].contains("Could not reach 10 bytes"));
+    }
+
+    #[test]
+    fn test_bleed_edges_carries_edge_color_into_transparency() {
+        // Red 2x2 block in the middle of a transparent black 6x6 canvas
+        let mut img = RgbaImage::from_pixel(6, 6, Rgba([0, 0, 0, 0]));
+        for (x, y) in [(2, 2), (3, 2), (2, 3), (3, 3)] {
+            img.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+        }
+
+        bleed_edges(&mut img, 1);
+        assert_eq!(*img.get_pixel(1, 2), Rgba([255, 0, 0, 0]));
+        // Two pixels out is only reached by a second iteration
+        assert_eq!(*img.get_pixel(0, 2), Rgba([0, 0, 0, 0]));
+
+        bleed_edges(&mut img, 2);
+        assert_eq!(*img.get_pixel(0, 0), Rgba([255, 0, 0, 0]));
+        assert!(img.pixels().all(|p| p[3] == 0 || *p == Rgba([255, 0, 0, 255])));
+    }
+
+    #[test]
+    fn test_over_limit_image_is_rejected() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let path = dir.join("wide.png");
+        RgbaImage::new(1500, 1000).save(&path).unwrap();
+
+        assert!(check_image_size(10_000, 10_000, 0).is_ok());
+        assert!(matches!(
+            check_image_size(10_000, 10_000, DEFAULT_MAX_MEGAPIXELS),
+            Err(PixelsError::InvalidParameter(_))
+        ));
+
+        // 1.5 MP against a 1 MP limit
+        match decode_image_limited(&path, 1) {
+            Err(PixelsError::InvalidParameter(msg)) => assert!(msg.contains("max_megapixels limit of 1 MP"), "{}", msg),
+            other => panic!("expected InvalidParameter, got {:?}", other.map(|i| (i.width(), i.height()))),
+        }
+        assert!(decode_image_limited(&path, 2).is_ok());
+        // No limit unless one is asked for
+        assert!(decode_image(&path).is_ok());
+
+        let config = PipelineConfig { max_megapixels: 1, ..PipelineConfig::default() };
+        let output = dir.join("out.png");
+        let result = process_and_save(&path, &output, &config, &SaveOptions::default(), false, false, StageTimings::new(false));
+        assert!(matches!(result, Err(PixelsError::InvalidParameter(_))));
+        assert!(!output.exists());
+
+        let info = inspect_image(&path).unwrap();
+        assert_eq!(info.estimated_memory_bytes, estimate_memory_bytes(1500, 1000));
+        assert!(info.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_detect_outline_breaks_ties_deterministically() {
+        // Edge is 4x A, 4x B, 2x C. B is within OUTLINE_MATCH_DELTA_E of both
+        // A and C but A and C are not, so only B clears the 80% bar
+        let (a, b, c) = (Rgba([100, 100, 100, 255]), Rgba([107, 100, 100, 255]), Rgba([114, 100, 100, 255]));
+        let mut img = RgbaImage::new(12, 3);
+        for (x, color) in [a, a, a, a, b, b, b, b, c, c].into_iter().enumerate() {
+            img.put_pixel(x as u32 + 1, 1, color);
+        }
+
+        for _ in 0..20 {
+            let result = detect_outline(&img);
+            assert_eq!(result.outline_color, Some((107, 100, 100, 255)));
+            assert_eq!(result.confidence, 1.0);
+        }
+    }
+
+    #[test]
+    fn test_process_files_writes_listed_files_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+        std::fs::create_dir_all(root.join("a")).unwrap();
+        std::fs::create_dir_all(root.join("b")).unwrap();
+        RgbaImage::from_pixel(6, 6, Rgba([200, 10, 10, 120])).save(root.join("a/hero.png")).unwrap();
+        RgbaImage::from_pixel(4, 5, Rgba([10, 200, 10, 255])).save(root.join("b/slime.png")).unwrap();
+        RgbaImage::new(3, 3).save(root.join("a/skipped.png")).unwrap();
+
+        let config = PipelineConfig { alpha: Some(AlphaSettings::default()), ..PipelineConfig::default() };
+        let inputs = vec![root.join("a/hero.png"), root.join("b/slime.png")];
+        let out = root.join("out");
+        let entries = process_files(&inputs, &out, &config, &SaveOptions::default(), false).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|e| e.error.is_none()));
+        assert_eq!(entries[0].final_size, Some((6, 6)));
+        assert_eq!(entries[1].final_size, Some((4, 5)));
+        assert!(out.join("hero.png").exists());
+        assert!(out.join("slime.png").exists());
+        assert!(!out.join("skipped.png").exists());
+
+        // Same base name from two folders collides in the output folder
+        let clash = vec![root.join("a/hero.png"), root.join("b/hero.png")];
+        assert!(process_files(&clash, &out, &config, &SaveOptions::default(), false).is_err());
+    }
+
+    #[test]
+    fn test_compare_folder_to_itself() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        std::fs::create_dir_all(dir.join("other")).unwrap();
+        RgbaImage::from_pixel(5, 4, Rgba([10, 20, 30, 255])).save(dir.join("a.png")).unwrap();
+        RgbaImage::from_fn(6, 6, |x, y| Rgba([x as u8 * 40, y as u8 * 40, 0, 255])).save(dir.join("b.png")).unwrap();
+
+        let result = compare_folders(&dir, &dir, DEFAULT_MIN_SIMILARITY).unwrap();
+        assert!(result.passed);
+        assert_eq!(result.mean_similarity, 1.0);
+        assert_eq!(result.entries.len(), 2);
+        for entry in &result.entries {
+            let report = entry.report.as_ref().unwrap();
+            assert_eq!((report.similarity, report.mean_error, report.differing_pixels), (1.0, 0.0, 0));
+        }
+
+        // Counterparts missing on either side are reported, not errors
+        RgbaImage::from_pixel(5, 4, Rgba([10, 20, 31, 255])).save(dir.join("other/a.png")).unwrap();
+        RgbaImage::new(2, 2).save(dir.join("other/c.png")).unwrap();
+        let result = compare_folders(&dir.join("other"), &dir, DEFAULT_MIN_SIMILARITY).unwrap();
+        assert!(!result.passed);
+        let problems: Vec<_> = result.entries.iter().map(|e| (e.file.as_str(), e.problem.as_deref())).collect();
+        assert_eq!(problems, [("a.png", None), ("b.png", Some("No output image")), ("c.png", Some("No reference image"))]);
+        assert_eq!(result.entries[0].report.as_ref().unwrap().similarity, 0.0);
+    }
+
+    #[test]
+    fn test_alpha_midpoint_outline_sits_inside_soft_fringe() {
+        // Opaque disc of radius 7.5 with a one-pixel fringe at alpha 100
+        let circle = RgbaImage::from_fn(24, 24, |x, y| {
+            let d = ((x as f32 - 12.0).powi(2) + (y as f32 - 12.0).powi(2)).sqrt();
+            let a = if d <= 7.5 { 255 } else if d <= 8.5 { 100 } else { 0 };
+            Rgba([200, 100, 50, a])
+        });
+        let outline = Rgba([17, 6, 2, 255]);
+
+        let mut hard = circle.clone();
+        add_outline(&mut hard, &OutlineSettings::default());
+        let mut soft = circle.clone();
+        add_outline(&mut soft, &OutlineSettings { alpha_midpoint: Some(128), ..OutlineSettings::default() });
+
+        // Along each axis the midpoint outline is one pixel further in
+        for (dx, dy) in [(1i32, 0i32), (-1, 0), (0, 1), (0, -1)] {
+            let at = |r: i32| ((12 + dx * r) as u32, (12 + dy * r) as u32);
+            assert_eq!(hard.get_pixel(at(8).0, at(8).1), &outline);
+            assert_ne!(hard.get_pixel(at(7).0, at(7).1), &outline);
+            assert_eq!(soft.get_pixel(at(7).0, at(7).1), &outline);
+            assert_eq!(soft.get_pixel(at(8).0, at(8).1)[3], 100);
+        }
+    }
+
+    #[test]
+    fn test_process_zip_keeps_entry_paths() {
+        use crate::archive::{read_zip, write_zip, ZipEntry};
+
+        let tmp = tempfile::tempdir().unwrap();
+
+        let dir = tmp.path().to_path_buf();
+
+        let hero = encode_png(&RgbaImage::from_pixel(6, 6, Rgba([200, 10, 10, 120]))).unwrap();
+        let slime = encode_png(&RgbaImage::from_pixel(4, 5, Rgba([10, 200, 10, 255]))).unwrap();
+        let input = write_zip(&[
+            ZipEntry { name: "sprites/hero.png".to_string(), data: hero.clone() },
+            ZipEntry { name: "slime.png".to_string(), data: slime },
+        ])
+        .unwrap();
+        std::fs::write(dir.join("in.zip"), input).unwrap();
+
+        let config = PipelineConfig { alpha: Some(AlphaSettings::default()), ..PipelineConfig::default() };
+        let report = process_zip(&dir.join("in.zip"), &dir.join("out.zip"), &config, &SaveOptions::default(), false).unwrap();
+        assert_eq!(report.len(), 2);
+        assert!(report.iter().all(|e| e.error.is_none()));
+
+        let output = read_zip(&std::fs::read(dir.join("out.zip")).unwrap()).unwrap();
+        let names: Vec<&str> = output.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["sprites/hero.png", "slime.png"]);
+        let processed = image::load_from_memory(&output[0].data).unwrap().to_rgba8();
+        assert_eq!(processed.dimensions(), (6, 6));
+        assert_eq!(processed.get_pixel(0, 0)[3], 0);
+    }
+
+    #[test]
+    fn test_process_files_resumes_from_checkpoint() {
+        use crate::downscaler::checkpoint_path;
+
+        let tmp = tempfile::tempdir().unwrap();
+
+        let root = tmp.path().to_path_buf();
+        std::fs::create_dir_all(root.join("in")).unwrap();
+        RgbaImage::from_pixel(4, 4, Rgba([200, 10, 10, 255])).save(root.join("in/a.png")).unwrap();
+        // An unreadable file stands in for a run that stopped before finishing
+        std::fs::write(root.join("in/b.png"), b"not an image").unwrap();
+
+        let config = PipelineConfig::default();
+        let inputs = vec![root.join("in/a.png"), root.join("in/b.png")];
+        let out = root.join("out");
+        let first = process_files(&inputs, &out, &config, &SaveOptions::default(), false).unwrap();
+        assert!(first[1].error.is_some());
+        assert!(checkpoint_path(&out).exists());
+        assert!(!root.join("in/.pixels").exists());
+
+        std::fs::remove_file(out.join("a.png")).unwrap();
+        RgbaImage::from_pixel(4, 4, Rgba([10, 200, 10, 255])).save(root.join("in/b.png")).unwrap();
+        let resumed = process_files(&inputs, &out, &config, &SaveOptions::default(), true).unwrap();
+
+        assert!(resumed[0].skipped && !out.join("a.png").exists());
+        assert!(!resumed[1].skipped && resumed[1].error.is_none());
+        assert!(out.join("b.png").exists());
+        assert!(!checkpoint_path(&out).exists());
+    }
+
+    #[test]
+    fn test_process_zip_resumes_from_staging() {
+        use crate::archive::{read_zip, write_zip, ZipEntry};
+
+        let tmp = tempfile::tempdir().unwrap();
+
+        let dir = tmp.path().to_path_buf();
+        let hero = encode_png(&RgbaImage::from_pixel(6, 6, Rgba([200, 10, 10, 255]))).unwrap();
+        let slime = encode_png(&RgbaImage::from_pixel(4, 5, Rgba([10, 200, 10, 255]))).unwrap();
+        let write_input = |second: &[u8]| {
+            let zip = write_zip(&[
+                ZipEntry { name: "hero.png".to_string(), data: hero.clone() },
+                ZipEntry { name: "slime.png".to_string(), data: second.to_vec() },
+            ])
+            .unwrap();
+            std::fs::write(dir.join("in.zip"), zip).unwrap();
+        };
+
+        write_input(b"not an image");
+        let config = PipelineConfig::default();
+        let (input, output) = (dir.join("in.zip"), dir.join("out.zip"));
+        let first = process_zip(&input, &output, &config, &SaveOptions::default(), false).unwrap();
+        assert!(first[1].error.is_some());
+        assert!(zip_staging_dir(&output).is_dir());
+
+        write_input(&slime);
+        let resumed = process_zip(&input, &output, &config, &SaveOptions::default(), true).unwrap();
+        assert!(resumed[0].skipped && !resumed[1].skipped);
+        assert!(resumed.iter().all(|e| e.error.is_none()));
+        assert!(!zip_staging_dir(&output).exists());
+
+        // The reused image still lands in the rewritten archive
+        let written = read_zip(&std::fs::read(&output).unwrap()).unwrap();
+        let names: Vec<&str> = written.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["hero.png", "slime.png"]);
+        assert_eq!(image::load_from_memory(&written[0].data).unwrap().to_rgba8().dimensions(), (6, 6));
+    }
+
+    #[test]
+    fn test_outline_color_from_reference() {
+        const BROWN: (u8, u8, u8, u8) = (17, 6, 2, 255);
+        // Reference sprite: green body inside a dark-brown outline
+        let reference = RgbaImage::from_fn(12, 12, |x, y| match (x, y) {
+            (2..=9, 2..=9) if x == 2 || x == 9 || y == 2 || y == 9 => Rgba([BROWN.0, BROWN.1, BROWN.2, 255]),
+            (3..=8, 3..=8) => Rgba([40, 180, 60, 255]),
+            _ => Rgba([0, 0, 0, 0]),
+        });
+        assert_eq!(outline_color_from_reference(&reference), BROWN);
+        assert_eq!(outline_color_from_reference(&RgbaImage::new(4, 4)), OutlineSettings::default().color);
+
+        let tmp = tempfile::tempdir().unwrap();
+
+        let dir = tmp.path().to_path_buf();
+        reference.save(dir.join("style.png")).unwrap();
+
+        let mut sprite = RgbaImage::from_fn(8, 8, |x, y| {
+            if (2..6).contains(&x) && (2..6).contains(&y) { Rgba([30, 30, 220, 255]) } else { Rgba([0, 0, 0, 0]) }
+        });
+        let settings = OutlineSettings {
+            color: (255, 0, 0, 255),
+            color_source: OutlineColorSource::FromReference { path: dir.join("style.png").to_string_lossy().to_string() },
+            ..OutlineSettings::default()
+        };
+        add_outline(&mut sprite, &settings);
+        assert_eq!(*sprite.get_pixel(2, 2), Rgba([BROWN.0, BROWN.1, BROWN.2, 255]));
+    }
+
+    #[test]
+    fn test_merge_to_reduction_halves_gradient() {
+        let img = RgbaImage::from_fn(16, 16, |x, y| Rgba([(x * 16) as u8, (y * 16) as u8, 128, 255]));
+        let mut merged = img.clone();
+        let result = merge_to_reduction(&mut merged, 0.5);
+
+        assert_eq!(result.unique_colors_before, 256);
+        let fraction = result.unique_colors_after as f32 / result.unique_colors_before as f32;
+        assert!((fraction - 0.5).abs() <= 0.05, "kept {} of 256 colors", result.unique_colors_after);
+        let remaining: HashSet<_> = merged.pixels().map(|p| (p[0], p[1], p[2])).collect();
+        assert_eq!(remaining.len(), result.unique_colors_after);
+
+        // Keeping everything leaves the image as it was
+        let mut untouched = img.clone();
+        let result = merge_to_reduction(&mut untouched, 1.0);
+        assert_eq!(untouched, img);
+        assert_eq!((result.unique_colors_before, result.unique_colors_after), (256, 256));
+
+        // Nothing visible: no search, no target of 1 for 0 colors
+        let empty = RgbaImage::new(4, 4);
+        let mut cleared = empty.clone();
+        let result = merge_to_reduction(&mut cleared, 0.5);
+        assert_eq!(cleared, empty);
+        assert_eq!((result.unique_colors_before, result.unique_colors_after), (0, 0));
+        assert_eq!(result.warnings, vec!["No visible pixels to merge; image unchanged".to_string()]);
+    }
+
+    #[test]
+    fn test_recipe_replays_pipeline() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+
+        let img = RgbaImage::from_fn(12, 12, |x, y| {
+            if (2..10).contains(&x) && (3..9).contains(&y) {
+                Rgba([(x * 20) as u8, (y * 20) as u8, 90, if x == 2 { 150 } else { 255 }])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        img.save(dir.join("in.png")).unwrap();
+
+        let config = PipelineConfig {
+            alpha: Some(AlphaSettings::default()),
+            merge: Some(MergeSettings { threshold: 12.0, ..MergeSettings::default() }),
+            outline: Some(OutlineSettings::default()),
+            bleed_iterations: Some(1),
+            ..PipelineConfig::default()
+        };
+        save_recipe(&dir.join("recipe.json"), &config).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(dir.join("recipe.json")).unwrap()).unwrap();
+        assert_eq!(json["schema_version"], RECIPE_SCHEMA_VERSION);
+        let stages: Vec<&str> = json["stages"].as_array().unwrap().iter().map(|s| s["stage"].as_str().unwrap()).collect();
+        assert_eq!(stages, vec!["alpha", "merge", "outline", "bleed"]);
+
+        apply_recipe(&dir.join("recipe.json"), &dir.join("in.png"), &dir.join("out.png"), &SaveOptions::default()).unwrap();
+        let direct = run_pipeline(img, &config, &mut StageTimings::new(false));
+        assert_eq!(load_image(&dir.join("out.png")).unwrap(), direct);
+
+        // Stages out of pipeline order are rejected
+        let shuffled = Recipe {
+            schema_version: RECIPE_SCHEMA_VERSION,
+            stages: vec![RecipeStage::Merge(MergeSettings::default()), RecipeStage::Alpha(AlphaSettings::default())],
+        };
+        assert!(shuffled.to_config().is_err());
+    }
+
+    #[test]
+    fn test_outline_before_alpha_follows_soft_edge() {
+        // Opaque 4x4 core inside a one-pixel ring of alpha 100
+        let img = RgbaImage::from_fn(10, 10, |x, y| match (x, y) {
+            (3..=6, 3..=6) => Rgba([200, 120, 60, 255]),
+            (2..=7, 2..=7) => Rgba([200, 120, 60, 100]),
+            _ => Rgba([0, 0, 0, 0]),
+        });
+        let outline = OutlineSettings::default();
+        let (r, g, b, a) = outline.color;
+        let config = PipelineConfig {
+            alpha: Some(AlphaSettings::default()),
+            outline: Some(outline),
+            ..PipelineConfig::default()
+        };
+
+        // Default: the ring is hardened away first, so the outline sits on the core
+        let hardened_first = run_pipeline(img.clone(), &config, &mut StageTimings::new(false));
+        assert_eq!(*hardened_first.get_pixel(3, 3), Rgba([r, g, b, a]));
+        assert_eq!(hardened_first.get_pixel(2, 2)[3], 0);
+
+        // Outline first: it lands on the soft ring, which then survives hardening
+        let config = PipelineConfig { outline_before_alpha: true, ..config };
+        let outlined_first = run_pipeline(img, &config, &mut StageTimings::new(false));
+        assert_eq!(*outlined_first.get_pixel(2, 2), Rgba([r, g, b, a]));
+        assert_eq!(*outlined_first.get_pixel(3, 3), Rgba([200, 120, 60, 255]));
+
+        // The choice survives a recipe round trip
+        let stages = Recipe::from_config(&config).stages;
+        assert!(matches!(stages[0], RecipeStage::Outline(_)));
+        assert!(Recipe { schema_version: RECIPE_SCHEMA_VERSION, stages }.to_config().unwrap().outline_before_alpha);
+    }
+
+    #[test]
+    fn test_merge_locked_color_survives() {
+        let mut img = RgbaImage::new(4, 1);
+        for x in 0..3 {
+            img.put_pixel(x, 0, Rgba([200, 30, 30, 255]));
+        }
+        img.put_pixel(3, 0, Rgba([204, 32, 30, 255]));
+
+        let mut unlocked = img.clone();
+        assert_eq!(merge_colors(&mut unlocked, &MergeSettings::default()).clusters_created, 1);
+
+        let settings = MergeSettings { locked_colors: vec![(204, 32, 30)], ..MergeSettings::default() };
+        let result = merge_colors(&mut img, &settings);
+        assert_eq!(result.clusters_created, 2);
+        assert_eq!(*img.get_pixel(3, 0), Rgba([204, 32, 30, 255]));
+        assert_eq!(*img.get_pixel(0, 0), Rgba([200, 30, 30, 255]));
+
+        // Two grays that would average onto a locked gray stay apart
+        let mut grays = RgbaImage::new(2, 1);
+        grays.put_pixel(0, 0, Rgba([100, 100, 100, 255]));
+        grays.put_pixel(1, 0, Rgba([104, 104, 104, 255]));
+        assert_eq!(merge_colors(&mut grays.clone(), &MergeSettings::default()).clusters_created, 1);
+        let settings = MergeSettings { locked_colors: vec![(102, 102, 102)], ..MergeSettings::default() };
+        assert_eq!(merge_colors(&mut grays, &settings).clusters_created, 2);
+    }
+
+    #[test]
+    fn test_merge_protects_outline_color() {
+        // A 1px outline ring around a block of slightly lighter darks
+        let outline = Rgba([17, 6, 2, 255]);
+        let mut img = RgbaImage::from_fn(6, 6, |x, y| {
+            if x == 0 || y == 0 || x == 5 || y == 5 {
+                outline
+            } else if (x + y) % 2 == 0 {
+                Rgba([30, 20, 15, 255])
+            } else {
+                Rgba([40, 28, 20, 255])
+            }
+        });
+        img.put_pixel(0, 0, Rgba([18, 7, 2, 255]));
+
+        let aggressive = MergeSettings { threshold: 30.0, ..MergeSettings::default() };
+        let mut unprotected = img.clone();
+        merge_colors(&mut unprotected, &aggressive);
+        assert_ne!(*unprotected.get_pixel(0, 2), outline);
+
+        let settings = MergeSettings {
+            protect_outline_color: Some((17, 6, 2)),
+            outline_tolerance: 2,
+            ..aggressive
+        };
+        merge_colors(&mut img, &settings);
+        for (x, y) in [(0, 2), (5, 3), (2, 0), (3, 5)] {
+            assert_eq!(*img.get_pixel(x, y), outline);
+        }
+        assert_eq!(*img.get_pixel(0, 0), Rgba([18, 7, 2, 255]));
+        assert_eq!(img.get_pixel(2, 2), img.get_pixel(2, 3));
+    }
+
+    #[test]
+    fn test_pipeline_trim_stage_crops_to_content() {
+        // Faint haze around a 4x3 sprite in a 12x12 canvas
+        let img = RgbaImage::from_fn(12, 12, |x, y| match (x, y) {
+            (4..=7, 5..=7) => Rgba([90, 140, 200, 255]),
+            (1..=10, 1..=10) => Rgba([90, 140, 200, 10]),
+            _ => Rgba([0, 0, 0, 0]),
+        });
+        let untrimmed = run_pipeline(img.clone(), &PipelineConfig::default(), &mut StageTimings::new(false));
+        assert_eq!(untrimmed.dimensions(), (12, 12));
+
+        let loose = PipelineConfig { trim_alpha_threshold: Some(0), ..PipelineConfig::default() };
+        assert_eq!(run_pipeline(img.clone(), &loose, &mut StageTimings::new(false)).dimensions(), (10, 10));
+
+        let config = PipelineConfig { trim_alpha_threshold: Some(10), ..PipelineConfig::default() };
+        let trimmed = run_pipeline(img, &config, &mut StageTimings::new(false));
+        assert_eq!(trimmed.dimensions(), (4, 3));
+        assert_eq!(*trimmed.get_pixel(0, 0), Rgba([90, 140, 200, 255]));
+
+        let recipe = Recipe::from_config(&config);
+        assert_eq!(recipe.to_config().unwrap().trim_alpha_threshold, Some(10));
+    }
+
+    #[test]
+    fn test_flatten_regions_diagonal_connectivity() {
+        // A two-tone diagonal line: pixels touch only at their corners
+        let mut line = RgbaImage::new(3, 3);
+        line.put_pixel(0, 0, Rgba([200, 100, 50, 255]));
+        line.put_pixel(1, 1, Rgba([204, 102, 52, 255]));
+        line.put_pixel(2, 2, Rgba([200, 100, 50, 255]));
+
+        let mut four = line.clone();
+        assert_eq!(flatten_regions(&mut four, 6.0, &Connectivity::Four), 3);
+        assert_eq!(four, line);
+
+        let mut eight = line;
+        assert_eq!(flatten_regions(&mut eight, 6.0, &Connectivity::Eight), 1);
+        assert_eq!(eight.get_pixel(0, 0), eight.get_pixel(1, 1));
+        assert_eq!(eight.get_pixel(1, 1), eight.get_pixel(2, 2));
+    }
+
+    #[test]
+    fn test_label_components_diagonal_connectivity() {
+        // Two pixels touching only at a corner, plus a separate pixel
+        let mut mask = GrayImage::new(4, 4);
+        mask.put_pixel(0, 0, image::Luma([255]));
+        mask.put_pixel(1, 1, image::Luma([255]));
+        mask.put_pixel(3, 3, image::Luma([1]));
+
+        let (labels, count) = label_components(&mask, &Connectivity::Eight);
+        assert_eq!(count, 2);
+        assert_eq!(labels[0], labels[5]);
+        assert_eq!((labels[0], labels[15]), (1, 2));
+
+        let (labels, count) = label_components(&mask, &Connectivity::Four);
+        assert_eq!(count, 3);
+        assert_ne!(labels[0], labels[5]);
+        assert_eq!(labels[1], 0);
+    }
+
+    #[test]
+    fn test_benchmark_reports_each_operation() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        // 8x upscale of a 12x12 checker with a transparent border
+        let sample = RgbaImage::from_fn(112, 112, |x, y| {
+            let (bx, by) = (x / 8, y / 8);
+            if bx == 0 || by == 0 || bx == 13 || by == 13 {
+                Rgba([0, 0, 0, 0])
+            } else if (bx + by) % 2 == 0 {
+                Rgba([200, 60, 40, 255])
+            } else {
+                Rgba([40, 60, 200, 255])
+            }
+        });
+        let sample_path = dir.join("sample.png");
+        sample.save(&sample_path).unwrap();
+
+        let report = benchmark(&sample_path).unwrap();
+        let operations: Vec<&str> = report.iter().map(|e| e.operation.as_str()).collect();
+        assert_eq!(operations, vec!["downscale", "merge", "outline", "pack"]);
+        for entry in &report {
+            assert!(entry.duration_ms > 0.0, "{} took no time", entry.operation);
+            assert!(entry.megapixels_per_second > 0.0);
+        }
+        assert_eq!(report[0].input_size, (112, 112));
+        assert!(report[1].input_size.0 < 112);
+    }
+
+    #[test]
+    fn test_edit_file_honors_save_options() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let input = dir.join("in.png");
+        RgbaImage::from_pixel(2, 2, Rgba([10, 20, 30, 255])).save(&input).unwrap();
+        let invert = |img: &mut RgbaImage| {
+            img.pixels_mut().for_each(|p| p[0] = 255 - p[0]);
+            Ok(img.dimensions())
+        };
+
+        // In place is refused unless allowed, and the input is untouched
+        let err = edit_file(&input, &input, &SaveOptions::default(), invert).unwrap_err();
+        assert!(err.to_string().contains("allow_in_place"));
+        assert_eq!(load_image(&input).unwrap().get_pixel(0, 0)[0], 10);
+        let in_place = SaveOptions { allow_in_place: true, ..Default::default() };
+        assert_eq!(edit_file(&input, &input, &in_place, invert).unwrap(), (2, 2));
+        assert_eq!(load_image(&input).unwrap().get_pixel(0, 0)[0], 245);
+
+        // Skip leaves an existing output alone
+        let output = dir.join("out.png");
+        RgbaImage::new(1, 1).save(&output).unwrap();
+        let skip = SaveOptions { overwrite_policy: OverwritePolicy::Skip, ..Default::default() };
+        edit_file(&input, &output, &skip, invert).unwrap();
+        assert_eq!(load_image(&output).unwrap().dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn test_process_and_save_dry_run_writes_nothing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let input = dir.join("in.png");
+        RgbaImage::from_pixel(3, 2, Rgba([10, 20, 30, 128])).save(&input).unwrap();
+        let output = dir.join("out").join("result.png");
+        let config = PipelineConfig { alpha: Some(AlphaSettings::default()), ..PipelineConfig::default() };
+
+        let dry = process_and_save(&input, &output, &config, &SaveOptions::default(), false, true, StageTimings::new(false)).unwrap();
+        assert!(dry.dry_run && dry.backup_path.is_none() && dry.timings.is_none());
+        assert_eq!(dry.saved_path, Some(output.to_string_lossy().to_string()));
+        assert!(!output.exists());
+
+        let saved = process_and_save(&input, &output, &config, &SaveOptions::default(), false, false, StageTimings::new(true)).unwrap();
+        assert_eq!(saved.final_size, (3, 2));
+        let stages: Vec<String> = saved.timings.unwrap().into_iter().map(|(s, _)| s).collect();
+        assert_eq!(stages, vec!["alpha", "encode"]);
+        assert!(output.exists());
     }
 }
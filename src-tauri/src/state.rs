@@ -39,6 +39,17 @@ pub enum VersionType {
     PostProcessed,
 }
 
+impl VersionType {
+    /// Name used in history entries and labels (matches the serde name)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VersionType::Original => "original",
+            VersionType::Downscaled => "downscaled",
+            VersionType::PostProcessed => "post_processed",
+        }
+    }
+}
+
 /// Settings snapshot for a post-processed version
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PostProcessSettings {
@@ -83,6 +94,79 @@ pub struct ImageVersion {
     pub downscale_settings: Option<DownscaleSettings>,
     /// Creation timestamp (ISO 8601)
     pub created: String,
+    /// Cached image width in pixels, recorded when the version is added
+    #[serde(default)]
+    pub width: Option<u32>,
+    /// Cached image height in pixels
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// Cached image file size in bytes
+    #[serde(default)]
+    pub byte_size: Option<u64>,
+}
+
+impl ImageVersion {
+    /// Fill in `width`, `height` and `byte_size` from the image at `path`,
+    /// reading only its header. Fields are left as they were if the file
+    /// can't be read.
+    pub fn record_image_info(&mut self, path: &Path) {
+        if let (Ok((width, height)), Ok(metadata)) = (image::image_dimensions(path), fs::metadata(path)) {
+            self.width = Some(width);
+            self.height = Some(height);
+            self.byte_size = Some(metadata.len());
+        }
+    }
+}
+
+/// Maximum number of history entries kept per source (oldest dropped first)
+pub const MAX_HISTORY_ENTRIES: usize = 200;
+
+/// One line of a source's append-only processing log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// When the operation was recorded (ISO 8601)
+    pub timestamp: String,
+    /// Operation name (e.g. "downscaled", "post_processed")
+    pub operation: String,
+    /// Short human-readable summary of the settings used
+    pub summary: String,
+}
+
+impl HistoryEntry {
+    /// Build a history entry describing how a version was produced
+    fn for_version(version: &ImageVersion) -> Self {
+        let operation = version.version_type.as_str();
+
+        let mut parts: Vec<String> = vec![format!("{} from {}", version.id, version.parent.as_deref().unwrap_or("-"))];
+        if let Some(ds) = &version.downscale_settings {
+            parts.push(format!("scale={}", ds.detected_scale));
+            parts.push(format!("trim={}", ds.auto_trim));
+            if let Some(pad) = ds.pad_canvas {
+                parts.push(format!("pad={}", pad));
+            }
+        }
+        if let Some(pp) = &version.post_process_settings {
+            if pp.alpha_enabled {
+                parts.push(format!(
+                    "alpha={}/{}",
+                    pp.alpha_low_cutoff.map_or("-".to_string(), |v| v.to_string()),
+                    pp.alpha_high_min.map_or("-".to_string(), |v| v.to_string()),
+                ));
+            }
+            if pp.merge_enabled {
+                parts.push(format!("merge={}", pp.merge_threshold.map_or("-".to_string(), |v| v.to_string())));
+            }
+            if pp.outline_enabled {
+                parts.push(format!("outline={}px", pp.outline_thickness.unwrap_or(1)));
+            }
+        }
+
+        Self {
+            timestamp: now_iso(),
+            operation: operation.to_string(),
+            summary: parts.join(" "),
+        }
+    }
 }
 
 // ============================================================================
@@ -114,6 +198,13 @@ pub struct SourceState {
     pub versions: Vec<ImageVersion>,
     /// Currently active version ID
     pub current_version: String,
+    /// Append-only log of operations applied to this source
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+    /// Processing settings this source starts from, seeded from the
+    /// workspace's source defaults when the source was first tracked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<ProcessingDefaults>,
 }
 
 impl SourceState {
@@ -132,8 +223,13 @@ impl SourceState {
                 post_process_settings: None,
                 downscale_settings: None,
                 created: now,
+                width: None,
+                height: None,
+                byte_size: None,
             }],
             current_version: "v1".to_string(),
+            history: Vec::new(),
+            settings: None,
         }
     }
 
@@ -147,8 +243,13 @@ impl SourceState {
         self.versions.iter().find(|v| v.id == id)
     }
 
-    /// Add a new version
+    /// Add a new version and record it in the history log
     pub fn add_version(&mut self, version: ImageVersion) {
+        self.history.push(HistoryEntry::for_version(&version));
+        if self.history.len() > MAX_HISTORY_ENTRIES {
+            let excess = self.history.len() - MAX_HISTORY_ENTRIES;
+            self.history.drain(..excess);
+        }
         self.versions.push(version);
     }
 }
@@ -175,6 +276,15 @@ impl Default for GlobalSettings {
     }
 }
 
+/// Full processing settings a project standardizes on for its art
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessingDefaults {
+    pub downscaler: crate::downscaler::DownscalerSettings,
+    pub processor: crate::processor::ProcessorSettings,
+    pub packer: crate::packer::PackerSettings,
+}
+
 /// Export settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExportSettings {
@@ -214,6 +324,10 @@ pub struct WorkspaceState {
     pub global_settings: GlobalSettings,
     /// Export settings
     pub export_settings: ExportSettings,
+    /// Settings copied into each newly tracked source (usually the owning
+    /// project's defaults); None leaves new sources on the built-in defaults
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_defaults: Option<ProcessingDefaults>,
 }
 
 impl WorkspaceState {
@@ -225,6 +339,7 @@ impl WorkspaceState {
             sources: HashMap::new(),
             global_settings: GlobalSettings::default(),
             export_settings: ExportSettings::default(),
+            source_defaults: None,
         }
     }
 }
@@ -233,6 +348,13 @@ impl WorkspaceState {
 // WORKSPACE MANAGER
 // ============================================================================
 
+/// Gap between contact sheet cells and around the sheet edge, in pixels
+const CONTACT_PADDING: u32 = 4;
+/// Contact sheet captions are drawn at this multiple of the 3x5 font
+const CONTACT_LABEL_SCALE: u32 = 2;
+/// Contact sheet background (opaque dark gray, so transparency reads as gaps)
+const CONTACT_BACKGROUND: image::Rgba<u8> = image::Rgba([40, 40, 40, 255]);
+
 /// Manages the .pixels folder and state for a workspace
 pub struct WorkspaceManager {
     /// Root workspace directory (user's folder)
@@ -241,6 +363,8 @@ pub struct WorkspaceManager {
     pixels_dir: PathBuf,
     /// Current state
     state: WorkspaceState,
+    /// When set, every operation that would write to disk fails instead
+    read_only: bool,
 }
 
 impl WorkspaceManager {
@@ -262,9 +386,36 @@ impl WorkspaceManager {
             workspace_root: workspace_path.to_path_buf(),
             pixels_dir,
             state,
+            read_only: false,
         })
     }
 
+    /// Open workspace state for inspection only
+    ///
+    /// `save`, `init` and `get_or_create_source` return an error on a
+    /// read-only manager, so browsing can never touch `state.json`.
+    pub fn open_readonly(workspace_path: &Path) -> Result<Self> {
+        let mut manager = Self::open(workspace_path)?;
+        manager.read_only = true;
+        Ok(manager)
+    }
+
+    /// Whether this manager was opened read-only
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Fail if this manager is read-only
+    fn ensure_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(PixelsError::ReadOnly(format!(
+                "workspace {} was opened read-only",
+                self.workspace_root.display()
+            )));
+        }
+        Ok(())
+    }
+
     /// Create manager from existing state (for saving updates)
     pub fn from_state(workspace_path: &Path, state: WorkspaceState) -> Self {
         let pixels_dir = workspace_path.join(".pixels");
@@ -272,6 +423,7 @@ impl WorkspaceManager {
             workspace_root: workspace_path.to_path_buf(),
             pixels_dir,
             state,
+            read_only: false,
         }
     }
 
@@ -287,6 +439,7 @@ impl WorkspaceManager {
 
     /// Initialize .pixels folder structure
     pub fn init(&self) -> Result<()> {
+        self.ensure_writable()?;
         fs::create_dir_all(self.pixels_dir.join("cache"))?;
         fs::create_dir_all(self.pixels_dir.join("thumbnails"))?;
         self.save()?;
@@ -295,6 +448,7 @@ impl WorkspaceManager {
 
     /// Save current state to disk
     pub fn save(&self) -> Result<()> {
+        self.ensure_writable()?;
         fs::create_dir_all(&self.pixels_dir)?;
         let state_path = self.pixels_dir.join("state.json");
         let content = serde_json::to_string_pretty(&self.state)?;
@@ -324,16 +478,17 @@ impl WorkspaceManager {
 
     /// Get or create source state for an image
     pub fn get_or_create_source(&mut self, relative_path: &str) -> Result<&mut SourceState> {
-        if !self.state.sources.contains_key(relative_path) {
+        self.ensure_writable()?;
+        let relative_path = normalize_relative(relative_path)?;
+        if !self.state.sources.contains_key(&relative_path) {
             // Calculate hash of original file
-            let full_path = self.workspace_root.join(relative_path);
+            let full_path = self.workspace_root.join(&relative_path);
             let hash = hash_file(&full_path)?;
-            self.state.sources.insert(
-                relative_path.to_string(),
-                SourceState::new(hash),
-            );
+            let mut source = SourceState::new(hash);
+            source.settings = self.state.source_defaults.clone();
+            self.state.sources.insert(relative_path.clone(), source);
         }
-        Ok(self.state.sources.get_mut(relative_path).unwrap())
+        Ok(self.state.sources.get_mut(&relative_path).unwrap())
     }
 
     /// Get source state (read-only)
@@ -341,11 +496,31 @@ impl WorkspaceManager {
         self.state.sources.get(relative_path)
     }
 
+    /// Processing history log for a source (empty if not yet tracked)
+    pub fn history(&self, relative_path: &str) -> Result<Vec<HistoryEntry>> {
+        Ok(self
+            .get_source(&normalize_relative(relative_path)?)
+            .map(|source| source.history.clone())
+            .unwrap_or_default())
+    }
+
     /// Get all source paths
     pub fn source_paths(&self) -> Vec<&String> {
         self.state.sources.keys().collect()
     }
 
+    /// Path of the image for a source's current version
+    /// Falls back to the original file when the version has no cached image
+    pub fn current_image_path(&self, relative_path: &str) -> Result<PathBuf> {
+        let relative_path = normalize_relative(relative_path)?;
+        Ok(self.get_source(&relative_path)
+            .and_then(|source| source.get_version(&source.current_version))
+            .and_then(|version| version.cache_path.as_ref())
+            .map(|filename| self.cache_path(filename))
+            .filter(|path| path.exists())
+            .unwrap_or_else(|| self.workspace_root.join(&relative_path)))
+    }
+
     /// Get global settings
     pub fn global_settings(&self) -> &GlobalSettings {
         &self.state.global_settings
@@ -356,6 +531,12 @@ impl WorkspaceManager {
         self.state.global_settings = settings;
     }
 
+    /// Set the settings new sources are seeded with (existing sources keep
+    /// theirs)
+    pub fn set_source_defaults(&mut self, defaults: Option<ProcessingDefaults>) {
+        self.state.source_defaults = defaults;
+    }
+
     /// Generate cache filename for a version
     pub fn cache_filename(&self, source_hash: &str, version_id: &str, suffix: &str) -> String {
         format!("{}_{}{}.png", &source_hash[..12], version_id, suffix)
@@ -366,6 +547,192 @@ impl WorkspaceManager {
         self.cache_dir().join(filename)
     }
 
+    /// Copy a source's original file into the cache, named by content hash
+    ///
+    /// Returns the cache filename. An existing backup with the same hash is
+    /// reused rather than copied again.
+    pub fn backup_original(&self, relative_path: &str) -> Result<String> {
+        self.ensure_writable()?;
+        let source_file = self.workspace_root.join(normalize_relative(relative_path)?);
+        let hash = hash_file(&source_file)?;
+        let backup_name = format!("{}_original.png", &hash[..16]);
+        let backup_path = self.cache_path(&backup_name);
+
+        if !backup_path.exists() {
+            fs::create_dir_all(self.cache_dir())?;
+            fs::copy(&source_file, &backup_path)?;
+        }
+
+        Ok(backup_name)
+    }
+
+    /// Back up a source's original and record it as the `Original` version's
+    /// cached image, so the original stays reachable after the source file
+    /// is overwritten. Saves the state.
+    pub fn backup_original_to_lineage(&mut self, relative_path: &str) -> Result<String> {
+        let backup_name = self.backup_original(relative_path)?;
+        let backup_path = self.cache_path(&backup_name);
+        let source = self.get_or_create_source(relative_path)?;
+        if let Some(original) = source
+            .versions
+            .iter_mut()
+            .find(|v| v.version_type == VersionType::Original && v.cache_path.is_none())
+        {
+            original.cache_path = Some(backup_name.clone());
+            original.record_image_info(&backup_path);
+        }
+        self.save()?;
+        Ok(backup_name)
+    }
+
+    /// Add a version to a source's lineage, recording its cached image's
+    /// dimensions and size. Doesn't save the state.
+    pub fn add_version(&mut self, relative_path: &str, mut version: ImageVersion) -> Result<()> {
+        if let Some(filename) = &version.cache_path {
+            let path = self.cache_path(filename);
+            version.record_image_info(&path);
+        }
+        self.get_or_create_source(relative_path)?.add_version(version);
+        Ok(())
+    }
+
+    /// Path of the image for one version of a source
+    /// Falls back to the original file for an `Original` version with no
+    /// cached copy; None if the version's image can't be found
+    pub fn version_image_path(&self, relative_path: &str, version: &ImageVersion) -> Result<Option<PathBuf>> {
+        let path = match &version.cache_path {
+            Some(filename) => Some(self.cache_path(filename)),
+            None if version.version_type == VersionType::Original => {
+                Some(self.workspace_root.join(normalize_relative(relative_path)?))
+            }
+            None => None,
+        };
+        Ok(path.filter(|path| path.exists()))
+    }
+
+    /// Lay out every version of a source in a labeled grid
+    ///
+    /// Cells are sized to the largest version; smaller versions are enlarged
+    /// by a whole-number factor (nearest-neighbor) to fill their cell, so a
+    /// downscaled result can be compared with its source. Each cell is
+    /// captioned with the version id and type. Versions whose image is
+    /// missing get an empty, captioned cell.
+    pub fn version_contact_sheet(&self, relative_path: &str) -> Result<image::RgbaImage> {
+        use crate::processor::{draw_text, text_width, GLYPH_HEIGHT};
+        use image::{imageops, Rgba, RgbaImage};
+
+        let relative_path = normalize_relative(relative_path)?;
+        let source = self.get_source(&relative_path).ok_or_else(|| {
+            PixelsError::InvalidParameter(format!("No versions recorded for {}", relative_path))
+        })?;
+
+        let cells: Vec<(String, Option<RgbaImage>)> = source
+            .versions
+            .iter()
+            .map(|version| {
+                let label = format!("{} {}", version.id, version.version_type.as_str());
+                let image = self
+                    .version_image_path(&relative_path, version)?
+                    .map(|path| crate::processor::load_image(&path))
+                    .transpose()?;
+                Ok((label, image))
+            })
+            .collect::<Result<_>>()?;
+
+        let label_height = GLYPH_HEIGHT * CONTACT_LABEL_SCALE + CONTACT_PADDING;
+        let widest_label = cells.iter().map(|(label, _)| text_width(label, CONTACT_LABEL_SCALE)).max().unwrap_or(0);
+        let cell_w = cells.iter().filter_map(|(_, img)| img.as_ref().map(|i| i.width())).max().unwrap_or(0).max(widest_label).max(1);
+        let cell_h = cells.iter().filter_map(|(_, img)| img.as_ref().map(|i| i.height())).max().unwrap_or(0).max(1);
+
+        let columns = (cells.len() as f64).sqrt().ceil().max(1.0) as u32;
+        let rows = (cells.len() as u32).div_ceil(columns);
+        let pitch_x = cell_w + CONTACT_PADDING;
+        let pitch_y = cell_h + label_height + CONTACT_PADDING;
+
+        let mut sheet = RgbaImage::from_pixel(
+            columns * pitch_x + CONTACT_PADDING,
+            rows * pitch_y + CONTACT_PADDING,
+            CONTACT_BACKGROUND,
+        );
+        for (i, (label, image)) in cells.iter().enumerate() {
+            let x = CONTACT_PADDING + (i as u32 % columns) * pitch_x;
+            let y = CONTACT_PADDING + (i as u32 / columns) * pitch_y;
+
+            draw_text(&mut sheet, x, y, label, CONTACT_LABEL_SCALE, Rgba([255, 255, 255, 255]));
+            if let Some(image) = image.as_ref().filter(|img| img.width() > 0 && img.height() > 0) {
+                let factor = (cell_w / image.width()).min(cell_h / image.height()).max(1);
+                let enlarged = imageops::resize(image, image.width() * factor, image.height() * factor, imageops::FilterType::Nearest);
+                imageops::overlay(&mut sheet, &enlarged, x as i64, (y + label_height) as i64);
+            }
+        }
+
+        Ok(sheet)
+    }
+
+    /// Group the workspace's images that look the same
+    ///
+    /// Byte-identical files (same SHA-256) are grouped first; those groups
+    /// are then merged with any image whose perceptual hash is within
+    /// `hamming_threshold` bits (single linkage). Returns only groups of two
+    /// or more, each as relative paths in name order. Unreadable images are
+    /// left out.
+    pub fn find_duplicates(&self, hamming_threshold: u32) -> Vec<Vec<String>> {
+        use rayon::prelude::*;
+
+        let files = crate::processor::list_image_files(&self.workspace_root).unwrap_or_default();
+        let hashed: Vec<(String, String, u64)> = files
+            .par_iter()
+            .filter_map(|path| {
+                let sha = hash_file(path).ok()?;
+                let phash = perceptual_hash(&crate::processor::load_image(path).ok()?);
+                let relative = path.strip_prefix(&self.workspace_root).unwrap_or(path).to_string_lossy().to_string();
+                Some((relative, sha, phash))
+            })
+            .collect();
+
+        // Exact duplicates: one group per SHA-256, in first-seen order
+        let mut exact: Vec<(u64, Vec<String>)> = Vec::new();
+        let mut by_sha: HashMap<&str, usize> = HashMap::new();
+        for (relative, sha, phash) in &hashed {
+            let index = *by_sha.entry(sha.as_str()).or_insert_with(|| {
+                exact.push((*phash, Vec::new()));
+                exact.len() - 1
+            });
+            exact[index].1.push(relative.clone());
+        }
+
+        // Near duplicates: union exact groups whose hashes are close
+        let mut parent: Vec<usize> = (0..exact.len()).collect();
+        fn find(parent: &mut [usize], i: usize) -> usize {
+            let mut root = i;
+            while parent[root] != root {
+                root = parent[root];
+            }
+            parent[i] = root;
+            root
+        }
+        for i in 0..exact.len() {
+            for j in i + 1..exact.len() {
+                if (exact[i].0 ^ exact[j].0).count_ones() <= hamming_threshold {
+                    let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                    parent[b.max(a)] = a.min(b);
+                }
+            }
+        }
+
+        let mut clusters: Vec<Vec<String>> = vec![Vec::new(); exact.len()];
+        for (i, (_, paths)) in exact.into_iter().enumerate() {
+            let root = find(&mut parent, i);
+            clusters[root].extend(paths);
+        }
+        let mut clusters: Vec<Vec<String>> = clusters.into_iter().filter(|c| c.len() > 1).collect();
+        for cluster in &mut clusters {
+            cluster.sort();
+        }
+        clusters.sort();
+        clusters
+    }
+
     /// Get full thumbnail path for a source
     pub fn thumbnail_path(&self, relative_path: &str) -> PathBuf {
         // Use sanitized filename for thumbnail
@@ -378,6 +745,114 @@ impl WorkspaceManager {
 // UTILITY FUNCTIONS
 // ============================================================================
 
+/// Lightweight listing entry for an image in a workspace folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceImage {
+    /// File name relative to the workspace root
+    pub relative_path: String,
+    /// File size in bytes
+    pub file_size: u64,
+    /// Pixel dimensions from the file header (None if the header is unreadable)
+    pub dimensions: Option<(u32, u32)>,
+}
+
+/// Read image dimensions from the file header without decoding pixels
+pub fn read_dimensions(path: &Path) -> Option<(u32, u32)> {
+    image::ImageReader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+/// Initialize the workspace state for a folder
+///
+/// With `defaults`, new sources in the workspace are seeded with them.
+pub fn init_workspace(workspace_root: &Path, defaults: Option<ProcessingDefaults>) -> Result<()> {
+    let mut manager = WorkspaceManager::open(workspace_root)?;
+    if defaults.is_some() {
+        manager.set_source_defaults(defaults);
+    }
+    manager.init()
+}
+
+/// Enumerate images directly inside a workspace folder, sorted by name
+///
+/// Only file metadata and image headers are read, so this stays fast for
+/// folders with thousands of images; thumbnails are loaded separately.
+pub fn list_workspace_images(workspace_root: &Path) -> Result<Vec<WorkspaceImage>> {
+    let files = crate::processor::list_image_files(workspace_root)?;
+
+    Ok(files
+        .iter()
+        .map(|path| WorkspaceImage {
+            relative_path: path.strip_prefix(workspace_root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_string(),
+            file_size: fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            dimensions: read_dimensions(path),
+        })
+        .collect())
+}
+
+/// Validate and canonicalize a caller-supplied workspace-relative path
+///
+/// Backslashes become `/`, and empty and `.` components are dropped.
+/// Absolute paths (including Windows drive and UNC prefixes) and `..`
+/// components are rejected so the result can never escape the workspace.
+pub fn normalize_relative(path: &str) -> Result<String> {
+    let unified = path.replace('\\', "/");
+    let invalid = |reason: &str| PixelsError::InvalidParameter(format!("Invalid relative path '{}': {}", path, reason));
+
+    if unified.starts_with('/') || Path::new(path).is_absolute() || unified.split('/').next().is_some_and(|c| c.contains(':')) {
+        return Err(invalid("must not be absolute"));
+    }
+
+    let mut parts = Vec::new();
+    for component in unified.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => return Err(invalid("must not contain '..'")),
+            part => parts.push(part),
+        }
+    }
+
+    if parts.is_empty() {
+        return Err(invalid("empty path"));
+    }
+    Ok(parts.join("/"))
+}
+
+/// Nearest ancestor directory of `path` containing a `.pixels` folder
+pub fn find_workspace_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .find(|dir| dir.join(".pixels").is_dir())
+        .map(Path::to_path_buf)
+}
+
+/// Back up `file` before it is overwritten in place
+///
+/// Records the backup in the lineage of the enclosing workspace and returns
+/// the backup path. Fails without touching the disk if `file` isn't inside
+/// a workspace, rather than creating a `.pixels` folder next to it.
+pub fn backup_before_overwrite(file: &Path) -> Result<PathBuf> {
+    let root = find_workspace_root(file).ok_or_else(|| {
+        PixelsError::InvalidParameter(format!("{} is not inside a workspace; cannot back it up", file.display()))
+    })?;
+    let relative_path = file
+        .strip_prefix(&root)
+        .unwrap_or(file)
+        .to_string_lossy()
+        .to_string();
+
+    let mut manager = WorkspaceManager::open(&root)?;
+    let backup_name = manager.backup_original_to_lineage(&relative_path)?;
+    Ok(manager.cache_path(&backup_name))
+}
+
 /// Calculate SHA-256 hash of a file
 pub fn hash_file(path: &Path) -> Result<String> {
     let content = fs::read(path)
@@ -396,6 +871,34 @@ pub fn hash_bytes(data: &[u8]) -> String {
     format!("{:x}", result)
 }
 
+/// Default `find_duplicates` threshold: bits of the 64-bit perceptual hash
+/// that may differ between images still treated as the same
+pub const DEFAULT_DUPLICATE_THRESHOLD: u32 = 4;
+
+/// 64-bit difference hash (dHash) of an image
+///
+/// The image is reduced to 9x8 grayscale (alpha-weighted, so transparent
+/// pixels count as black whatever their RGB) and each bit records whether a
+/// pixel is brighter than its right-hand neighbor. Re-encodes, small color
+/// tweaks and rescales barely change it.
+pub fn perceptual_hash(img: &image::RgbaImage) -> u64 {
+    use image::imageops::{resize, FilterType};
+
+    let gray = image::GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        let p = img.get_pixel(x, y);
+        image::Luma([(crate::color::luma(p[0], p[1], p[2]) as u32 * p[3] as u32 / 255) as u8])
+    });
+    let small = resize(&gray, 9, 8, FilterType::Triangle);
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash = (hash << 1) | (small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0]) as u64;
+        }
+    }
+    hash
+}
+
 /// Get current timestamp as ISO 8601 string
 pub fn now_iso() -> String {
     chrono::Utc::now().to_rfc3339()
@@ -431,6 +934,9 @@ mod tests {
             post_process_settings: None,
             downscale_settings: None,
             created: now_iso(),
+            width: None,
+            height: None,
+            byte_size: None,
         });
 
         assert_eq!(state.next_version_id(), "v3");
@@ -454,4 +960,301 @@ mod tests {
         assert_ne!(hash1, hash3);
         assert_eq!(hash1.len(), 64); // SHA-256 produces 64 hex chars
     }
+
+    #[test]
+    fn test_add_version_records_history() {
+        let mut state = SourceState::new("abc123".to_string());
+        assert!(state.history.is_empty());
+
+        state.add_version(ImageVersion {
+            id: "v2".to_string(),
+            version_type: VersionType::Downscaled,
+            cache_path: Some("a.png".to_string()),
+            parent: Some("v1".to_string()),
+            post_process_settings: None,
+            downscale_settings: Some(DownscaleSettings {
+                detected_scale: 8,
+                auto_trim: true,
+                pad_canvas: None,
+            }),
+            created: now_iso(),
+            width: None,
+            height: None,
+            byte_size: None,
+        });
+        state.add_version(ImageVersion {
+            id: "v3".to_string(),
+            version_type: VersionType::PostProcessed,
+            cache_path: Some("b.png".to_string()),
+            parent: Some("v2".to_string()),
+            post_process_settings: Some(PostProcessSettings {
+                alpha_enabled: false,
+                alpha_low_cutoff: None,
+                alpha_high_min: None,
+                merge_enabled: true,
+                merge_threshold: Some(3.0),
+                outline_enabled: true,
+                outline_color: Some((17, 6, 2, 255)),
+                outline_thickness: Some(1),
+            }),
+            downscale_settings: None,
+            created: now_iso(),
+            width: None,
+            height: None,
+            byte_size: None,
+        });
+
+        assert_eq!(state.history.len(), 2);
+        assert_eq!(state.history[0].operation, "downscaled");
+        assert_eq!(state.history[1].operation, "post_processed");
+        assert!(state.history[0].summary.contains("scale=8"));
+        assert!(state.history[1].summary.contains("merge=3"));
+
+        let t0 = chrono::DateTime::parse_from_rfc3339(&state.history[0].timestamp).unwrap();
+        let t1 = chrono::DateTime::parse_from_rfc3339(&state.history[1].timestamp).unwrap();
+        assert!(t0 <= t1);
+    }
+
+    #[test]
+    fn test_history_is_capped() {
+        let mut state = SourceState::new("abc123".to_string());
+        for i in 0..(MAX_HISTORY_ENTRIES + 5) {
+            state.add_version(ImageVersion {
+                id: format!("v{}", i + 2),
+                version_type: VersionType::PostProcessed,
+                cache_path: None,
+                parent: Some("v1".to_string()),
+                post_process_settings: None,
+                downscale_settings: None,
+                created: now_iso(),
+                width: None,
+                height: None,
+                byte_size: None,
+            });
+        }
+        assert_eq!(state.history.len(), MAX_HISTORY_ENTRIES);
+        assert!(state.history[0].summary.starts_with("v7 "));
+    }
+
+    #[test]
+    fn test_readonly_manager_refuses_writes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+
+        WorkspaceManager::open(&root).unwrap().init().unwrap();
+        fs::write(root.join("hero.png"), b"source").unwrap();
+        let state_path = root.join(".pixels").join("state.json");
+        let before = fs::read_to_string(&state_path).unwrap();
+
+        let mut manager = WorkspaceManager::open_readonly(&root).unwrap();
+        assert!(manager.is_read_only());
+        manager.set_global_settings(GlobalSettings {
+            merge_threshold: 9.0,
+            ..GlobalSettings::default()
+        });
+
+        assert!(matches!(manager.save(), Err(PixelsError::ReadOnly(_))));
+        assert!(matches!(manager.init(), Err(PixelsError::ReadOnly(_))));
+        // Refused for being read-only, not because the file is missing
+        let err = manager.get_or_create_source("hero.png").unwrap_err();
+        assert!(matches!(err, PixelsError::ReadOnly(..)), "{:?}", err);
+        assert!(manager.get_source("hero.png").is_none());
+        assert_eq!(fs::read_to_string(&state_path).unwrap(), before);
+    }
+
+    #[test]
+    fn test_list_workspace_images_reads_headers_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+
+        // A large image truncated right after its header: dimensions are
+        // readable but a full decode must fail
+        let big = image::RgbaImage::from_pixel(4000, 3000, image::Rgba([5, 5, 5, 255]));
+        let png = crate::processor::encode_png(&big).unwrap();
+        fs::write(root.join("big.png"), &png[..64]).unwrap();
+        image::RgbaImage::new(3, 2).save(root.join("a.png")).unwrap();
+        fs::write(root.join("readme.txt"), "hi").unwrap();
+
+        let images = list_workspace_images(&root).unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].relative_path, "a.png");
+        assert_eq!(images[0].dimensions, Some((3, 2)));
+        assert_eq!(images[1].relative_path, "big.png");
+        assert_eq!(images[1].dimensions, Some((4000, 3000)));
+        assert_eq!(images[1].file_size, 64);
+        assert!(crate::processor::load_image(&root.join("big.png")).is_err());
+    }
+
+    #[test]
+    fn test_backup_before_overwrite_keeps_original() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+        fs::create_dir_all(root.join("sprites")).unwrap();
+        WorkspaceManager::open(&root).unwrap().init().unwrap();
+
+        let file = root.join("sprites").join("hero.png");
+        image::RgbaImage::from_pixel(3, 3, image::Rgba([1, 2, 3, 255])).save(&file).unwrap();
+        let original_bytes = fs::read(&file).unwrap();
+
+        let backup = backup_before_overwrite(&file).unwrap();
+        image::RgbaImage::from_pixel(3, 3, image::Rgba([9, 9, 9, 255])).save(&file).unwrap();
+
+        assert!(backup.starts_with(root.join(".pixels").join("cache")));
+        assert_eq!(fs::read(&backup).unwrap(), original_bytes);
+
+        let manager = WorkspaceManager::open(&root).unwrap();
+        let source = manager.get_source("sprites/hero.png").unwrap();
+        let original = source.get_version("v1").unwrap();
+        assert_eq!(manager.cache_path(original.cache_path.as_ref().unwrap()), backup);
+
+        // A second backup of the now-modified file doesn't replace the recorded original
+        backup_before_overwrite(&file).unwrap();
+        let manager = WorkspaceManager::open(&root).unwrap();
+        let original = manager.get_source("sprites/hero.png").unwrap().get_version("v1").unwrap();
+        assert_eq!(manager.cache_path(original.cache_path.as_ref().unwrap()), backup);
+    }
+
+    #[test]
+    fn test_backup_before_overwrite_outside_workspace() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+
+        let file = root.join("hero.png");
+        image::RgbaImage::from_pixel(3, 3, image::Rgba([1, 2, 3, 255])).save(&file).unwrap();
+
+        let err = backup_before_overwrite(&file).unwrap_err();
+        assert!(matches!(err, PixelsError::InvalidParameter(_)));
+        assert!(!root.join(".pixels").exists());
+    }
+
+    #[test]
+    fn test_normalize_relative() {
+        assert_eq!(normalize_relative("sub/sprite.png").unwrap(), "sub/sprite.png");
+        assert_eq!(normalize_relative("sub\\.\\sprite.png").unwrap(), "sub/sprite.png");
+        assert_eq!(normalize_relative("./a//b.png").unwrap(), "a/b.png");
+
+        assert!(normalize_relative("../secret").is_err());
+        assert!(normalize_relative("sub/../../etc/passwd").is_err());
+        assert!(normalize_relative("/etc/passwd").is_err());
+        assert!(normalize_relative("C:\\Windows\\win.ini").is_err());
+        assert!(normalize_relative("\\\\server\\share").is_err());
+        assert!(normalize_relative("").is_err());
+
+        let tmp = tempfile::tempdir().unwrap();
+
+        let root = tmp.path().to_path_buf();
+        let mut manager = WorkspaceManager::open(&root).unwrap();
+        assert!(manager.get_or_create_source("../secret").is_err());
+        assert!(manager.backup_original("../secret").is_err());
+    }
+
+    #[test]
+    fn test_version_contact_sheet_fits_three_labeled_cells() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+        image::RgbaImage::from_pixel(40, 32, image::Rgba([200, 0, 0, 255])).save(root.join("hero.png")).unwrap();
+
+        let mut manager = WorkspaceManager::open(&root).unwrap();
+        manager.init().unwrap();
+        for (version_type, size) in [(VersionType::Downscaled, 5), (VersionType::PostProcessed, 4)] {
+            let cache_name = format!("hero_{}.png", version_type.as_str());
+            image::RgbaImage::from_pixel(size, size, image::Rgba([0, 0, 200, 255]))
+                .save(manager.cache_path(&cache_name))
+                .unwrap();
+            let source = manager.get_or_create_source("hero.png").unwrap();
+            let id = source.next_version_id();
+            source.add_version(ImageVersion {
+                id,
+                version_type,
+                cache_path: Some(cache_name),
+                parent: Some("v1".to_string()),
+                post_process_settings: None,
+                downscale_settings: None,
+                created: now_iso(),
+                width: None,
+                height: None,
+                byte_size: None,
+            });
+        }
+
+        let sheet = manager.version_contact_sheet("hero.png").unwrap();
+
+        // 2x2 grid of cells at least as large as the original plus a caption
+        let label_height = crate::processor::GLYPH_HEIGHT * CONTACT_LABEL_SCALE + CONTACT_PADDING;
+        let widest_label = crate::processor::text_width("v3 post_processed", CONTACT_LABEL_SCALE);
+        assert!(sheet.width() >= 2 * widest_label.max(40));
+        assert!(sheet.height() >= 2 * (32 + label_height));
+        // The 5x5 version is enlarged 6x (to 30px, fitting the 40x32 cell)
+        let cell_x = CONTACT_PADDING + widest_label.max(40) + CONTACT_PADDING;
+        let image_y = CONTACT_PADDING + label_height;
+        assert_eq!(*sheet.get_pixel(cell_x + 29, image_y + 29), image::Rgba([0, 0, 200, 255]));
+        assert_eq!(*sheet.get_pixel(cell_x + 30, image_y), CONTACT_BACKGROUND);
+
+        assert!(manager.version_contact_sheet("missing.png").is_err());
+    }
+
+    #[test]
+    fn test_find_duplicates_groups_lookalikes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+
+        let sprite = image::RgbaImage::from_fn(32, 32, |x, y| image::Rgba([(x * 8) as u8, (y * 8) as u8, 90, 255]));
+        sprite.save(root.join("a.png")).unwrap();
+        fs::copy(root.join("a.png"), root.join("a_copy.png")).unwrap();
+        // Visually identical, different bytes
+        let mut tweaked = sprite.clone();
+        tweaked.get_pixel_mut(5, 5)[2] = 91;
+        tweaked.save(root.join("b.png")).unwrap();
+        // Distinct: gradient runs the other way
+        image::RgbaImage::from_fn(32, 32, |x, y| image::Rgba([255 - (x * 8) as u8, (y * 8) as u8, 90, 255]))
+            .save(root.join("c.png"))
+            .unwrap();
+
+        let manager = WorkspaceManager::open_readonly(&root).unwrap();
+        assert_eq!(
+            manager.find_duplicates(DEFAULT_DUPLICATE_THRESHOLD),
+            vec![vec!["a.png".to_string(), "a_copy.png".to_string(), "b.png".to_string()]]
+        );
+
+        // Byte-identical files group even with no perceptual tolerance
+        assert!(manager.find_duplicates(0)[0].contains(&"a_copy.png".to_string()));
+    }
+
+    #[test]
+    fn test_add_version_records_image_info() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+        image::RgbaImage::from_pixel(64, 48, image::Rgba([10, 20, 30, 255])).save(root.join("hero.png")).unwrap();
+
+        let mut manager = WorkspaceManager::open(&root).unwrap();
+        manager.init().unwrap();
+        let cache_name = "hero_v2.png".to_string();
+        image::RgbaImage::from_pixel(8, 6, image::Rgba([10, 20, 30, 255]))
+            .save(manager.cache_path(&cache_name))
+            .unwrap();
+        let expected_size = fs::metadata(manager.cache_path(&cache_name)).unwrap().len();
+
+        manager
+            .add_version("hero.png", ImageVersion {
+                id: "v2".to_string(),
+                version_type: VersionType::Downscaled,
+                cache_path: Some(cache_name),
+                parent: Some("v1".to_string()),
+                post_process_settings: None,
+                downscale_settings: None,
+                created: now_iso(),
+                width: None,
+                height: None,
+                byte_size: None,
+            })
+            .unwrap();
+        manager.save().unwrap();
+
+        let reopened = WorkspaceManager::open_readonly(&root).unwrap();
+        let version = reopened.get_source("hero.png").unwrap().get_version("v2").unwrap();
+        assert_eq!((version.width, version.height), (Some(8), Some(6)));
+        assert_eq!(version.byte_size, Some(expected_size));
+        // The original has no cached copy yet, so nothing to record
+        assert_eq!(reopened.get_source("hero.png").unwrap().get_version("v1").unwrap().width, None);
+    }
 }
@@ -0,0 +1,73 @@
+//! Locks core operations to known outputs on checked-in fixtures
+//!
+//! Each case runs an operation on an image from `tests/fixtures/` and
+//! compares the SHA-256 of the result (dimensions + raw RGBA) against a
+//! golden hash. Cases also run several times in-process, which catches
+//! dependence on `HashMap` iteration order (randomly seeded per map).
+//! After an intentional output change, update the hash from the failure
+//! message.
+
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+use tauri_app_lib::pixels_core::{downscaler, processor, state};
+
+/// Runs per case; each run gets fresh `HashMap` seeds
+const RUNS: usize = 3;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+/// SHA-256 of an image's dimensions followed by its RGBA bytes
+fn digest(img: &RgbaImage) -> String {
+    let mut bytes = Vec::with_capacity(8 + img.as_raw().len());
+    bytes.extend_from_slice(&img.width().to_le_bytes());
+    bytes.extend_from_slice(&img.height().to_le_bytes());
+    bytes.extend_from_slice(img.as_raw());
+    state::hash_bytes(&bytes)
+}
+
+fn check(name: &str, golden: &str, run: impl Fn() -> RgbaImage) {
+    for _ in 0..RUNS {
+        let actual = digest(&run());
+        assert_eq!(actual, golden, "{} output changed", name);
+    }
+}
+
+#[test]
+fn merge_matches_golden() {
+    check("merge", "9da9cafa57f798634e95ac0b735973819f1c8f5fe04cf1d7e4ebc61d99ddfbdd", || {
+        let mut img = processor::load_image(&fixture("merge.png")).unwrap();
+        let settings = processor::MergeSettings { threshold: 8.0, ..processor::MergeSettings::default() };
+        processor::merge_colors(&mut img, &settings);
+        img
+    });
+}
+
+#[test]
+fn outline_matches_golden() {
+    check("outline", "0ae6a678c248fc686fd2b2a6a2e81914a8d5311438be75fceb39f50def437ad1", || {
+        let mut img = processor::load_image(&fixture("outline.png")).unwrap();
+        processor::add_outline(&mut img, &processor::OutlineSettings::default());
+        img
+    });
+}
+
+#[test]
+fn downscale_matches_golden() {
+    check("downscale", "cd40876b701f1b30d6048bcd2fca4754eb743058ce136bc1235aa1819fc581ac", || {
+        let (img, result) = downscaler::downscale_file(&fixture("downscale.png"), &downscaler::DownscalerSettings::default()).unwrap();
+        assert_eq!(result.scale_factor, 8.0);
+        img
+    });
+}
+
+#[test]
+fn deartifact_matches_golden() {
+    check("deartifact", "65c208a10c49a860279347bb54ddece0c18fd50bf390f084a9ff3ae225152b8b", || {
+        let mut img = processor::load_image(&fixture("downscale.png")).unwrap();
+        processor::deartifact(&mut img, 0.5);
+        img
+    });
+}
@@ -0,0 +1,62 @@
+//! Drives downscale -> process -> pack through the library API alone
+
+use std::path::PathBuf;
+
+use image::{Rgba, RgbaImage};
+use tauri_app_lib::pixels_core::{downscaler, packer, processor};
+
+/// Native sprite with an opaque body on a transparent border, upscaled
+fn upscaled_sprite(native: u32, scale: u32, color: Rgba<u8>) -> RgbaImage {
+    let small = RgbaImage::from_fn(native, native, |x, y| {
+        if x == 0 || y == 0 || x == native - 1 || y == native - 1 {
+            Rgba([0, 0, 0, 0])
+        } else if (x + y) % 3 == 0 {
+            Rgba([color[0] / 2, color[1] / 2, color[2] / 2, 255])
+        } else {
+            color
+        }
+    });
+    RgbaImage::from_fn(native * scale, native * scale, |x, y| *small.get_pixel(x / scale, y / scale))
+}
+
+#[test]
+fn downscale_process_pack_without_tauri() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+
+    let mut processed: Vec<PathBuf> = Vec::new();
+    for (name, color) in [("knight", Rgba([200, 60, 40, 255])), ("slime", Rgba([40, 180, 90, 255]))] {
+        let source = root.join(format!("{}.png", name));
+        upscaled_sprite(18, 8, color).save(&source).unwrap();
+
+        let downscaled = root.join("downscaled").join(format!("{}.png", name));
+        let result = downscaler::downscale_image(
+            source.clone(),
+            downscaled.clone(),
+            downscaler::DownscalerSettings::default(),
+        )
+        .unwrap();
+        assert_eq!(result.scale_factor, 8.0);
+
+        let img = processor::load_image(&downscaled).unwrap();
+        let config = processor::PipelineConfig {
+            merge: Some(processor::MergeSettings::default()),
+            outline: Some(processor::OutlineSettings::default()),
+            ..processor::PipelineConfig::default()
+        };
+        let mut timings = processor::StageTimings::new(false);
+        let img = processor::run_pipeline(img, &config, &mut timings);
+
+        let output = root.join("processed").join(format!("{}.png", name));
+        processor::save_image(&img, &output).unwrap();
+        processed.push(output);
+    }
+
+    let settings = packer::PackerSettings { export_metadata: false, ..packer::PackerSettings::default() };
+    let sheet = root.join("sheet.png");
+    let result = packer::pack_sprites(processed, sheet.clone(), settings).unwrap();
+
+    assert_eq!(result.items.len(), 2);
+    let sheet_img = processor::load_image(&sheet).unwrap();
+    assert!(packer::validate_atlas(&sheet_img, &result).is_empty());
+}
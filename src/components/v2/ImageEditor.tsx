@@ -456,6 +456,7 @@ export function ImageEditor({
         alphaSettings,
         mergeSettings,
         outlineSettings,
+        saveOptions: { overwrite_policy: 'overwrite', allow_in_place: isOverwriting },
       });
 
       // Record version in lineage (folder mode only)
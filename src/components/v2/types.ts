@@ -93,6 +93,8 @@ export interface ScaleDetectionResult {
   is_ai_upscaled: boolean;
   dimensions: [number, number];
   estimated_native_size: [number, number];
+  /** DownscalerSettings tuned to this image (see suggest_settings in downscaler.rs) */
+  suggested_settings?: Record<string, unknown>;
 }
 
 export type WorkspaceMode = 'folder' | 'single-file';
@@ -125,10 +127,19 @@ export interface OutlineDetectionResult {
   edge_pixel_count: number;
 }
 
+export type OverwritePolicy = 'overwrite' | 'skip' | 'error' | 'rename_with_suffix';
+
+/** Options for process_and_save_command (match SaveOptions in processor.rs) */
+export interface SaveOptions {
+  overwrite_policy?: OverwritePolicy;
+  allow_in_place?: boolean;
+}
+
 export interface MergeResult {
   unique_colors_before: number;
   unique_colors_after: number;
   clusters_created: number;
+  warnings?: string[];
 }
 
 // Utility to convert hex to RGBA tuple
@@ -179,12 +190,19 @@ export interface ImageVersion {
   created: string;
 }
 
+export interface HistoryEntry {
+  timestamp: string;
+  operation: string;
+  summary: string;
+}
+
 export interface SourceState {
   hash: string;
   detected_type: SourceType;
   detected_scale: number | null;
   versions: ImageVersion[];
   current_version: string;
+  history: HistoryEntry[];
 }
 
 export interface GlobalSettings {